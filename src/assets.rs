@@ -0,0 +1,173 @@
+use std::{
+    borrow::Cow,
+    fs, io,
+    path::{Path, PathBuf},
+};
+use tracing::warn;
+
+/// 通过 `--error-page` 指定的错误页面，启动时读入内存
+#[derive(Debug, Clone)]
+pub struct ErrorPage {
+    pub content_type: String,
+    pub body: Vec<u8>,
+}
+
+impl ErrorPage {
+    pub fn load(path: &Path, content_type: &str) -> io::Result<ErrorPage> {
+        Ok(ErrorPage {
+            content_type: content_type.to_string(),
+            body: fs::read(path)?,
+        })
+    }
+}
+
+/// 解析 `--error-page` 的 `status=path`，状态码必须是 4xx 或 5xx
+pub fn parse_error_page(value: &str) -> Result<(u16, PathBuf), String> {
+    let (status, path) = value
+        .split_once('=')
+        .ok_or_else(|| format!("expected STATUS=PATH, got `{}`", value))?;
+    let status = status
+        .trim()
+        .parse::<u16>()
+        .ok()
+        .filter(|status| (400..=599).contains(status))
+        .ok_or_else(|| format!("invalid error status `{}`", status))?;
+    if path.is_empty() {
+        return Err(String::from("missing error page path"));
+    }
+
+    Ok((status, PathBuf::from(path)))
+}
+
+/// 编译时嵌入的默认错误页面，根目录下没有对应页面时使用
+const ERROR_PAGES: &[(u16, &str)] = &[
+    (400, include_str!("../static/400.html")),
+    (401, include_str!("../static/401.html")),
+    (403, include_str!("../static/403.html")),
+    (404, include_str!("../static/404.html")),
+    (405, include_str!("../static/405.html")),
+    (408, include_str!("../static/408.html")),
+    (413, include_str!("../static/413.html")),
+    (415, include_str!("../static/415.html")),
+    (429, include_str!("../static/429.html")),
+    (431, include_str!("../static/431.html")),
+    (500, include_str!("../static/500.html")),
+    (501, include_str!("../static/501.html")),
+    (502, include_str!("../static/502.html")),
+    (503, include_str!("../static/503.html")),
+    (505, include_str!("../static/505.html")),
+];
+
+/// 编译时嵌入的 data 目录下的文件，按相对于工作目录的路径查找
+const DATA_FILES: &[(&str, &[u8])] = &[
+    ("data/data.txt", include_bytes!("../data/data.txt")),
+    ("data/error.json", include_bytes!("../data/error.json")),
+    ("data/error.txt", include_bytes!("../data/error.txt")),
+    (
+        "data/not_found.json",
+        include_bytes!("../data/not_found.json"),
+    ),
+];
+
+/// 根目录下的 `{status}.html`，不存在时使用嵌入的默认页面
+pub fn error_page(root: &Path, status: u16) -> Option<Cow<'static, [u8]>> {
+    let path = root.join(format!("{}.html", status));
+    if let Some(contents) = read_if_exists(&path) {
+        return Some(Cow::Owned(contents));
+    }
+
+    ERROR_PAGES
+        .iter()
+        .find(|(code, _)| *code == status)
+        .map(|(_, page)| Cow::Borrowed(page.as_bytes()))
+}
+
+/// 工作目录下的数据文件，不存在时使用嵌入的版本
+pub fn data_file(path: &str) -> Cow<'static, [u8]> {
+    data_file_in(Path::new(""), path)
+}
+
+/// `dir` 下的数据文件，`path` 同时是查找嵌入版本的名字
+fn data_file_in(dir: &Path, path: &str) -> Cow<'static, [u8]> {
+    if let Some(contents) = read_if_exists(&dir.join(path)) {
+        return Cow::Owned(contents);
+    }
+
+    DATA_FILES
+        .iter()
+        .find(|(name, _)| *name == path)
+        .map(|(_, contents)| Cow::Borrowed(*contents))
+        .unwrap_or_default()
+}
+
+/// 文件存在且可读时返回其内容，无法读取时另外记录下来
+fn read_if_exists(path: &Path) -> Option<Vec<u8>> {
+    match fs::read(path) {
+        Ok(contents) => Some(contents),
+        Err(err) => {
+            if err.kind() != io::ErrorKind::NotFound {
+                warn!(path = %path.display(), error = %err, "cannot read file, using the built-in copy");
+            }
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn error_pages_on_disk_replace_the_embedded_ones() {
+        let root = TempDir::new().unwrap();
+        let embedded = include_bytes!("../static/404.html");
+        assert_eq!(error_page(root.path(), 404).unwrap(), &embedded[..]);
+
+        fs::write(root.path().join("404.html"), "custom 404").unwrap();
+        assert_eq!(error_page(root.path(), 404).unwrap(), &b"custom 404"[..]);
+        // 其他状态码仍然使用嵌入的页面
+        assert_eq!(
+            error_page(root.path(), 500).unwrap(),
+            include_str!("../static/500.html").as_bytes()
+        );
+    }
+
+    #[test]
+    fn statuses_without_an_embedded_page_need_a_file() {
+        let root = TempDir::new().unwrap();
+        assert_eq!(error_page(root.path(), 418), None);
+
+        fs::write(root.path().join("418.html"), "teapot").unwrap();
+        assert_eq!(error_page(root.path(), 418).unwrap(), &b"teapot"[..]);
+    }
+
+    #[test]
+    fn unreadable_files_fall_back_to_the_embedded_copy() {
+        let root = TempDir::new().unwrap();
+        fs::create_dir(root.path().join("403.html")).unwrap();
+        assert_eq!(
+            error_page(root.path(), 403).unwrap(),
+            include_str!("../static/403.html").as_bytes()
+        );
+    }
+
+    #[test]
+    fn data_files_on_disk_replace_the_embedded_ones() {
+        let dir = TempDir::new().unwrap();
+        assert_eq!(
+            data_file_in(dir.path(), "data/error.json"),
+            &include_bytes!("../data/error.json")[..]
+        );
+        assert!(data_file_in(dir.path(), "data/missing.json").is_empty());
+
+        fs::create_dir(dir.path().join("data")).unwrap();
+        fs::write(dir.path().join("data/error.json"), r#"{"error":"custom"}"#).unwrap();
+        fs::write(dir.path().join("data/missing.json"), "[]").unwrap();
+        assert_eq!(
+            data_file_in(dir.path(), "data/error.json"),
+            &br#"{"error":"custom"}"#[..]
+        );
+        assert_eq!(data_file_in(dir.path(), "data/missing.json"), &b"[]"[..]);
+    }
+}
@@ -0,0 +1,208 @@
+use crate::{url, Request};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use std::{collections::HashMap, error::Error, fmt, fs, io, path::Path};
+
+/// 默认的认证域
+pub const DEFAULT_REALM: &str = "http-server";
+
+/// 加载认证文件失败的原因
+#[derive(Debug)]
+pub enum AuthError {
+    Io(io::Error),
+    /// 行号从 1 开始
+    InvalidLine(usize),
+}
+
+impl fmt::Display for AuthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuthError::Io(err) => write!(f, "{}", err),
+            AuthError::InvalidLine(line) => {
+                write!(
+                    f,
+                    "Invalid auth file line {}: expected `user:bcrypt-hash`",
+                    line
+                )
+            }
+        }
+    }
+}
+
+impl Error for AuthError {}
+
+impl From<io::Error> for AuthError {
+    fn from(err: io::Error) -> Self {
+        AuthError::Io(err)
+    }
+}
+
+/// HTTP Basic 认证，保护路径以 `prefix` 开头的请求
+#[derive(Debug, Clone)]
+pub struct BasicAuth {
+    pub realm: String,
+    pub prefix: String,
+    /// 用户名到 bcrypt 哈希的映射
+    users: HashMap<String, String>,
+}
+
+impl BasicAuth {
+    pub fn new(users: HashMap<String, String>, realm: &str, prefix: &str) -> BasicAuth {
+        BasicAuth {
+            realm: realm.to_string(),
+            prefix: prefix.trim_end_matches('/').to_string(),
+            users,
+        }
+    }
+
+    /// 从每行一个 `user:bcrypt-hash` 的文件加载用户，忽略空行和 `#` 开头的注释
+    pub fn load(path: &Path, realm: &str, prefix: &str) -> Result<BasicAuth, AuthError> {
+        let mut users = HashMap::new();
+        for (i, line) in fs::read_to_string(path)?.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            match line.split_once(':') {
+                Some((user, hash)) if !user.is_empty() && !hash.is_empty() => {
+                    users.insert(user.to_string(), hash.to_string());
+                }
+                _ => return Err(AuthError::InvalidLine(i + 1)),
+            }
+        }
+
+        Ok(BasicAuth::new(users, realm, prefix))
+    }
+
+    /// 路径等于前缀或者位于前缀目录之下
+    pub fn protects(&self, path: &str) -> bool {
+        url::is_under_prefix(&self.prefix, path)
+    }
+
+    /// 请求的 `Authorization: Basic` 凭据是否有效，缺少请求头或格式错误都视为无效
+    pub fn authorize(&self, request: &Request) -> bool {
+        let Some((user, password)) = request.header("authorization").and_then(credentials) else {
+            return false;
+        };
+
+        self.users
+            .get(&user)
+            .is_some_and(|hash| bcrypt::verify(password, hash).unwrap_or(false))
+    }
+
+    /// 401 响应的 `WWW-Authenticate` 取值
+    pub fn challenge(&self) -> String {
+        format!("Basic realm=\"{}\"", self.realm.replace('"', "\\\""))
+    }
+}
+
+/// 解码 `Basic <base64(user:password)>`
+fn credentials(authorization: &str) -> Option<(String, String)> {
+    let (scheme, encoded) = authorization.trim().split_once(' ')?;
+    if !scheme.eq_ignore_ascii_case("basic") {
+        return None;
+    }
+
+    let decoded = String::from_utf8(STANDARD.decode(encoded.trim()).ok()?).ok()?;
+    let (user, password) = decoded.split_once(':')?;
+    Some((user.to_string(), password.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn basic_auth(prefix: &str) -> BasicAuth {
+        let hash = bcrypt::hash("secret", 4).unwrap();
+        BasicAuth::new(
+            HashMap::from([(String::from("alice"), hash)]),
+            DEFAULT_REALM,
+            prefix,
+        )
+    }
+
+    fn request(authorization: Option<&str>) -> Request {
+        let header = authorization
+            .map(|value| format!("Authorization: {}\r\n", value))
+            .unwrap_or_default();
+        let raw = format!("GET /admin HTTP/1.1\r\n{}\r\n", header);
+        Request::from_bytes(raw.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn accepts_correct_credentials() {
+        // alice:secret
+        assert!(basic_auth("/").authorize(&request(Some("Basic YWxpY2U6c2VjcmV0"))));
+        assert!(basic_auth("/").authorize(&request(Some("basic  YWxpY2U6c2VjcmV0 "))));
+    }
+
+    #[test]
+    fn rejects_wrong_or_missing_credentials() {
+        let auth = basic_auth("/");
+        let cases = [
+            None,
+            // alice:wrong
+            Some("Basic YWxpY2U6d3Jvbmc="),
+            // bob:secret
+            Some("Basic Ym9iOnNlY3JldA=="),
+            Some("Basic not*base64"),
+            // 没有冒号的 alicesecret
+            Some("Basic YWxpY2VzZWNyZXQ="),
+            Some("Bearer YWxpY2U6c2VjcmV0"),
+            Some("Basic"),
+        ];
+        for authorization in cases {
+            assert!(
+                !auth.authorize(&request(authorization)),
+                "{:?}",
+                authorization
+            );
+        }
+    }
+
+    #[test]
+    fn protects_the_prefix_and_below() {
+        let auth = basic_auth("/admin/");
+        assert!(auth.protects("/admin"));
+        assert!(auth.protects("/admin/secret.txt"));
+        assert!(!auth.protects("/administrator"));
+        assert!(!auth.protects("/public/admin"));
+        assert!(basic_auth("/").protects("/anything"));
+    }
+
+    #[test]
+    fn protects_non_normalized_paths() {
+        let auth = basic_auth("/admin");
+        for path in [
+            "//admin/secret.txt",
+            "/./admin/secret.txt",
+            "/x/../admin/secret.txt",
+            "/admin/../admin/secret.txt",
+            "/../admin",
+        ] {
+            assert!(auth.protects(path), "{}", path);
+        }
+        assert!(!auth.protects("/admin/../public.txt"));
+    }
+
+    #[test]
+    fn challenge_escapes_the_realm() {
+        let auth = BasicAuth::new(HashMap::new(), "say \"hi\"", "/");
+        assert_eq!(auth.challenge(), "Basic realm=\"say \\\"hi\\\"\"");
+    }
+
+    #[test]
+    fn loads_users_and_reports_bad_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("users");
+
+        fs::write(&path, "# comment\n\nalice:$2b$04$hash\n").unwrap();
+        let auth = BasicAuth::load(&path, DEFAULT_REALM, "/").unwrap();
+        assert_eq!(auth.users.len(), 1);
+
+        fs::write(&path, "alice:$2b$04$hash\nbob\n").unwrap();
+        assert!(matches!(
+            BasicAuth::load(&path, DEFAULT_REALM, "/"),
+            Err(AuthError::InvalidLine(2))
+        ));
+    }
+}
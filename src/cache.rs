@@ -0,0 +1,264 @@
+use crate::{datetime, Request};
+use std::{
+    fs::{self, Metadata},
+    io,
+    path::Path,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// 根据文件的修改时间和大小生成强 ETag
+pub fn file_etag(path: &Path) -> Result<String, io::Error> {
+    Ok(etag_from_metadata(&fs::metadata(path)?))
+}
+
+/// 与 [`file_etag`] 相同，用于已经取得元数据的文件
+pub fn etag_from_metadata(metadata: &Metadata) -> String {
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|duration| duration.as_nanos())
+        .unwrap_or(0);
+
+    format!("\"{:x}-{:x}\"", modified, metadata.len())
+}
+
+/// 压缩后的表示使用的 ETag，加上编码后缀，保证不同编码的表示不会共用同一个 ETag；弱 ETag 保留 `W/` 前缀
+pub fn encoded_etag(etag: &str, encoding: &str) -> String {
+    let (weak, tag) = match etag.strip_prefix("W/") {
+        Some(tag) => ("W/", tag),
+        None => ("", etag),
+    };
+    format!("{}\"{}-{}\"", weak, tag.trim_matches('"'), encoding)
+}
+
+/// 客户端缓存的副本是否仍然有效，有效时应返回 304
+///
+/// 有 `If-None-Match` 时只比较 ETag，忽略 `If-Modified-Since`
+pub fn is_not_modified(request: &Request, etag: &str, modified: Option<SystemTime>) -> bool {
+    if let Some(if_none_match) = request.header("if-none-match") {
+        return if_none_match.split(',').any(|tag| {
+            let tag = tag.trim();
+            tag == "*" || weak_eq(tag, etag)
+        });
+    }
+
+    match (request.header("if-modified-since"), modified) {
+        (Some(since), Some(modified)) => match datetime::parse_http_date(since) {
+            // HTTP 日期只精确到秒
+            Ok(since) => truncate_to_secs(modified) <= since,
+            Err(_) => false,
+        },
+        _ => false,
+    }
+}
+
+/// 弱比较，忽略 `W/` 前缀
+fn weak_eq(a: &str, b: &str) -> bool {
+    a.trim_start_matches("W/") == b.trim_start_matches("W/")
+}
+
+fn truncate_to_secs(time: SystemTime) -> SystemTime {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(duration) => UNIX_EPOCH + Duration::from_secs(duration.as_secs()),
+        Err(_) => time,
+    }
+}
+
+/// 一条 `Cache-Control` 规则，`pattern` 匹配请求路径
+#[derive(Debug, Clone, PartialEq)]
+pub struct CacheRule {
+    pub pattern: String,
+    pub value: String,
+}
+
+/// 解析 `pattern=value` 形式的规则，用于 `--cache-rule` 参数，只在第一个 `=` 处分割
+pub fn parse_cache_rule(spec: &str) -> Result<CacheRule, String> {
+    match spec.split_once('=') {
+        Some((pattern, value)) if !pattern.trim().is_empty() && !value.trim().is_empty() => {
+            Ok(CacheRule {
+                pattern: pattern.trim().to_string(),
+                value: value.trim().to_string(),
+            })
+        }
+        _ => Err(format!(
+            "Invalid cache rule `{}`, expected pattern=cache-control",
+            spec
+        )),
+    }
+}
+
+/// 按顺序找到第一条匹配路径的规则，返回对应的 `Cache-Control` 取值
+///
+/// 含有 `/` 的模式匹配完整路径，否则只匹配文件名；`*` 匹配任意字符，`?` 匹配单个字符
+pub fn cache_control<'a>(rules: &'a [CacheRule], path: &str) -> Option<&'a str> {
+    let file_name = path.rsplit('/').next().unwrap_or(path);
+
+    rules
+        .iter()
+        .find(|rule| {
+            let target = if rule.pattern.contains('/') {
+                path
+            } else {
+                file_name
+            };
+            glob_match(rule.pattern.as_bytes(), target.as_bytes())
+        })
+        .map(|rule| rule.value.as_str())
+}
+
+fn glob_match(pattern: &[u8], text: &[u8]) -> bool {
+    let (mut p, mut t) = (0, 0);
+    // 最近一次 `*` 的位置以及它当时对应的文本位置，用于回溯
+    let mut star: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some(b'*') => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == b'?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                Some((star_p, star_t)) => {
+                    p = star_p + 1;
+                    t = star_t + 1;
+                    star = Some((star_p, star_t + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == b'*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn request(headers: &[&str]) -> Request {
+        let raw = format!("GET / HTTP/1.1\r\n{}\r\n\r\n", headers.join("\r\n"));
+        Request::from_bytes(raw.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn etag_changes_with_size_and_mtime() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.txt");
+        fs::write(&path, "one").unwrap();
+        let first = file_etag(&path).unwrap();
+        assert!(first.starts_with('"') && first.ends_with("-3\""));
+
+        fs::write(&path, "three").unwrap();
+        let second = file_etag(&path).unwrap();
+        assert_ne!(first, second);
+
+        let file = fs::File::options().write(true).open(&path).unwrap();
+        file.set_modified(UNIX_EPOCH + Duration::from_secs(1))
+            .unwrap();
+        assert_eq!(file_etag(&path).unwrap(), "\"3b9aca00-5\"");
+        assert!(file_etag(&dir.path().join("missing")).is_err());
+    }
+
+    #[test]
+    fn encoded_etag_keeps_a_single_pair_of_quotes() {
+        assert_eq!(encoded_etag("\"abc-3\"", "gzip"), "\"abc-3-gzip\"");
+        assert_eq!(encoded_etag("W/\"abc\"", "br"), "W/\"abc-br\"");
+    }
+
+    #[test]
+    fn if_none_match_compares_weakly() {
+        let etag = "\"abc\"";
+        assert!(is_not_modified(
+            &request(&["If-None-Match: \"abc\""]),
+            etag,
+            None
+        ));
+        assert!(is_not_modified(
+            &request(&["If-None-Match: W/\"abc\""]),
+            etag,
+            None
+        ));
+        assert!(is_not_modified(
+            &request(&["If-None-Match: \"x\", \"abc\""]),
+            etag,
+            None
+        ));
+        assert!(is_not_modified(&request(&["If-None-Match: *"]), etag, None));
+        assert!(!is_not_modified(
+            &request(&["If-None-Match: \"abcd\""]),
+            etag,
+            None
+        ));
+        assert!(!is_not_modified(&request(&[]), etag, None));
+    }
+
+    #[test]
+    fn if_modified_since_is_ignored_when_if_none_match_is_present() {
+        let modified = Some(UNIX_EPOCH);
+        let request = request(&[
+            "If-None-Match: \"other\"",
+            "If-Modified-Since: Thu, 01 Jan 1970 00:00:10 GMT",
+        ]);
+        assert!(!is_not_modified(&request, "\"abc\"", modified));
+    }
+
+    #[test]
+    fn if_modified_since_has_second_precision() {
+        let modified = UNIX_EPOCH + Duration::from_millis(10_500);
+        let at = |date: &str| request(&[&format!("If-Modified-Since: {}", date)]);
+
+        assert!(is_not_modified(
+            &at("Thu, 01 Jan 1970 00:00:10 GMT"),
+            "\"e\"",
+            Some(modified)
+        ));
+        assert!(!is_not_modified(
+            &at("Thu, 01 Jan 1970 00:00:09 GMT"),
+            "\"e\"",
+            Some(modified)
+        ));
+        assert!(!is_not_modified(&at("yesterday"), "\"e\"", Some(modified)));
+        assert!(!is_not_modified(
+            &at("Thu, 01 Jan 1970 00:00:10 GMT"),
+            "\"e\"",
+            None
+        ));
+    }
+
+    #[test]
+    fn cache_rules_match_in_order() {
+        let rules: Vec<CacheRule> = [
+            "/assets/*=max-age=31536000",
+            "*.html=no-cache",
+            "*=max-age=60",
+        ]
+        .into_iter()
+        .map(|spec| parse_cache_rule(spec).unwrap())
+        .collect();
+
+        assert_eq!(rules[0].value, "max-age=31536000");
+        assert_eq!(
+            cache_control(&rules, "/assets/app.js"),
+            Some("max-age=31536000")
+        );
+        assert_eq!(cache_control(&rules, "/docs/index.html"), Some("no-cache"));
+        assert_eq!(cache_control(&rules, "/logo.png"), Some("max-age=60"));
+        assert_eq!(cache_control(&rules[..1], "/logo.png"), None);
+        assert!(parse_cache_rule("no-value=").is_err());
+    }
+
+    #[test]
+    fn glob_supports_star_and_question_mark() {
+        assert!(glob_match(b"a*c", b"abbbc"));
+        assert!(glob_match(b"a?c", b"abc"));
+        assert!(glob_match(b"*", b""));
+        assert!(!glob_match(b"a?c", b"ac"));
+        assert!(!glob_match(b"*.css", b"style.css.map"));
+    }
+}
@@ -0,0 +1,590 @@
+use crate::{
+    assets, cache, mime, parse_proxy_target, Args, BasicAuth, CorsConfig, ErrorPage, JwtAuth,
+    Limits, LogFormat, LogLevel, MimeTypes, RateLimiter, ServerConfig, ServerState, ServerStats,
+    SseBroadcaster, WebSocketHandler, WebSocketRoute, DEFAULT_IP, DEFAULT_PORT, DEFAULT_THREADS,
+};
+use clap::{parser::ValueSource, ArgMatches, CommandFactory, FromArgMatches};
+use serde::Deserialize;
+use std::{
+    env::{self, VarError},
+    error::Error,
+    fmt, fs, io,
+    num::{NonZeroU16, NonZeroU8},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
+
+/// 读取或校验配置文件失败的原因
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(io::Error),
+    Parse(toml::de::Error),
+    /// 取值不合法的配置项和原因
+    Invalid {
+        key: &'static str,
+        message: String,
+    },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(err) => write!(f, "{}", err),
+            ConfigError::Parse(err) => write!(f, "{}", err),
+            ConfigError::Invalid { key, message } => write!(f, "Invalid `{}`: {}", key, message),
+        }
+    }
+}
+
+impl Error for ConfigError {}
+
+impl From<io::Error> for ConfigError {
+    fn from(err: io::Error) -> Self {
+        ConfigError::Io(err)
+    }
+}
+
+impl From<toml::de::Error> for ConfigError {
+    fn from(err: toml::de::Error) -> Self {
+        ConfigError::Parse(err)
+    }
+}
+
+/// TOML 配置文件的内容，键与命令行参数同名（`-` 换成 `_`），都可以省略
+///
+/// `cache_rules`、`mime_types` 和 `error_pages` 是字符串数组，每项的格式与对应的命令行参数相同；
+/// 相对路径和命令行参数一样相对于工作目录
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FileConfig {
+    pub ip: Option<String>,
+    pub port: Option<u16>,
+    pub threads: Option<u8>,
+    pub rate_limit: Option<u32>,
+    pub max_connections: Option<u32>,
+    pub proxy: Option<String>,
+    pub socks_target: Option<String>,
+    pub proxy_timeout: Option<u64>,
+    /// 毫秒，与命令行参数相同
+    pub read_timeout: Option<u64>,
+    /// 毫秒，与命令行参数相同
+    pub write_timeout: Option<u64>,
+    pub keepalive_timeout: Option<u64>,
+    pub max_keepalive_requests: Option<u64>,
+    pub max_header_size: Option<usize>,
+    pub max_request_size: Option<usize>,
+    pub max_body_size: Option<usize>,
+    pub tls_cert: Option<PathBuf>,
+    pub tls_key: Option<PathBuf>,
+    #[serde(rename = "cors_origin", alias = "cors")]
+    pub cors: Option<String>,
+    pub cors_methods: Option<String>,
+    pub server_banner: Option<String>,
+    pub cors_max_age: Option<u64>,
+    pub root: Option<PathBuf>,
+    pub no_index: Option<bool>,
+    pub spa: Option<bool>,
+    pub compress: Option<bool>,
+    pub cache_rules: Option<Vec<String>>,
+    pub mime_types: Option<Vec<String>>,
+    pub error_pages: Option<Vec<String>>,
+    pub upload_dir: Option<PathBuf>,
+    pub log_file: Option<PathBuf>,
+    pub log_format: Option<LogFormat>,
+    pub log_level: Option<LogLevel>,
+    pub no_probe_log: Option<bool>,
+    pub auth_file: Option<PathBuf>,
+    pub auth_realm: Option<String>,
+    pub auth_prefix: Option<String>,
+    pub version_string: Option<String>,
+    pub jwt_secret: Option<String>,
+    pub jwt_audience: Option<String>,
+    pub websocket: Option<String>,
+    pub websocket_idle_timeout: Option<u64>,
+}
+
+impl FileConfig {
+    /// 读取并校验配置文件，未知的键视为错误
+    pub fn from_toml_file(path: &Path) -> Result<FileConfig, ConfigError> {
+        let config: FileConfig = toml::from_str(&fs::read_to_string(path)?)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// 命令行参数的取值范围在这里同样检查
+    fn validate(&self) -> Result<(), ConfigError> {
+        let at_least_one = [
+            ("port", self.port.map(u64::from)),
+            ("threads", self.threads.map(u64::from)),
+            ("rate_limit", self.rate_limit.map(u64::from)),
+            ("max_connections", self.max_connections.map(u64::from)),
+            ("read_timeout", self.read_timeout),
+            ("write_timeout", self.write_timeout),
+            ("max_keepalive_requests", self.max_keepalive_requests),
+            ("websocket_idle_timeout", self.websocket_idle_timeout),
+        ];
+        for (key, value) in at_least_one {
+            if value == Some(0) {
+                return Err(invalid(key, "must be at least 1"));
+            }
+        }
+        Ok(())
+    }
+
+    /// 把配置文件中的取值合并到 `args`，命令行参数和 `HTTPSERVER_*` 环境变量优先，
+    /// 配置文件只替换默认值
+    ///
+    /// 由 [`resolve`] 读取环境变量的几项（`ip`、`port` 等）不在这里合并
+    pub fn merge_into(&self, args: &mut Args, matches: &ArgMatches) -> Result<(), ConfigError> {
+        let explicit = |id: &str| {
+            matches!(
+                matches.value_source(id),
+                Some(ValueSource::CommandLine | ValueSource::EnvVariable)
+            )
+        };
+
+        macro_rules! merge {
+            ($($field:ident),* $(,)?) => {
+                $(
+                    if let Some(value) = &self.$field {
+                        if !explicit(stringify!($field)) {
+                            args.$field = value.clone().into();
+                        }
+                    }
+                )*
+            };
+        }
+        merge!(
+            max_connections,
+            proxy_timeout,
+            read_timeout,
+            write_timeout,
+            keepalive_timeout,
+            max_keepalive_requests,
+            max_header_size,
+            max_request_size,
+            max_body_size,
+            server_banner,
+            cors_max_age,
+            root,
+            no_index,
+            spa,
+            compress,
+            log_format,
+            no_probe_log,
+            auth_realm,
+            auth_prefix,
+            version_string,
+            websocket_idle_timeout,
+            // 以下参数在命令行中也是可选的
+            rate_limit,
+            socks_target,
+            cors,
+            cors_methods,
+            upload_dir,
+            log_file,
+            log_level,
+            auth_file,
+            jwt_audience,
+            websocket,
+        );
+
+        // 列表整体以命令行为准，不与配置文件中的合并；列表没有对应的环境变量
+        if let Some(rules) = self
+            .cache_rules
+            .as_ref()
+            .filter(|_| !explicit("cache_rules"))
+        {
+            args.cache_rules = parse_list("cache_rules", rules, cache::parse_cache_rule)?;
+        }
+        if let Some(mappings) = self.mime_types.as_ref().filter(|_| !explicit("mime_types")) {
+            args.mime_types = parse_list("mime_types", mappings, mime::parse_mapping)?;
+        }
+        if let Some(pages) = self
+            .error_pages
+            .as_ref()
+            .filter(|_| !explicit("error_pages"))
+        {
+            args.error_pages = parse_list("error_pages", pages, assets::parse_error_page)?;
+        }
+        Ok(())
+    }
+}
+
+/// 最终生效的监听地址、线程数、代理和密钥，以及其余的参数
+#[derive(Debug)]
+pub struct ResolvedConfig {
+    pub ip: String,
+    pub port: u16,
+    pub threads: u8,
+    /// 为空时不使用代理
+    pub proxy: String,
+    pub jwt_secret: Option<String>,
+    pub tls_cert: Option<PathBuf>,
+    pub tls_key: Option<PathBuf>,
+    pub args: Args,
+}
+
+/// 按 命令行参数 → `HTTPSERVER_*` 环境变量 → `--config` 指定的配置文件 → 默认值 的顺序
+/// 得到监听地址、线程数、代理、JWT 密钥和 TLS 文件
+///
+/// 这几项的环境变量在这里用 `std::env::var` 读取，不经过 clap，密钥不会出现在 `--help` 中。
+/// 为空的环境变量视为未设置；`file` 是调用方已经读取的配置文件，没有配置文件时传入默认值
+pub fn resolve(args: Args, file: FileConfig) -> Result<ResolvedConfig, ConfigError> {
+    resolve_layers(args, file, |name| env::var(name))
+}
+
+/// [`resolve`] 的实现，`var` 读取环境变量
+fn resolve_layers(
+    mut args: Args,
+    file: FileConfig,
+    var: impl Fn(&str) -> Result<String, VarError>,
+) -> Result<ResolvedConfig, ConfigError> {
+    let config = ResolvedConfig {
+        ip: args
+            .ip
+            .take()
+            .or(env_value(&var, "ip", "HTTPSERVER_IP")?)
+            .or(file.ip)
+            .unwrap_or_else(|| String::from(DEFAULT_IP)),
+        port: args
+            .port
+            .take()
+            .or(env_value(&var, "port", "HTTPSERVER_PORT")?.map(NonZeroU16::get))
+            .or(file.port)
+            .unwrap_or(DEFAULT_PORT),
+        threads: args
+            .threads
+            .take()
+            .or(env_value(&var, "threads", "HTTPSERVER_THREADS")?.map(NonZeroU8::get))
+            .or(file.threads)
+            .unwrap_or(DEFAULT_THREADS),
+        proxy: args
+            .proxy
+            .take()
+            .or(env_value(&var, "proxy", "HTTPSERVER_PROXY")?)
+            .or(file.proxy)
+            .unwrap_or_default(),
+        jwt_secret: args
+            .jwt_secret
+            .take()
+            .or(env_value(&var, "jwt_secret", "HTTPSERVER_JWT_SECRET")?)
+            .or(file.jwt_secret),
+        tls_cert: args
+            .tls_cert
+            .take()
+            .or(env_value(&var, "tls_cert", "HTTPSERVER_TLS_CERT")?)
+            .or(file.tls_cert),
+        tls_key: args
+            .tls_key
+            .take()
+            .or(env_value(&var, "tls_key", "HTTPSERVER_TLS_KEY")?)
+            .or(file.tls_key),
+        args,
+    };
+    check_requires(&config)?;
+    Ok(config)
+}
+
+/// 读取并解析环境变量 `name`，未设置或为空时返回 `None`
+fn env_value<T: FromStr>(
+    var: impl Fn(&str) -> Result<String, VarError>,
+    key: &'static str,
+    name: &str,
+) -> Result<Option<T>, ConfigError> {
+    match var(name) {
+        Ok(value) if value.is_empty() => Ok(None),
+        Ok(value) => value
+            .parse()
+            .map(Some)
+            .map_err(|_| invalid(key, &format!("invalid value {:?} in {}", value, name))),
+        Err(VarError::NotPresent) => Ok(None),
+        Err(VarError::NotUnicode(_)) => Err(invalid(key, &format!("{} is not valid UTF-8", name))),
+    }
+}
+
+impl ServerConfig {
+    /// 只按配置文件构造服务器配置，文件中没有的项使用默认值，不读取命令行参数和环境变量
+    ///
+    /// 监听地址、线程数等不属于 `ServerConfig` 的项同样会被校验
+    pub fn from_toml_file(path: &Path) -> Result<ServerConfig, ConfigError> {
+        let file = FileConfig::from_toml_file(path)?;
+        let matches = Args::command()
+            .mut_args(|arg| arg.env(None))
+            .try_get_matches_from(["http-server"])
+            .expect("default arguments are valid");
+        let mut args = Args::from_arg_matches(&matches).expect("default arguments are valid");
+        file.merge_into(&mut args, &matches)?;
+        ServerConfig::from_resolved(&resolve_layers(args, file, |_| Err(VarError::NotPresent))?)
+    }
+
+    /// 按最终生效的参数构造服务器配置，同时读取认证文件和自定义错误页面
+    pub fn from_resolved(config: &ResolvedConfig) -> Result<ServerConfig, ConfigError> {
+        let args = &config.args;
+
+        let mut mime_types = MimeTypes::default();
+        for (extension, mime_type) in &args.mime_types {
+            mime_types.insert(extension, mime_type);
+        }
+
+        let proxy = if config.proxy.is_empty() {
+            None
+        } else {
+            let target = parse_proxy_target(&config.proxy, args.socks_target.as_deref())
+                .map_err(|err| invalid("proxy", &err.to_string()))?;
+            Some(target)
+        };
+
+        let auth = match &args.auth_file {
+            Some(path) => Some(
+                BasicAuth::load(path, &args.auth_realm, &args.auth_prefix)
+                    .map_err(|err| invalid("auth_file", &format!("{}: {}", path.display(), err)))?,
+            ),
+            None => None,
+        };
+
+        let error_pages = args
+            .error_pages
+            .iter()
+            .map(|(status, path)| {
+                let content_type = mime_types.lookup(&path.to_string_lossy());
+                ErrorPage::load(path, content_type)
+                    .map(|page| (*status, page))
+                    .map_err(|err| invalid("error_pages", &format!("{}: {}", path.display(), err)))
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(ServerConfig {
+            root: args.root.clone(),
+            listing: !args.no_index,
+            spa: args.spa,
+            compress: args.compress,
+            limits: Limits {
+                max_header_size: args.max_header_size,
+                max_request_size: args.max_request_size,
+                max_body_size: args.max_body_size,
+            },
+            cors: args.cors.as_deref().map(|spec| {
+                let cors = CorsConfig {
+                    max_age: args.cors_max_age,
+                    ..CorsConfig::parse(spec)
+                };
+                match &args.cors_methods {
+                    Some(methods) => cors.with_methods(methods),
+                    None => cors,
+                }
+            }),
+            proxy,
+            proxy_timeout: Duration::from_secs(args.proxy_timeout),
+            read_timeout: Duration::from_millis(args.read_timeout),
+            keepalive_timeout: Duration::from_secs(args.keepalive_timeout),
+            max_keepalive_requests: args.max_keepalive_requests,
+            cache_rules: args.cache_rules.clone(),
+            server_banner: args.server_banner.clone(),
+            upload_dir: args.upload_dir.clone().unwrap_or_else(env::temp_dir),
+            auth,
+            jwt: config
+                .jwt_secret
+                .as_deref()
+                .map(|secret| JwtAuth::new(secret, args.jwt_audience.as_deref())),
+            metrics: ServerStats::default(),
+            state: Arc::new(ServerState::new(&args.version_string)),
+            events: SseBroadcaster::new(),
+            websocket: args.websocket.as_ref().map(|path| WebSocketRoute {
+                path: path.clone(),
+                handler: WebSocketHandler::new(|message| Some(message.to_string())),
+                idle_timeout: Duration::from_secs(args.websocket_idle_timeout),
+            }),
+            error_pages,
+            mime_types,
+            log_probes: !args.no_probe_log,
+            rate_limiter: args
+                .rate_limit
+                .map(|limit| Arc::new(RateLimiter::new(limit))),
+        })
+    }
+}
+
+/// 合并后再检查参数之间的依赖，它们可能分别来自命令行、环境变量和配置文件
+fn check_requires(config: &ResolvedConfig) -> Result<(), ConfigError> {
+    let args = &config.args;
+    if config.tls_cert.is_some() != config.tls_key.is_some() {
+        return Err(invalid(
+            "tls_cert",
+            "tls_cert and tls_key must be set together",
+        ));
+    }
+    if args.socks_target.is_some() && !config.proxy.starts_with("socks5://") {
+        return Err(invalid("socks_target", "requires a socks5:// proxy"));
+    }
+    if args.cors_methods.is_some() && args.cors.is_none() {
+        return Err(invalid("cors_methods", "requires cors_origin"));
+    }
+    if args.jwt_audience.is_some() && config.jwt_secret.is_none() {
+        return Err(invalid("jwt_audience", "requires jwt_secret"));
+    }
+    Ok(())
+}
+
+fn parse_list<T>(
+    key: &'static str,
+    values: &[String],
+    parse: impl Fn(&str) -> Result<T, String>,
+) -> Result<Vec<T>, ConfigError> {
+    values
+        .iter()
+        .map(|value| parse(value).map_err(|message| invalid(key, &message)))
+        .collect()
+}
+
+fn invalid(key: &'static str, message: &str) -> ConfigError {
+    ConfigError::Invalid {
+        key,
+        message: message.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DEFAULT_READ_TIMEOUT;
+    use tempfile::TempDir;
+
+    fn parse(args: &[&str]) -> Result<(Args, ArgMatches), clap::Error> {
+        let matches = Args::command()
+            .try_get_matches_from(std::iter::once("http-server").chain(args.iter().copied()))?;
+        let args = Args::from_arg_matches(&matches)?;
+        Ok((args, matches))
+    }
+
+    /// 把 `content` 写入临时目录中的配置文件
+    fn config_file(content: &str) -> (TempDir, PathBuf) {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("server.toml");
+        fs::write(&path, content).unwrap();
+        (dir, path)
+    }
+
+    #[test]
+    fn port_is_a_non_zero_u16() {
+        assert_eq!(parse(&[]).unwrap().0.port, None);
+        assert_eq!(parse(&["-p", "65535"]).unwrap().0.port, Some(65535));
+        for port in ["0", "65536", "http", "-1"] {
+            assert!(parse(&["-p", port]).is_err(), "{}", port);
+        }
+    }
+
+    #[test]
+    fn file_port_fills_the_default_only() {
+        let (_dir, path) = config_file("port = 9000");
+        let path = path.to_str().unwrap();
+
+        let file = || FileConfig::from_toml_file(Path::new(path)).unwrap();
+        let (args, _) = parse(&["--config", path]).unwrap();
+        assert_eq!(resolve(args, file()).unwrap().port, 9000);
+        let (args, _) = parse(&["--config", path, "-p", "7000"]).unwrap();
+        assert_eq!(resolve(args, file()).unwrap().port, 7000);
+
+        let file: FileConfig = toml::from_str("port = 0").unwrap();
+        assert!(file.validate().is_err());
+        assert!(toml::from_str::<FileConfig>("port = 70000").is_err());
+    }
+
+    #[test]
+    fn cli_flags_override_the_file() {
+        let (_dir, path) = config_file(
+            "root = \"public\"\nmax_connections = 10\ncompress = true\n\
+             cache_rules = [\"*.css=3600\"]\nlog_format = \"json\"\n",
+        );
+        let (mut args, matches) = parse(&[
+            "--root",
+            "site",
+            "--max-connections",
+            "20",
+            "--cache-rule",
+            "*.js=60",
+        ])
+        .unwrap();
+        FileConfig::from_toml_file(&path)
+            .unwrap()
+            .merge_into(&mut args, &matches)
+            .unwrap();
+
+        assert_eq!(args.root, PathBuf::from("site"));
+        assert_eq!(args.max_connections, 20);
+        assert_eq!(args.cache_rules.len(), 1);
+        assert_eq!(args.cache_rules[0].pattern, "*.js");
+        // 命令行中没有出现的项取自配置文件
+        assert!(args.compress);
+        assert_eq!(args.log_format, LogFormat::Json);
+    }
+
+    #[test]
+    fn server_config_is_built_from_the_file() {
+        let (_dir, path) = config_file(
+            "root = \"public\"\nspa = true\nproxy_timeout = 3\nserver_banner = \"\"\n\
+             cors_origin = \"https://app.example\"\nmax_body_size = 1024\n",
+        );
+        let config = ServerConfig::from_toml_file(&path).unwrap();
+        assert_eq!(config.root, PathBuf::from("public"));
+        assert!(config.spa);
+        assert_eq!(config.proxy_timeout, Duration::from_secs(3));
+        assert_eq!(config.server_banner, "");
+        assert_eq!(config.limits.max_body_size, 1024);
+        assert!(config.cors.is_some());
+        // 文件中没有的项使用默认值
+        assert!(config.listing);
+        assert_eq!(
+            config.read_timeout,
+            Duration::from_millis(DEFAULT_READ_TIMEOUT)
+        );
+        assert!(config.proxy.is_none());
+    }
+
+    #[test]
+    fn invalid_values_are_rejected_with_their_key() {
+        for (content, expected) in [
+            ("threads = 0", "threads"),
+            ("port = 0", "port"),
+            ("max_connections = 0", "max_connections"),
+            ("read_timeout = 0", "read_timeout"),
+            ("tls_cert = \"cert.pem\"", "tls_cert"),
+            ("jwt_audience = \"api\"", "jwt_audience"),
+            ("proxy = \"ftp://x\"", "proxy"),
+            ("proxy = \"socks5://127.0.0.1\"", "proxy"),
+            ("socks_target = \"example.com:80\"", "socks_target"),
+            ("cache_rules = [\"*.css\"]", "cache_rules"),
+        ] {
+            let (_dir, path) = config_file(content);
+            match ServerConfig::from_toml_file(&path).err() {
+                Some(ConfigError::Invalid { key, .. }) => assert_eq!(key, expected),
+                other => panic!("{}: {:?}", content, other),
+            }
+        }
+    }
+
+    #[test]
+    fn unknown_keys_and_wrong_types_are_rejected() {
+        for (content, message) in [
+            ("prot = 8080", "unknown field `prot`"),
+            ("threads = 300", "threads"),
+            ("spa = \"yes\"", "spa"),
+        ] {
+            let (_dir, path) = config_file(content);
+            match ServerConfig::from_toml_file(&path).err() {
+                Some(ConfigError::Parse(err)) => {
+                    assert!(err.to_string().contains(message), "{}", err)
+                }
+                other => panic!("{}: {:?}", content, other),
+            }
+        }
+
+        let missing = Path::new("/nonexistent/server.toml");
+        assert!(matches!(
+            ServerConfig::from_toml_file(missing).err(),
+            Some(ConfigError::Io(_))
+        ));
+    }
+}
@@ -0,0 +1,136 @@
+use crate::{Request, Response};
+
+/// 允许跨域访问的来源
+#[derive(Debug, Clone, PartialEq)]
+pub enum AllowedOrigins {
+    Any,
+    List(Vec<String>),
+}
+
+/// 跨域资源共享（CORS）配置
+#[derive(Debug, Clone, PartialEq)]
+pub struct CorsConfig {
+    pub origins: AllowedOrigins,
+    /// 允许的请求方法，为空时预检响应使用路由表中该路径的方法
+    pub methods: Vec<String>,
+    /// 预检结果的缓存时间（秒）
+    pub max_age: u64,
+}
+
+/// 预检结果的默认缓存时间（秒）
+pub const DEFAULT_MAX_AGE: u64 = 600;
+
+impl CorsConfig {
+    /// 解析 `*` 或以逗号分隔的来源列表
+    pub fn parse(spec: &str) -> CorsConfig {
+        let origins = if spec.trim() == "*" {
+            AllowedOrigins::Any
+        } else {
+            AllowedOrigins::List(
+                spec.split(',')
+                    .map(|origin| origin.trim().to_string())
+                    .filter(|origin| !origin.is_empty())
+                    .collect(),
+            )
+        };
+
+        CorsConfig {
+            origins,
+            methods: Vec::new(),
+            max_age: DEFAULT_MAX_AGE,
+        }
+    }
+
+    /// 设置允许的请求方法，`methods` 是以逗号分隔的列表
+    pub fn with_methods(mut self, methods: &str) -> CorsConfig {
+        self.methods = methods
+            .split(',')
+            .map(|method| method.trim().to_ascii_uppercase())
+            .filter(|method| !method.is_empty())
+            .collect();
+        self
+    }
+
+    /// 来源被允许时返回 `Access-Control-Allow-Origin` 的取值
+    pub fn allow_origin(&self, origin: &str) -> Option<&str> {
+        match &self.origins {
+            AllowedOrigins::Any => Some("*"),
+            AllowedOrigins::List(list) => list
+                .iter()
+                .find(|allowed| allowed.as_str() == origin)
+                .map(|allowed| allowed.as_str()),
+        }
+    }
+}
+
+/// 为 OPTIONS 预检请求加上 CORS 响应头，不是预检请求或来源不被允许时原样返回
+pub fn preflight(
+    mut response: Response,
+    request: &Request,
+    allowed_methods: &[String],
+    config: &CorsConfig,
+) -> Response {
+    let (Some(origin), Some(_)) = (
+        request.header("origin"),
+        request.header("access-control-request-method"),
+    ) else {
+        return response;
+    };
+
+    let Some(allow_origin) = config.allow_origin(origin) else {
+        return response;
+    };
+
+    let methods = if config.methods.is_empty() {
+        allowed_methods
+    } else {
+        &config.methods
+    };
+
+    response = response
+        .header("Access-Control-Allow-Origin", allow_origin)
+        .header("Access-Control-Allow-Methods", &methods.join(", "))
+        .header("Access-Control-Max-Age", &config.max_age.to_string());
+
+    if let Some(headers) = request.header("access-control-request-headers") {
+        response = response.header("Access-Control-Allow-Headers", headers);
+    }
+    if allow_origin != "*" {
+        response = response.header("Vary", "Origin");
+    }
+
+    response
+}
+
+/// 为普通的跨域请求加上 CORS 响应头，请求没有 `Origin`、来源不被允许或响应已经处理过时不做修改
+pub fn apply(response: &mut Response, request: &Request, config: &CorsConfig) {
+    let Some(origin) = request.header("origin") else {
+        return;
+    };
+    if response
+        .headers
+        .iter()
+        .any(|(name, _)| name.eq_ignore_ascii_case("Access-Control-Allow-Origin"))
+    {
+        return;
+    }
+    let Some(allow_origin) = config.allow_origin(origin) else {
+        return;
+    };
+
+    response.headers.push((
+        String::from("Access-Control-Allow-Origin"),
+        allow_origin.to_string(),
+    ));
+    if !config.methods.is_empty() {
+        response.headers.push((
+            String::from("Access-Control-Allow-Methods"),
+            config.methods.join(", "),
+        ));
+    }
+    if allow_origin != "*" {
+        response
+            .headers
+            .push((String::from("Vary"), String::from("Origin")));
+    }
+}
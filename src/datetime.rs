@@ -0,0 +1,323 @@
+use std::{
+    error::Error,
+    fmt,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+const WEEKDAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+const LONG_WEEKDAYS: [&str; 7] = [
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+    "Thursday",
+    "Friday",
+    "Saturday",
+    "Sunday",
+];
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// HTTP 日期格式错误
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError(String);
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid HTTP date: {}", self.0)
+    }
+}
+
+impl Error for ParseError {}
+
+/// 格式化为 RFC 7231 的 IMF-fixdate，例如 `Tue, 15 Nov 1994 08:12:31 GMT`
+///
+/// 早于 1970 年的时间按 1970-01-01 处理
+pub fn format_http_date(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0);
+    let days = (secs / 86400) as i64;
+    let rem = secs % 86400;
+    let (year, month, day) = civil_from_days(days);
+    // 1970-01-01 是星期四
+    let weekday = (days + 3).rem_euclid(7) as usize;
+
+    format!(
+        "{}, {:02} {} {:04} {:02}:{:02}:{:02} GMT",
+        WEEKDAYS[weekday],
+        day,
+        MONTHS[month as usize - 1],
+        year,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
+
+/// 格式化为访问日志使用的时间，例如 `10/Oct/2000:13:55:36 +0000`，固定使用 UTC
+pub fn format_log_date(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0);
+    let rem = secs % 86400;
+    let (year, month, day) = civil_from_days((secs / 86400) as i64);
+
+    format!(
+        "{:02}/{}/{:04}:{:02}:{:02}:{:02} +0000",
+        day,
+        MONTHS[month as usize - 1],
+        year,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
+
+/// 格式化为 RFC 3339 的 UTC 时间，精确到毫秒，例如 `2000-10-10T13:55:36.123Z`
+pub fn format_rfc3339(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs();
+    let rem = secs % 86400;
+    let (year, month, day) = civil_from_days((secs / 86400) as i64);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+/// 当前时间的 HTTP 日期，用于 `Date` 响应头
+pub fn now_as_http_date() -> String {
+    format_http_date(SystemTime::now())
+}
+
+/// 解析 HTTP 日期，接受 IMF-fixdate 以及规范要求兼容的 RFC 850 和 asctime 格式
+pub fn parse_http_date(s: &str) -> Result<SystemTime, ParseError> {
+    let error = || ParseError(s.to_string());
+    let parts: Vec<&str> = s.split_whitespace().collect();
+
+    let (day, month, year, time) = match parts.as_slice() {
+        // Sun, 06 Nov 1994 08:49:37 GMT
+        [weekday, day, month, year, time, "GMT"]
+            if WEEKDAYS.contains(&weekday.trim_end_matches(',')) =>
+        {
+            (
+                *day,
+                *month,
+                year.parse::<i64>().map_err(|_| error())?,
+                *time,
+            )
+        }
+        // Sunday, 06-Nov-94 08:49:37 GMT
+        [weekday, date, time, "GMT"] if LONG_WEEKDAYS.contains(&weekday.trim_end_matches(',')) => {
+            let mut fields = date.split('-');
+            let (day, month, year) =
+                match (fields.next(), fields.next(), fields.next(), fields.next()) {
+                    (Some(day), Some(month), Some(year), None) => (day, month, year),
+                    _ => return Err(error()),
+                };
+            let year: i64 = year.parse().map_err(|_| error())?;
+            // 两位年份按 RFC 7231 的规则解释为 1970 年到 2069 年
+            let year = if year < 70 {
+                2000 + year
+            } else if year < 100 {
+                1900 + year
+            } else {
+                year
+            };
+            (day, month, year, *time)
+        }
+        // Sun Nov  6 08:49:37 1994
+        [weekday, month, day, time, year] if WEEKDAYS.contains(weekday) => (
+            *day,
+            *month,
+            year.parse::<i64>().map_err(|_| error())?,
+            *time,
+        ),
+        _ => return Err(error()),
+    };
+
+    let day: i64 = day.parse().map_err(|_| error())?;
+    let month = MONTHS
+        .iter()
+        .position(|name| *name == month)
+        .ok_or_else(error)? as i64
+        + 1;
+
+    let mut fields = time.split(':');
+    let (hour, minute, second) = match (fields.next(), fields.next(), fields.next(), fields.next())
+    {
+        (Some(hour), Some(minute), Some(second), None) => (
+            hour.parse::<u64>().map_err(|_| error())?,
+            minute.parse::<u64>().map_err(|_| error())?,
+            second.parse::<u64>().map_err(|_| error())?,
+        ),
+        _ => return Err(error()),
+    };
+
+    if day < 1 || day > days_in_month(year, month) || hour > 23 || minute > 59 || second > 60 {
+        return Err(error());
+    }
+
+    let days = days_from_civil(year, month, day);
+    if days < 0 {
+        return Err(error());
+    }
+    let secs = days as u64 * 86400 + hour * 3600 + minute * 60 + second;
+
+    Ok(UNIX_EPOCH + Duration::from_secs(secs))
+}
+
+fn is_leap_year(year: i64) -> bool {
+    year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// 公历日期和 1970-01-01 起的天数互相换算，算法来自 Howard Hinnant 的 days_from_civil 和 civil_from_days
+
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (year, month, day)
+}
+
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = if month > 2 { month - 3 } else { month + 9 };
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+
+    era * 146097 + doe - 719468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(secs: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn formats_imf_fixdate() {
+        assert_eq!(format_http_date(at(0)), "Thu, 01 Jan 1970 00:00:00 GMT");
+        assert_eq!(
+            format_http_date(at(784111777)),
+            "Sun, 06 Nov 1994 08:49:37 GMT"
+        );
+        assert_eq!(format_log_date(at(971186136)), "10/Oct/2000:13:55:36 +0000");
+        assert_eq!(
+            format_rfc3339(at(971186136) + Duration::from_millis(123)),
+            "2000-10-10T13:55:36.123Z"
+        );
+    }
+
+    #[test]
+    fn accepts_all_three_formats() {
+        let expected = Ok(at(784111777));
+        assert_eq!(parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT"), expected);
+        assert_eq!(parse_http_date("Sunday, 06-Nov-94 08:49:37 GMT"), expected);
+        assert_eq!(parse_http_date("Sun Nov  6 08:49:37 1994"), expected);
+    }
+
+    #[test]
+    fn handles_leap_years() {
+        // 2000 年能被 400 整除是闰年，1900 和 2100 年不是
+        let leap_day = parse_http_date("Tue, 29 Feb 2000 12:00:00 GMT").unwrap();
+        assert_eq!(format_http_date(leap_day), "Tue, 29 Feb 2000 12:00:00 GMT");
+        assert_eq!(
+            format_http_date(leap_day + Duration::from_secs(86400)),
+            "Wed, 01 Mar 2000 12:00:00 GMT"
+        );
+        assert!(parse_http_date("Thu, 29 Feb 2024 00:00:00 GMT").is_ok());
+        assert!(parse_http_date("Wed, 29 Feb 2023 00:00:00 GMT").is_err());
+        assert!(parse_http_date("Mon, 29 Feb 2100 00:00:00 GMT").is_err());
+        assert_eq!(
+            format_http_date(at(4107542400)),
+            "Mon, 01 Mar 2100 00:00:00 GMT"
+        );
+    }
+
+    #[test]
+    fn only_gmt_is_accepted() {
+        for date in [
+            "Sun, 06 Nov 1994 08:49:37 UTC",
+            "Sun, 06 Nov 1994 08:49:37 +0000",
+            "Sun, 06 Nov 1994 08:49:37 PST",
+            "Sun, 06 Nov 1994 08:49:37",
+        ] {
+            assert!(parse_http_date(date).is_err(), "{}", date);
+        }
+    }
+
+    #[test]
+    fn day_boundaries_round_trip() {
+        for secs in [86399, 86400, 951868799, 1704067199, 1704067200] {
+            assert_eq!(parse_http_date(&format_http_date(at(secs))), Ok(at(secs)));
+        }
+    }
+
+    #[test]
+    fn two_digit_years_are_windowed() {
+        let year = |date| format_http_date(parse_http_date(date).unwrap());
+        assert_eq!(
+            year("Thursday, 01-Jan-70 00:00:00 GMT"),
+            "Thu, 01 Jan 1970 00:00:00 GMT"
+        );
+        assert_eq!(
+            year("Tuesday, 01-Jan-69 00:00:00 GMT"),
+            "Tue, 01 Jan 2069 00:00:00 GMT"
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_fields() {
+        for date in [
+            "",
+            "Sun, 32 Jan 1995 00:00:00 GMT",
+            "Sun, 06 Foo 1994 08:49:37 GMT",
+            "Sun, 06 Nov 1994 24:00:00 GMT",
+            "Sun, 06 Nov 1994 08:60:00 GMT",
+            "Sun, 06 Nov 1994 08:49 GMT",
+            "Wed, 31 Dec 1969 23:59:59 GMT",
+            "Xyz, 06 Nov 1994 08:49:37 GMT",
+        ] {
+            assert!(parse_http_date(date).is_err(), "{:?}", date);
+        }
+    }
+
+    #[test]
+    fn times_before_the_epoch_format_as_the_epoch() {
+        assert_eq!(
+            format_http_date(UNIX_EPOCH - Duration::from_secs(1)),
+            "Thu, 01 Jan 1970 00:00:00 GMT"
+        );
+    }
+}
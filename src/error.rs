@@ -0,0 +1,98 @@
+use crate::{multipart::MultipartError, request, tls, ProxyError, RequestError};
+use std::io;
+use thiserror::Error;
+
+/// 处理请求时出现的错误
+#[derive(Debug, Error)]
+pub enum ServerError {
+    #[error("{0}")]
+    Io(#[from] io::Error),
+    #[error("{0}")]
+    Parse(String),
+    #[error("Not found: {0}")]
+    NotFound(String),
+    #[error("Method not allowed, allowed methods: {}", .0.join(", "))]
+    MethodNotAllowed(Vec<String>),
+    #[error("Unsupported media type: {0}")]
+    UnsupportedMediaType(String),
+    #[error("{0}")]
+    Regex(#[from] regex::Error),
+    #[error("{0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Proxy error: {0}")]
+    Proxy(String),
+    #[error("TLS error: {0}")]
+    TlsError(String),
+    #[error("HTTP version not supported: {0}")]
+    VersionNotSupported(String),
+}
+
+impl ServerError {
+    /// 该错误对应的响应状态码
+    pub fn status(&self) -> u16 {
+        match self {
+            ServerError::Io(err) if err.kind() == io::ErrorKind::NotFound => 404,
+            ServerError::Parse(_) => 400,
+            ServerError::NotFound(_) => 404,
+            ServerError::MethodNotAllowed(_) => 405,
+            ServerError::UnsupportedMediaType(_) => 415,
+            ServerError::Proxy(_) => 502,
+            ServerError::VersionNotSupported(_) => 505,
+            ServerError::Io(_)
+            | ServerError::Regex(_)
+            | ServerError::Json(_)
+            | ServerError::TlsError(_) => 500,
+        }
+    }
+}
+
+impl From<request::ParseError> for ServerError {
+    fn from(err: request::ParseError) -> Self {
+        match err {
+            request::ParseError::UnsupportedVersion(version) => {
+                ServerError::VersionNotSupported(version)
+            }
+            err => ServerError::Parse(err.to_string()),
+        }
+    }
+}
+
+impl From<RequestError> for ServerError {
+    fn from(err: RequestError) -> Self {
+        match err {
+            RequestError::Io(err) => ServerError::Io(err),
+            RequestError::Parse(err) => err.into(),
+            err => ServerError::Parse(err.to_string()),
+        }
+    }
+}
+
+impl From<MultipartError> for ServerError {
+    fn from(err: MultipartError) -> Self {
+        ServerError::Parse(err.to_string())
+    }
+}
+
+impl From<ProxyError> for ServerError {
+    fn from(err: ProxyError) -> Self {
+        ServerError::Proxy(err.to_string())
+    }
+}
+
+impl From<tls::TlsError> for ServerError {
+    fn from(err: tls::TlsError) -> Self {
+        match err {
+            tls::TlsError::Io(err) => ServerError::Io(err),
+            err => ServerError::TlsError(err.to_string()),
+        }
+    }
+}
+
+/// 向线程池提交任务失败的原因
+#[derive(Debug, Error)]
+pub enum ThreadPoolError {
+    #[error("Thread pool queue is full")]
+    QueueFull,
+    #[error("Thread pool has shut down")]
+    ShutDown,
+}
@@ -0,0 +1,252 @@
+use crate::ServerError;
+use serde_json::{Map, Value};
+use std::{
+    fs, io,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, RwLock, RwLockReadGuard},
+    thread,
+    time::{Duration, SystemTime},
+};
+use tempfile::NamedTempFile;
+use tracing::{info, warn};
+
+/// 保存在 JSON 文件中的对象数组，每个对象以数字 `id` 区分
+///
+/// 数组保存在内存中，读取互不阻塞；修改时先写入同目录下的临时文件再重命名，文件不会出现写了一半的内容
+#[derive(Debug)]
+pub struct ItemStore {
+    path: PathBuf,
+    items: RwLock<Vec<Value>>,
+    /// 内存中的数据对应的文件修改时间和大小，用来发现其他程序对文件的修改
+    version: Mutex<Option<FileVersion>>,
+}
+
+type FileVersion = (SystemTime, u64);
+
+impl ItemStore {
+    pub fn new(path: impl Into<PathBuf>, items: Vec<Value>) -> ItemStore {
+        ItemStore {
+            path: path.into(),
+            items: RwLock::new(items),
+            version: Mutex::new(None),
+        }
+    }
+
+    /// 从文件加载，文件内容必须是 JSON 数组
+    pub fn load(path: impl Into<PathBuf>) -> Result<ItemStore, ServerError> {
+        let path = path.into();
+        let version = file_version(&path).ok();
+        let store = ItemStore::new(&path, read_items(&path)?);
+        *store.version.lock().unwrap() = version;
+        Ok(store)
+    }
+
+    /// 文件被其他程序修改过时重新加载，返回是否重新加载了；文件不存在或者内容无效时保留内存中的数据
+    pub fn reload_if_changed(&self) -> Result<bool, ServerError> {
+        // 持有写锁，检查期间不会有本进程的修改写入文件
+        let mut items = self.items.write().unwrap();
+        let version = match file_version(&self.path) {
+            Ok(version) => version,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(false),
+            Err(err) => return Err(err.into()),
+        };
+        let mut current = self.version.lock().unwrap();
+        if *current == Some(version) {
+            return Ok(false);
+        }
+
+        // 内容无效的文件只报告一次，下次修改后再尝试
+        *current = Some(version);
+        *items = read_items(&self.path)?;
+        Ok(true)
+    }
+
+    /// 在后台线程中每隔 `interval` 检查一次文件，`store` 被释放后线程退出
+    pub fn watch(store: &Arc<ItemStore>, interval: Duration) {
+        let store = Arc::downgrade(store);
+        thread::spawn(move || loop {
+            thread::sleep(interval);
+            let Some(store) = store.upgrade() else {
+                break;
+            };
+            match store.reload_if_changed() {
+                Ok(true) => info!(path = %store.path.display(), "reloaded data file"),
+                Ok(false) => {}
+                Err(err) => {
+                    warn!(path = %store.path.display(), error = %err, "cannot reload data file")
+                }
+            }
+        });
+    }
+
+    pub fn read(&self) -> RwLockReadGuard<'_, Vec<Value>> {
+        self.items.read().unwrap()
+    }
+
+    /// 在末尾加入新对象，已有相同 `id` 的对象时返回 `false`
+    pub fn insert(&self, item: Value) -> Result<bool, ServerError> {
+        let id = item
+            .get("id")
+            .and_then(Value::as_u64)
+            .ok_or_else(|| ServerError::Parse(String::from("Item has no numeric id")))?;
+
+        let mut items = self.items.write().unwrap();
+        if position(&items, id).is_some() {
+            return Ok(false);
+        }
+
+        let mut updated = items.clone();
+        updated.push(item);
+        self.save(&updated)?;
+        *items = updated;
+        Ok(true)
+    }
+
+    /// 用 `item` 替换指定 `id` 的对象，`item` 中的 `id` 统一改为该 `id`，不存在时返回 `false`
+    pub fn replace(&self, id: u64, mut item: Value) -> Result<bool, ServerError> {
+        let object = item
+            .as_object_mut()
+            .ok_or_else(|| ServerError::Parse(String::from("Item is not a JSON object")))?;
+        object.insert(String::from("id"), Value::from(id));
+
+        let mut items = self.items.write().unwrap();
+        let Some(index) = position(&items, id) else {
+            return Ok(false);
+        };
+
+        let mut updated = items.clone();
+        updated[index] = item;
+        self.save(&updated)?;
+        *items = updated;
+        Ok(true)
+    }
+
+    /// 按 JSON Merge Patch（RFC 7396）修改指定 `id` 的对象，`id` 保持不变，
+    /// 返回修改后的对象，不存在时返回 `None`
+    pub fn patch(&self, id: u64, patch: &Value) -> Result<Option<Value>, ServerError> {
+        let mut items = self.items.write().unwrap();
+        let Some(index) = position(&items, id) else {
+            return Ok(None);
+        };
+
+        let mut updated = items.clone();
+        let item = &mut updated[index];
+        json_merge_patch(item, patch);
+        if let Some(object) = item.as_object_mut() {
+            object.insert(String::from("id"), Value::from(id));
+        }
+        let item = item.clone();
+
+        self.save(&updated)?;
+        *items = updated;
+        Ok(Some(item))
+    }
+
+    /// 删除指定 `id` 的对象，不存在时返回 `false`
+    pub fn remove(&self, id: u64) -> Result<bool, ServerError> {
+        let mut items = self.items.write().unwrap();
+        let Some(index) = position(&items, id) else {
+            return Ok(false);
+        };
+
+        let mut updated = items.clone();
+        updated.remove(index);
+        self.save(&updated)?;
+        *items = updated;
+        Ok(true)
+    }
+
+    /// 写入文件并记录新的文件版本，自己写入的修改不会被当成外部修改重新加载
+    fn save(&self, items: &[Value]) -> io::Result<()> {
+        save(&self.path, items)?;
+        *self.version.lock().unwrap() = file_version(&self.path).ok();
+        Ok(())
+    }
+}
+
+/// JSON Merge Patch：对象逐个键合并，值为 `null` 的键被删除，其他类型的补丁直接替换目标
+pub fn json_merge_patch(target: &mut Value, patch: &Value) {
+    let Value::Object(patch) = patch else {
+        *target = patch.clone();
+        return;
+    };
+
+    if !target.is_object() {
+        *target = Value::Object(Map::new());
+    }
+    if let Value::Object(target) = target {
+        for (key, value) in patch {
+            if value.is_null() {
+                target.remove(key);
+            } else {
+                json_merge_patch(target.entry(key.as_str()).or_insert(Value::Null), value);
+            }
+        }
+    }
+}
+
+fn read_items(path: &Path) -> Result<Vec<Value>, ServerError> {
+    match serde_json::from_str(&fs::read_to_string(path)?)? {
+        Value::Array(items) => Ok(items),
+        _ => Err(ServerError::Parse(String::from(
+            "JSON data is not an array",
+        ))),
+    }
+}
+
+fn file_version(path: &Path) -> io::Result<FileVersion> {
+    let metadata = fs::metadata(path)?;
+    Ok((metadata.modified()?, metadata.len()))
+}
+
+fn position(items: &[Value], id: u64) -> Option<usize> {
+    items
+        .iter()
+        .position(|item| item.get("id").and_then(Value::as_u64) == Some(id))
+}
+
+/// 写入临时文件后重命名为目标文件，写入失败时内存和文件中的数据都保持不变
+///
+/// 临时文件创建时只有所有者可读写，重命名前复制原文件的权限，保存不会改变文件权限
+fn save(path: &Path, items: &[Value]) -> io::Result<()> {
+    let dir = path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let mut file = NamedTempFile::new_in(dir)?;
+    file.write_all(&serde_json::to_vec(items)?)?;
+    if let Ok(metadata) = fs::metadata(path) {
+        file.as_file().set_permissions(metadata.permissions())?;
+    }
+    file.as_file().sync_all()?;
+    file.persist(path).map_err(|err| err.error)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[cfg(unix)]
+    #[test]
+    fn save_keeps_the_file_mode() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("data.json");
+        fs::write(&path, "[]").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+
+        let store = ItemStore::load(&path).unwrap();
+        assert!(store.insert(json!({"id": 1, "name": "a"})).unwrap());
+
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o644);
+        assert_eq!(
+            read_items(&path).unwrap(),
+            vec![json!({"id": 1, "name": "a"})]
+        );
+    }
+}
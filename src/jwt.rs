@@ -0,0 +1,140 @@
+use crate::{url, Request};
+use jsonwebtoken::{decode, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::fmt;
+
+/// 需要 Bearer token 的路径前缀
+pub const PROTECTED_PREFIX: &str = "/api";
+
+/// JWT 中的声明，除 `sub` 和 `exp` 外的声明（包括 `aud`）保留在 `extra` 中
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Claims {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sub: Option<String>,
+    /// 过期时间（Unix 时间戳，秒）
+    pub exp: u64,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// 使用 HS256 签名的 Bearer token 认证，保护 `/api` 下的路径
+#[derive(Clone)]
+pub struct JwtAuth {
+    secret: String,
+    /// 设置后 token 的 `aud` 必须包含该取值
+    pub audience: Option<String>,
+}
+
+impl fmt::Debug for JwtAuth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JwtAuth")
+            .field("secret", &"<redacted>")
+            .field("audience", &self.audience)
+            .finish()
+    }
+}
+
+impl JwtAuth {
+    pub fn new(secret: &str, audience: Option<&str>) -> JwtAuth {
+        JwtAuth {
+            secret: secret.to_string(),
+            audience: audience.map(String::from),
+        }
+    }
+
+    pub fn protects(&self, path: &str) -> bool {
+        url::is_under_prefix(PROTECTED_PREFIX, path)
+    }
+
+    /// 验证 `Authorization: Bearer` 中 token 的签名、`exp` 和 `aud`，成功时返回其中的声明
+    pub fn verify(&self, request: &Request) -> Option<Claims> {
+        let authorization = request.header("authorization")?.trim();
+        let (scheme, token) = authorization.split_once(' ')?;
+        if !scheme.eq_ignore_ascii_case("bearer") {
+            return None;
+        }
+
+        let mut validation = Validation::new(Algorithm::HS256);
+        validation.leeway = 0;
+        match &self.audience {
+            Some(audience) => {
+                validation.set_audience(&[audience]);
+                validation.set_required_spec_claims(&["exp", "aud"]);
+            }
+            None => validation.validate_aud = false,
+        }
+
+        let key = DecodingKey::from_secret(self.secret.as_bytes());
+        decode::<Claims>(token.trim(), &key, &validation)
+            .ok()
+            .map(|data| data.claims)
+    }
+}
+
+/// 用 HS256 签名生成 token
+pub fn generate_token(claims: &Claims, secret: &str) -> String {
+    let key = EncodingKey::from_secret(secret.as_bytes());
+    // 声明只包含 JSON 值，HMAC 签名不会失败
+    encode(&Header::new(Algorithm::HS256), claims, &key).expect("Failed to encode JWT")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn request(token: &str) -> Request {
+        let raw = format!(
+            "GET /api/list HTTP/1.1\r\nAuthorization: Bearer {}\r\n\r\n",
+            token
+        );
+        Request::from_bytes(raw.as_bytes()).unwrap()
+    }
+
+    fn expiring(expires_in: i64) -> Claims {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        Claims {
+            sub: Some(String::from("alice")),
+            exp: now.saturating_add_signed(expires_in),
+            extra: Map::new(),
+        }
+    }
+
+    #[test]
+    fn protects_api_paths_however_they_are_written() {
+        let jwt = JwtAuth::new("secret", None);
+        for path in [
+            "/api",
+            "/api/list",
+            "//api/list",
+            "/./api/list",
+            "/x/../api/list",
+        ] {
+            assert!(jwt.protects(path), "{}", path);
+        }
+        assert!(!jwt.protects("/apis"));
+        assert!(!jwt.protects("/index.html"));
+        assert!(!jwt.protects("/api/../index.html"));
+    }
+
+    #[test]
+    fn verifies_signature_and_expiry() {
+        let jwt = JwtAuth::new("secret", None);
+        let claims = expiring(60);
+        let valid = generate_token(&claims, "secret");
+
+        assert_eq!(jwt.verify(&request(&valid)), Some(claims.clone()));
+        assert_eq!(jwt.verify(&request(&format!(" {} ", valid))), Some(claims));
+        assert!(jwt
+            .verify(&request(&generate_token(&expiring(60), "other")))
+            .is_none());
+        assert!(jwt
+            .verify(&request(&generate_token(&expiring(-60), "secret")))
+            .is_none());
+        assert!(jwt.verify(&request("not-a-token")).is_none());
+    }
+}
@@ -1,48 +1,506 @@
+pub mod assets;
+pub mod auth;
+pub mod cache;
+pub mod config;
+pub mod cors;
+pub mod datetime;
+mod error;
+pub mod items;
+pub mod jwt;
+mod listing;
+pub mod logger;
+pub mod metrics;
+pub mod middleware;
+pub mod mime;
+pub mod multipart;
+pub mod proxy;
+mod range;
+pub mod ratelimit;
+mod request;
+mod response;
+mod router;
+pub mod security;
+pub mod semaphore;
+pub mod sse;
+pub mod tls;
+pub mod url;
+pub mod websocket;
+
+pub use assets::ErrorPage;
+pub use auth::BasicAuth;
+pub use cache::CacheRule;
 pub use clap::Parser;
-use regex::Regex;
-use serde_json::json;
+pub use config::{ConfigError, FileConfig, ResolvedConfig};
+pub use cors::CorsConfig;
+pub use error::{ServerError, ThreadPoolError};
+pub use items::ItemStore;
+pub use jwt::{generate_token, Claims, JwtAuth};
+pub use logger::{LogFormat, LogLevel, LogRecord, Logger};
+pub use metrics::{Metrics, MetricsSnapshot, ServerStats};
+pub use middleware::{Middleware, MiddlewareStack};
+use mime::detect_content_type;
+pub use mime::MimeTypes;
+pub use proxy::{parse_proxy_target, ProxyError, ProxyTarget};
+pub use ratelimit::RateLimiter;
+pub use request::{
+    read_raw_body, read_raw_head, read_raw_request, ParseError, Request, RequestError,
+};
+pub use response::{stream_response, ChunkedWriter, Response, ResponseBody, StreamBody};
+pub use router::{Handler, PathParams, Pattern, Route, Router};
+pub use security::{sanitize_path, SecurityError};
+pub use semaphore::{Semaphore, SemaphoreGuard};
+use serde_json::{json, Value};
+pub use sse::SseBroadcaster;
 use std::{
+    any::Any,
     collections::HashMap,
-    error::Error,
-    fs,
-    io::prelude::*,
-    net::TcpStream,
-    net::ToSocketAddrs,
-    sync::{mpsc, Arc, Mutex},
+    env, fs,
+    io::{self, BufRead, BufReader, Read, Write},
+    iter,
+    net::{IpAddr, Shutdown, SocketAddr, TcpStream},
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc, Arc, Mutex,
+    },
     thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
+use tracing::{debug, error, info, instrument, warn, Span};
+pub use websocket::{WebSocketHandler, WebSocketRoute};
 
 #[derive(Parser, Debug)]
 pub struct Args {
-    /// IP
-    #[arg(short, long, default_value_t = String::from("127.0.0.1"))]
-    pub ip: String,
+    /// 配置文件（TOML），命令行参数和环境变量优先
+    #[arg(long, value_name = "PATH", env = "HTTPSERVER_CONFIG")]
+    pub config: Option<PathBuf>,
+
+    /// IP，默认 127.0.0.1，也可以用 HTTPSERVER_IP 设置
+    #[arg(short, long)]
+    pub ip: Option<String>,
+
+    /// 端口，默认 8080，也可以用 HTTPSERVER_PORT 设置
+    #[arg(short, long, value_parser = clap::value_parser!(u16).range(1..))]
+    pub port: Option<u16>,
+
+    /// 线程数，默认 8，也可以用 HTTPSERVER_THREADS 设置
+    #[arg(short, long, value_parser = clap::value_parser!(u8).range(1..))]
+    pub threads: Option<u8>,
+
+    /// 每个客户端 IP 每秒最多发送的请求数，允许 2 倍的突发，不设置时不限制
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..), env = "HTTPSERVER_RATE_LIMIT")]
+    pub rate_limit: Option<u32>,
+
+    /// 同时处理（包括排队等待）的最大连接数，超出时直接回复 503
+    #[arg(long, default_value_t = DEFAULT_MAX_CONNECTIONS, value_parser = clap::value_parser!(u32).range(1..), env = "HTTPSERVER_MAX_CONNECTIONS")]
+    pub max_connections: u32,
+
+    /// 代理，也可以用 HTTPSERVER_PROXY 设置
+    #[arg(long)]
+    pub proxy: Option<String>,
+
+    /// 经 `socks5://` 代理连接的目标 `host:port`，使用 SOCKS5 代理时必须设置
+    #[arg(long, value_name = "HOST:PORT", env = "HTTPSERVER_SOCKS_TARGET")]
+    pub socks_target: Option<String>,
+
+    /// 代理超时时间（秒）
+    #[arg(long, default_value_t = 10, env = "HTTPSERVER_PROXY_TIMEOUT")]
+    pub proxy_timeout: u64,
+
+    /// 读取请求的超时时间（毫秒）
+    #[arg(long, value_name = "MILLIS", default_value_t = DEFAULT_READ_TIMEOUT, value_parser = clap::value_parser!(u64).range(1..), env = "HTTPSERVER_READ_TIMEOUT")]
+    pub read_timeout: u64,
+
+    /// 写出响应的超时时间（毫秒）
+    #[arg(long, value_name = "MILLIS", default_value_t = DEFAULT_WRITE_TIMEOUT, value_parser = clap::value_parser!(u64).range(1..), env = "HTTPSERVER_WRITE_TIMEOUT")]
+    pub write_timeout: u64,
+
+    /// keep-alive 连接等待下一个请求的超时时间（秒），为 0 时每个请求后关闭连接
+    #[arg(long, default_value_t = 5, env = "HTTPSERVER_KEEPALIVE_TIMEOUT")]
+    pub keepalive_timeout: u64,
+
+    /// 单个 keep-alive 连接最多处理的请求数
+    #[arg(long, default_value_t = 100, value_parser = clap::value_parser!(u64).range(1..), env = "HTTPSERVER_MAX_KEEPALIVE_REQUESTS")]
+    pub max_keepalive_requests: u64,
+
+    /// 请求头大小上限（字节）
+    #[arg(long, default_value_t = DEFAULT_MAX_HEADER_SIZE, env = "HTTPSERVER_MAX_HEADER_SIZE")]
+    pub max_header_size: usize,
+
+    /// 请求总大小上限（字节）
+    #[arg(long, default_value_t = DEFAULT_MAX_REQUEST_SIZE, env = "HTTPSERVER_MAX_REQUEST_SIZE")]
+    pub max_request_size: usize,
+
+    /// 请求体大小上限（字节）
+    #[arg(long, default_value_t = DEFAULT_MAX_BODY_SIZE, env = "HTTPSERVER_MAX_BODY_SIZE")]
+    pub max_body_size: usize,
+
+    /// TLS 证书文件（PEM），也可以用 HTTPSERVER_TLS_CERT 设置
+    #[arg(long)]
+    pub tls_cert: Option<PathBuf>,
+
+    /// TLS 私钥文件（PEM），也可以用 HTTPSERVER_TLS_KEY 设置
+    #[arg(long)]
+    pub tls_key: Option<PathBuf>,
+
+    /// 允许跨域访问的来源，`*` 或以逗号分隔的列表，不带取值时为 `*`
+    #[arg(
+        long = "cors-origin",
+        alias = "cors",
+        value_name = "ORIGIN",
+        num_args = 0..=1,
+        default_missing_value = "*",
+        env = "HTTPSERVER_CORS_ORIGIN"
+    )]
+    pub cors: Option<String>,
+
+    /// 允许跨域使用的请求方法，以逗号分隔
+    #[arg(
+        long,
+        value_name = "LIST",
+        requires = "cors",
+        env = "HTTPSERVER_CORS_METHODS"
+    )]
+    pub cors_methods: Option<String>,
+
+    /// `Server` 响应头的取值，为空时不发送该响应头
+    #[arg(long, default_value = DEFAULT_SERVER_BANNER, env = "HTTPSERVER_SERVER_BANNER")]
+    pub server_banner: String,
+
+    /// 浏览器缓存 CORS 预检结果的时间（秒）
+    #[arg(long, default_value_t = cors::DEFAULT_MAX_AGE, env = "HTTPSERVER_CORS_MAX_AGE")]
+    pub cors_max_age: u64,
+
+    /// 静态文件根目录
+    #[arg(long, default_value = DEFAULT_ROOT, env = "HTTPSERVER_ROOT")]
+    pub root: PathBuf,
+
+    /// 目录中没有 index.html 时返回 403，而不是列出目录内容
+    #[arg(long, env = "HTTPSERVER_NO_INDEX")]
+    pub no_index: bool,
+
+    /// 单页应用模式，找不到的页面路径返回 index.html
+    #[arg(long, env = "HTTPSERVER_SPA")]
+    pub spa: bool,
+
+    /// 客户端接受 gzip 时压缩超过 1 KB 的文本响应
+    #[arg(long, env = "HTTPSERVER_COMPRESS")]
+    pub compress: bool,
+
+    /// 静态文件的 `Cache-Control` 规则，格式为 `pattern=value`，可重复指定，按顺序第一条匹配的生效
+    #[arg(long = "cache-rule", value_name = "PATTERN=VALUE", value_parser = cache::parse_cache_rule)]
+    pub cache_rules: Vec<CacheRule>,
+
+    /// 额外的 MIME 类型映射，格式为 `ext=type`，可重复指定
+    #[arg(long = "mime-type", value_name = "EXT=TYPE", value_parser = mime::parse_mapping)]
+    pub mime_types: Vec<(String, String)>,
+
+    /// 替换默认错误页面，格式为 `status=path`，可重复指定，`Content-Type` 按文件扩展名确定
+    #[arg(long = "error-page", value_name = "STATUS=PATH", value_parser = assets::parse_error_page)]
+    pub error_pages: Vec<(u16, PathBuf)>,
+
+    /// 上传文件的保存目录，默认为系统临时目录
+    #[arg(long, env = "HTTPSERVER_UPLOAD_DIR")]
+    pub upload_dir: Option<PathBuf>,
+
+    /// 访问日志文件，不设置时写到标准输出
+    #[arg(long, env = "HTTPSERVER_LOG_FILE")]
+    pub log_file: Option<PathBuf>,
+
+    /// 访问日志格式
+    #[arg(long, value_enum, default_value_t = LogFormat::Combined, env = "HTTPSERVER_LOG_FORMAT")]
+    pub log_format: LogFormat,
+
+    /// `/health`、`/ready` 和 `/metrics` 的请求不写访问日志
+    #[arg(long, env = "HTTPSERVER_NO_PROBE_LOG")]
+    pub no_probe_log: bool,
+
+    /// 运行日志的最低级别，优先于环境变量 `RUST_LOG`，两者都没有时为 info
+    #[arg(long, value_enum, env = "HTTPSERVER_LOG_LEVEL")]
+    pub log_level: Option<LogLevel>,
+
+    /// Basic 认证的用户文件，每行一个 `用户名:bcrypt 哈希`
+    #[arg(long, env = "HTTPSERVER_AUTH_FILE")]
+    pub auth_file: Option<PathBuf>,
+
+    /// Basic 认证的域
+    #[arg(long, default_value = auth::DEFAULT_REALM, requires = "auth_file", env = "HTTPSERVER_AUTH_REALM")]
+    pub auth_realm: String,
+
+    /// 需要认证的路径前缀，默认保护所有路径
+    #[arg(
+        long,
+        default_value = "/",
+        requires = "auth_file",
+        env = "HTTPSERVER_AUTH_PREFIX"
+    )]
+    pub auth_prefix: String,
+
+    /// `/health` 返回的版本号
+    #[arg(long, default_value = env!("CARGO_PKG_VERSION"), env = "HTTPSERVER_VERSION_STRING")]
+    pub version_string: String,
+
+    /// 设置后 /api 下的请求需要用该密钥以 HS256 签名的 Bearer token，也可以用 HTTPSERVER_JWT_SECRET 设置
+    #[arg(long)]
+    pub jwt_secret: Option<String>,
+
+    /// token 的 `aud` 必须包含的取值
+    #[arg(long, env = "HTTPSERVER_JWT_AUDIENCE")]
+    pub jwt_audience: Option<String>,
+
+    /// 在该路径上接受 WebSocket 升级，收到的文本消息原样发回；不设置时不接受升级请求
+    #[arg(long, value_name = "PATH", env = "HTTPSERVER_WEBSOCKET")]
+    pub websocket: Option<String>,
+
+    /// WebSocket 连接空闲多久（秒）后发送 Ping，再过同样的时间没有收到任何帧时关闭连接
+    #[arg(long, default_value_t = DEFAULT_WEBSOCKET_IDLE_TIMEOUT, value_parser = clap::value_parser!(u64).range(1..), env = "HTTPSERVER_WEBSOCKET_IDLE_TIMEOUT")]
+    pub websocket_idle_timeout: u64,
+}
+
+/// 默认的监听地址
+pub const DEFAULT_IP: &str = "127.0.0.1";
+
+/// 默认的监听端口
+pub const DEFAULT_PORT: u16 = 8080;
+
+/// 默认的工作线程数
+pub const DEFAULT_THREADS: u8 = 8;
+
+/// 默认的静态文件根目录
+pub const DEFAULT_ROOT: &str = "static";
+
+/// `Server` 响应头的默认取值
+pub const DEFAULT_SERVER_BANNER: &str = concat!("http-server/", env!("CARGO_PKG_VERSION"));
+
+/// 请求头的默认大小上限
+pub const DEFAULT_MAX_HEADER_SIZE: usize = 16 * 1024;
+
+/// 请求（请求头加请求体）的默认大小上限
+pub const DEFAULT_MAX_REQUEST_SIZE: usize = 8 * 1024 * 1024;
+
+/// 请求体的默认大小上限
+pub const DEFAULT_MAX_BODY_SIZE: usize = 8 * 1024 * 1024;
+
+/// 读取请求的默认超时时间（毫秒）
+pub const DEFAULT_READ_TIMEOUT: u64 = 30_000;
+
+/// 写出响应的默认超时时间（毫秒）
+pub const DEFAULT_WRITE_TIMEOUT: u64 = 30_000;
+
+/// WebSocket 连接默认的空闲超时时间（秒）
+pub const DEFAULT_WEBSOCKET_IDLE_TIMEOUT: u64 = 30;
+
+/// 默认的最大连接数
+pub const DEFAULT_MAX_CONNECTIONS: u32 = 256;
+
+/// `/api/list`、`/api/search` 和 `/api/items` 使用的数据文件
+const DATA_FILE: &str = "data/data.json";
+
+/// 检查数据文件是否被其他程序修改的间隔
+const DATA_RELOAD_INTERVAL: Duration = Duration::from_secs(1);
+
+/// `/api/list` 和 `/api/search` 每页默认和最多返回的对象数
+const DEFAULT_PAGE_LIMIT: usize = 100;
+const MAX_PAGE_LIMIT: usize = 1000;
+
+/// 超过这个大小的静态文件整体返回时按 chunked 编码分块发送
+const STREAM_FILE_THRESHOLD: u64 = 1024 * 1024;
+
+/// 分块发送静态文件时每次读取并作为一个块写出的大小
+const STREAM_CHUNK_SIZE: usize = 64 * 1024;
+
+/// 预压缩文件的编码和扩展名，按优先级排列
+const PRECOMPRESSED_EXTENSIONS: &[(&str, &str)] = &[("br", "br"), ("gzip", "gz")];
+
+/// 关闭连接前最多等待客户端关闭的时间和最多丢弃的数据量
+pub const LINGER_TIMEOUT: Duration = Duration::from_secs(1);
+const MAX_LINGER_BYTES: usize = 64 * 1024;
+
+/// 单个连接读取请求时的限制
+#[derive(Clone, Copy, Debug)]
+pub struct Limits {
+    pub max_header_size: usize,
+    pub max_request_size: usize,
+    pub max_body_size: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_header_size: DEFAULT_MAX_HEADER_SIZE,
+            max_request_size: DEFAULT_MAX_REQUEST_SIZE,
+            max_body_size: DEFAULT_MAX_BODY_SIZE,
+        }
+    }
+}
 
-    /// 端口
-    #[arg(short, long, default_value_t = String::from("8080"))]
-    pub port: String,
+/// 服务器的运行状态，供 `/health` 和 `/ready` 使用
+#[derive(Debug)]
+pub struct ServerState {
+    pub started: Instant,
+    pub version: String,
+    /// 线程池创建完成后才开始接受请求
+    ready: AtomicBool,
+    /// 收到退出信号后不再接受新连接，处理中的连接在当前请求后关闭
+    shutting_down: AtomicBool,
+}
 
-    ///线程数
-    #[arg(short, long, default_value_t = 8_u8)]
-    pub threads: u8,
+impl ServerState {
+    pub fn new(version: &str) -> ServerState {
+        ServerState {
+            started: Instant::now(),
+            version: version.to_string(),
+            ready: AtomicBool::new(false),
+            shutting_down: AtomicBool::new(false),
+        }
+    }
+
+    pub fn set_ready(&self) {
+        self.ready.store(true, Ordering::SeqCst);
+    }
 
-    ///代理
-    #[arg(long, default_value_t = String::from(""))]
-    pub proxy: String,
+    pub fn is_ready(&self) -> bool {
+        self.ready.load(Ordering::SeqCst) && !self.is_shutting_down()
+    }
+
+    /// 开始退出，返回之前是否已经在退出
+    pub fn begin_shutdown(&self) -> bool {
+        self.shutting_down.swap(true, Ordering::SeqCst)
+    }
+
+    pub fn is_shutting_down(&self) -> bool {
+        self.shutting_down.load(Ordering::SeqCst)
+    }
 }
 
+impl Default for ServerState {
+    fn default() -> Self {
+        ServerState::new(env!("CARGO_PKG_VERSION"))
+    }
+}
+
+/// 处理连接时用到的服务器配置
+#[derive(Clone, Debug)]
+pub struct ServerConfig {
+    /// 静态文件和错误页面所在的目录
+    pub root: PathBuf,
+    /// 是否为没有 index.html 的目录生成列表
+    pub listing: bool,
+    /// 单页应用模式
+    pub spa: bool,
+    /// 是否压缩文本响应
+    pub compress: bool,
+    pub limits: Limits,
+    pub cors: Option<CorsConfig>,
+    /// 设置后所有请求都转发到该地址
+    pub proxy: Option<ProxyTarget>,
+    pub proxy_timeout: Duration,
+    /// 读取第一个请求的超时
+    pub read_timeout: Duration,
+    /// keep-alive 连接的空闲超时，为 0 时不保持连接
+    pub keepalive_timeout: Duration,
+    /// 单个连接最多处理的请求数
+    pub max_keepalive_requests: u64,
+    pub mime_types: MimeTypes,
+    pub cache_rules: Vec<CacheRule>,
+    /// `Server` 响应头，为空时不发送
+    pub server_banner: String,
+    /// 上传文件的保存目录
+    pub upload_dir: PathBuf,
+    /// 设置后对前缀下的路径要求 Basic 认证
+    pub auth: Option<BasicAuth>,
+    /// 设置后 /api 下的路径要求 Bearer token
+    pub jwt: Option<JwtAuth>,
+    /// 运行指标，复制的配置共享同一组计数器
+    pub metrics: ServerStats,
+    pub state: Arc<ServerState>,
+    /// `/api/events` 的订阅者，应用代码通过它推送事件
+    pub events: SseBroadcaster,
+    /// 设置后在该路径上接受 WebSocket 升级请求
+    pub websocket: Option<WebSocketRoute>,
+    /// 按状态码替换默认错误页面
+    pub error_pages: HashMap<u16, ErrorPage>,
+    /// 是否为健康检查和指标请求写访问日志
+    pub log_probes: bool,
+    /// 设置后按客户端 IP 限制每秒请求数：接受连接时检查一次，keep-alive 连接上之后的请求再各检查一次；
+    /// 复制的配置共享同一组令牌桶
+    pub rate_limiter: Option<Arc<RateLimiter>>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            root: PathBuf::from(DEFAULT_ROOT),
+            listing: true,
+            spa: false,
+            compress: false,
+            limits: Limits::default(),
+            cors: None,
+            proxy: None,
+            proxy_timeout: Duration::from_secs(10),
+            read_timeout: Duration::from_millis(DEFAULT_READ_TIMEOUT),
+            keepalive_timeout: Duration::from_secs(5),
+            max_keepalive_requests: 100,
+            mime_types: MimeTypes::default(),
+            cache_rules: Vec::new(),
+            server_banner: String::from(DEFAULT_SERVER_BANNER),
+            upload_dir: env::temp_dir(),
+            auth: None,
+            jwt: None,
+            metrics: ServerStats::default(),
+            state: Arc::new(ServerState::default()),
+            events: SseBroadcaster::new(),
+            websocket: None,
+            error_pages: HashMap::new(),
+            log_probes: true,
+            rate_limiter: None,
+        }
+    }
+}
+
+/// 负载均衡器和监控系统定期访问的路径，可以不写访问日志
+const PROBE_PATHS: [&str; 3] = ["/health", "/ready", "/metrics"];
+
+/// 所有路径都实现的方法，其他方法（PUT、DELETE 等）只在注册了该方法的路由上实现，
+/// 其余路径上回复 501
+const GENERAL_METHODS: [&str; 3] = ["GET", "HEAD", "POST"];
+
+/// 没有匹配的路由、交给静态文件处理的请求在运行指标中的路由名
+const STATIC_ROUTE: &str = "static";
+
+/// 队列容量为线程数的多少倍
+const QUEUE_CAPACITY_PER_THREAD: usize = 10;
+
 pub struct ThreadPool {
-    workers: Vec<Worker>,
-    sender: Option<mpsc::Sender<Job>>,
+    workers: Mutex<Vec<Worker>>,
+    sender: Option<mpsc::SyncSender<Message>>,
+    receiver: Arc<Mutex<mpsc::Receiver<Message>>>,
+    /// 工作线程数，缩减时在发出退出消息的同时减少
+    size: AtomicUsize,
+    next_id: AtomicUsize,
 }
 
 type Job = Box<dyn FnOnce() + Send + 'static>;
 
+/// 发给工作线程的消息
+enum Message {
+    Job(Job),
+    /// 让收到的工作线程处理完手头的任务后退出
+    Terminate,
+}
+
 impl ThreadPool {
+    /// 创建 `size` 个工作线程，任务队列容量为线程数的 10 倍
     pub fn new(size: usize) -> ThreadPool {
+        ThreadPool::with_capacity(size, size * QUEUE_CAPACITY_PER_THREAD)
+    }
+
+    /// 创建 `size` 个工作线程，排队等待的任务最多 `capacity` 个
+    pub fn with_capacity(size: usize, capacity: usize) -> ThreadPool {
         assert!(size > 0);
 
-        let (sender, receiver) = mpsc::channel();
+        let (sender, receiver) = mpsc::sync_channel(capacity);
 
         let receiver = Arc::new(Mutex::new(receiver));
 
@@ -53,18 +511,68 @@ impl ThreadPool {
         }
 
         ThreadPool {
-            workers,
+            workers: Mutex::new(workers),
             sender: Some(sender),
+            receiver,
+            size: AtomicUsize::new(size),
+            next_id: AtomicUsize::new(size),
         }
     }
 
-    pub fn execute<F>(&self, f: F) -> Result<(), Box<dyn Error>>
+    /// 提交任务，队列已满时立即返回 [`ThreadPoolError::QueueFull`]，不会阻塞调用者
+    #[instrument(level = "trace", skip_all)]
+    pub fn execute<F>(&self, f: F) -> Result<(), ThreadPoolError>
     where
         F: FnOnce() + Send + 'static,
     {
         let job = Box::new(f);
 
-        self.sender.as_ref().unwrap().send(job)?;
+        self.sender
+            .as_ref()
+            .unwrap()
+            .try_send(Message::Job(job))
+            .map_err(|err| match err {
+                mpsc::TrySendError::Full(_) => ThreadPoolError::QueueFull,
+                mpsc::TrySendError::Disconnected(_) => ThreadPoolError::ShutDown,
+            })
+    }
+
+    /// 当前的工作线程数
+    pub fn workers(&self) -> usize {
+        self.size.load(Ordering::SeqCst)
+    }
+
+    /// 调整工作线程数：增加时启动新的线程，减少时发送退出消息，
+    /// 多出的线程在处理完已经排队的任务后退出
+    pub fn set_workers(&self, new_size: usize) -> Result<(), ThreadPoolError> {
+        assert!(new_size > 0);
+
+        let mut workers = self.workers.lock().unwrap();
+        workers.retain(|worker| {
+            worker
+                .thread
+                .as_ref()
+                .is_some_and(|thread| !thread.is_finished())
+        });
+
+        let size = self.size.load(Ordering::SeqCst);
+        if new_size > size {
+            for _ in size..new_size {
+                let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+                workers.push(Worker::new(id, Arc::clone(&self.receiver)));
+            }
+        } else {
+            // 退出消息排在已有任务之后，队列满时等待空位
+            for _ in new_size..size {
+                self.sender
+                    .as_ref()
+                    .unwrap()
+                    .send(Message::Terminate)
+                    .map_err(|_| ThreadPoolError::ShutDown)?;
+            }
+        }
+        self.size.store(new_size, Ordering::SeqCst);
+
         Ok(())
     }
 }
@@ -73,7 +581,7 @@ impl Drop for ThreadPool {
     fn drop(&mut self) {
         drop(self.sender.take());
 
-        for worker in &mut self.workers {
+        for worker in self.workers.get_mut().unwrap().iter_mut() {
             if let Some(thread) = worker.thread.take() {
                 thread.join().unwrap();
             }
@@ -87,300 +595,1512 @@ struct Worker {
 }
 
 impl Worker {
-    fn new(_id: usize, receiver: Arc<Mutex<mpsc::Receiver<Job>>>) -> Worker {
-        let thread = thread::spawn(move || loop {
-            let message = receiver.lock().unwrap().recv();
-
-            match message {
-                Ok(job) => {
-                    job();
-                }
-                Err(_) => {
-                    break;
+    fn new(id: usize, receiver: Arc<Mutex<mpsc::Receiver<Message>>>) -> Worker {
+        // 线程名会出现在运行日志中，用来区分是哪个工作线程
+        let thread = thread::Builder::new()
+            .name(format!("worker-{}", id))
+            .spawn(move || loop {
+                let message = receiver.lock().unwrap().recv();
+
+                match message {
+                    Ok(Message::Job(job)) => {
+                        // 任务 panic 时只记录下来，线程继续处理后续任务
+                        if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(job)) {
+                            error!(worker = id, "worker panicked: {}", panic_message(&*payload));
+                        }
+                    }
+                    Ok(Message::Terminate) | Err(_) => {
+                        break;
+                    }
                 }
-            }
-        });
+            })
+            .expect("failed to spawn worker thread");
 
         Worker {
-            _id,
+            _id: id,
             thread: Some(thread),
         }
     }
 }
 
-pub fn handle_connection(mut stream: TcpStream) -> Result<(), Box<dyn Error>> {
-    let mut buffer = [0; 1024];
-    stream.read(&mut buffer)?;
+/// 取出 panic 携带的字符串信息
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message
+    } else {
+        "unknown panic payload"
+    }
+}
 
-    let (method, path, _) = parse_request(&buffer)?;
+/// 按配置组装内置的中间件
+pub fn build_middleware(config: &ServerConfig) -> MiddlewareStack {
+    let mut middleware = MiddlewareStack::new();
+    middleware.push(middleware::ContentHeaders);
+    if let Some(cors) = &config.cors {
+        middleware.push(middleware::Cors(cors.clone()));
+    }
+    middleware
+}
 
-    let (status_line, content_type, contents) = if method != "GET" || method != "POST" {
-        let contents = fs::read_to_string("static/501.html")?;
-        ("HTTP/1.1 501 OK", "text/html", contents)
-    } else {
-        match (method.as_str(), path.as_str()) {
-            ("GET", "/") | ("GET", "/index.html") => read_static_file("static/index.html")?,
-            ("GET", "/501.html") => {
-                let contents = fs::read_to_string("static/501.html")?;
-                (
-                    "HTTP/1.1 501 Not Implemented",
-                    detect_content_type("static/404.html"),
-                    contents,
-                )
+/// 注册服务器内置的全部路由
+pub fn build_router(config: &Arc<ServerConfig>) -> Router {
+    let mut router = Router::new();
+
+    // 数据文件无法加载时以空数组启动，修改接口对所有 id 返回 404
+    let items = Arc::new(ItemStore::load(DATA_FILE).unwrap_or_else(|err| {
+        warn!(path = DATA_FILE, error = %err, "cannot load data file");
+        ItemStore::new(DATA_FILE, Vec::new())
+    }));
+    ItemStore::watch(&items, DATA_RELOAD_INTERVAL);
+
+    router
+        .get("/", {
+            let config = Arc::clone(config);
+            move |request, _| {
+                let response = read_static_file(&config.root.join("index.html"), request, &config)?;
+                Ok(with_cache_control(
+                    response,
+                    &config.cache_rules,
+                    "/index.html",
+                ))
             }
-            ("GET", "/api/check") => read_static_file("data/data.txt")?,
-            ("GET", "/api/list") => read_static_file("data/data.json")?,
-            ("POST", "/api/echo") => handle_echo_request(&buffer)?,
-            ("POST", "/api/upload") => handle_upload_request(&buffer)?,
-            ("GET", path) if path.starts_with("/api/search") => handle_search_request(path)?,
-            ("GET", path) if path.ends_with(".html") => {
-                read_static_file(&format!("static{}", path))?
+        })
+        .get("/501.html", {
+            let config = Arc::clone(config);
+            move |_, _| Ok(error_page(&config, 501))
+        })
+        .get("/health", {
+            let config = Arc::clone(config);
+            move |_, _| {
+                let health = json!({
+                    "status": "ok",
+                    "uptime_seconds": config.state.started.elapsed().as_secs(),
+                    "version": config.state.version,
+                });
+                Ok(Response::ok()
+                    .content_type("application/json")
+                    .body_text(health.to_string()))
+            }
+        })
+        .get("/ready", {
+            let config = Arc::clone(config);
+            move |_, _| {
+                let (status, ready) = match config.state.is_ready() {
+                    true => (200, "ready"),
+                    false => (503, "starting"),
+                };
+                Ok(Response::new(status)
+                    .content_type("application/json")
+                    .body_text(json!({ "status": ready }).to_string()))
+            }
+        })
+        .get("/metrics", {
+            let config = Arc::clone(config);
+            move |_, _| {
+                Ok(Response::ok()
+                    .content_type("text/plain; version=0.0.4")
+                    .body_text(config.metrics.render()))
+            }
+        })
+        .get("/api/events", {
+            let config = Arc::clone(config);
+            move |_, _| Ok(config.events.response())
+        })
+        .get("/api/check", {
+            let config = Arc::clone(config);
+            move |request, _| {
+                let path = Path::new("data/data.txt");
+                if path.exists() {
+                    read_static_file(path, request, &config)
+                } else {
+                    Ok(Response::ok()
+                        .content_type("text/plain")
+                        .body_bytes(assets::data_file("data/data.txt").into_owned()))
+                }
             }
-            ("GET", path) if path.ends_with(".js") => read_static_file(&format!("static{}", path))?,
-            ("GET", path) if path.ends_with(".json") => {
-                read_static_file(&format!("static{}", path))?
+        })
+        .get("/api/list", {
+            let items = Arc::clone(&items);
+            move |request, _| handle_list_request(request.query(), &items)
+        })
+        .post("/api/echo", |request, _| Ok(handle_echo_request(request)))
+        .post("/api/echo/form", |request, _| {
+            handle_echo_form_request(&request.body)
+        })
+        .post("/api/upload", {
+            let config = Arc::clone(config);
+            let items = Arc::clone(&items);
+            move |request, _| handle_upload_request(request, &config.upload_dir, &items)
+        })
+        .get("/api/search", {
+            let items = Arc::clone(&items);
+            move |request, _| handle_search_request(request.query(), &items)
+        })
+        .put("/api/items/:id", {
+            let items = Arc::clone(&items);
+            move |request, params| handle_replace_item(request, params, &items)
+        })
+        .patch("/api/items/:id", {
+            let items = Arc::clone(&items);
+            move |request, params| handle_patch_item(request, params, &items)
+        })
+        .delete("/api/items/:id", {
+            let items = Arc::clone(&items);
+            move |_, params| handle_delete_item(params, &items)
+        })
+        .put("/api/records", {
+            let items = Arc::clone(&items);
+            move |request, _| handle_replace_record(request, &items)
+        })
+        .delete("/api/records", {
+            let items = Arc::clone(&items);
+            move |request, _| handle_delete_record(request, &items)
+        })
+        .get("/*", {
+            let config = Arc::clone(config);
+            move |request, _| handle_static_request(request, &config)
+        })
+        .fallback({
+            let config = Arc::clone(config);
+            move |_, _| Ok(not_found(&config))
+        })
+        .method_not_allowed({
+            let config = Arc::clone(config);
+            move |_, _| Ok(error_page(&config, 405))
+        });
+
+    router
+}
+
+/// 客户端连接：keep-alive 连接在等待下一个请求前重新设置读取超时，访问日志记录对端地址
+pub trait Connection: Read + Write {
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()>;
+    fn peer_addr(&self) -> io::Result<SocketAddr>;
+}
+
+impl Connection for TcpStream {
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        TcpStream::set_read_timeout(self, timeout)
+    }
+
+    fn peer_addr(&self) -> io::Result<SocketAddr> {
+        TcpStream::peer_addr(self)
+    }
+}
+
+impl Connection for tls::TlsStream {
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.sock.set_read_timeout(timeout)
+    }
+
+    fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.sock.peer_addr()
+    }
+}
+
+impl<C: Connection + ?Sized> Connection for &mut C {
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        (**self).set_read_timeout(timeout)
+    }
+
+    fn peer_addr(&self) -> io::Result<SocketAddr> {
+        (**self).peer_addr()
+    }
+}
+
+/// 在一个连接上依次处理请求，直到客户端要求关闭、连接断开、空闲超过 keep-alive 超时
+/// 或者达到单个连接的请求数上限
+///
+/// 处理过程中的错误按 [`ServerError::status`] 转换为错误响应，读写连接本身的错误
+/// 记录到当前连接的 span 后关闭连接，不会返回给调用方；每个得到响应的请求写一条访问日志
+#[instrument(skip_all, fields(peer))]
+pub fn handle_connection(
+    stream: impl Connection,
+    config: &ServerConfig,
+    router: &Router,
+    middleware: &MiddlewareStack,
+    logger: &Mutex<Logger>,
+) {
+    let peer = stream.peer_addr().ok().map(|addr| addr.ip());
+    if let Some(peer) = peer {
+        Span::current().record("peer", tracing::field::display(peer));
+    }
+    info!("connection accepted");
+    config.metrics.connection_opened();
+
+    let mut reader = BufReader::new(stream);
+    let context = RequestContext {
+        peer,
+        middleware,
+        logger,
+    };
+    let mut served = 0;
+
+    loop {
+        // keep-alive 连接在空闲超时内没有开始发送下一个请求时直接关闭，不回复 408
+        if served > 0 {
+            match wait_for_next_request(&mut reader, config.keepalive_timeout) {
+                Ok(true) => {}
+                Ok(false) => break,
+                Err(err) => {
+                    log_connection_error(&err, &config.metrics);
+                    break;
+                }
             }
-            ("GET", path) if path.ends_with(".css") => {
-                read_static_file(&format!("static{}", path))?
+        }
+        if let Err(err) = reader.get_ref().set_read_timeout(Some(config.read_timeout)) {
+            log_connection_error(&err.into(), &config.metrics);
+            break;
+        }
+
+        served += 1;
+        match handle_request(&mut reader, config, router, &context, served) {
+            Ok(true) => {}
+            Ok(false) => break,
+            Err(err) => {
+                // 新连接没有发送请求，或者请求没有在读取超时内读完时回复 408
+                if matches!(&err, ServerError::Io(err) if request::is_timeout(err)) {
+                    let response = error_page(config, 408);
+                    let _ = write_response(reader.get_mut(), response, config, None, true, false);
+                }
+                log_connection_error(&err, &config.metrics);
+                break;
             }
-            _ => {
-                let contents = fs::read_to_string("static/404.html")?;
-                (
-                    "HTTP/1.1 404 NOT FOUND",
-                    detect_content_type("static/404.html"),
-                    contents,
-                )
+        }
+    }
+
+    config.metrics.connection_closed();
+    info!("connection closed");
+}
+
+/// 等待 keep-alive 连接上下一个请求的第一个字节，空闲超时或者客户端关闭连接时返回 `false`
+fn wait_for_next_request<S: Connection>(
+    reader: &mut BufReader<S>,
+    timeout: Duration,
+) -> Result<bool, ServerError> {
+    reader.get_ref().set_read_timeout(Some(timeout))?;
+    match reader.fill_buf() {
+        Ok(buf) => Ok(!buf.is_empty()),
+        Err(err) if request::is_timeout(&err) => Ok(false),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// 超时前客户端没有发送任何数据时不记录；客户端中途断开只记录为 debug，
+/// 其他错误同时计入运行指标
+fn log_connection_error(err: &ServerError, metrics: &Metrics) {
+    match err {
+        ServerError::Io(err) if request::is_timeout(err) => {}
+        ServerError::Io(err)
+            if matches!(
+                err.kind(),
+                io::ErrorKind::BrokenPipe
+                    | io::ErrorKind::ConnectionReset
+                    | io::ErrorKind::ConnectionAborted
+                    | io::ErrorKind::UnexpectedEof
+            ) =>
+        {
+            debug!(error = %err, "client disconnected");
+        }
+        err => {
+            metrics.connection_error();
+            warn!(error = %err, "connection error");
+        }
+    }
+}
+
+/// 同一连接上的请求共用的信息
+struct RequestContext<'a> {
+    peer: Option<IpAddr>,
+    middleware: &'a MiddlewareStack,
+    logger: &'a Mutex<Logger>,
+}
+
+/// 读取并处理一个请求，返回连接是否可以继续用于下一个请求，`served` 是这个连接上包括当前请求在内的请求数
+fn handle_request<S: Connection>(
+    reader: &mut BufReader<S>,
+    config: &ServerConfig,
+    router: &Router,
+    context: &RequestContext,
+    served: u64,
+) -> Result<bool, ServerError> {
+    let head = match read_raw_head(reader, &config.limits) {
+        Ok(head) => head,
+        Err(err) => return reject_request(reader.get_mut(), err, config),
+    };
+    let started = Instant::now();
+
+    // 接受连接时已经为第一个请求消耗了令牌，keep-alive 连接上之后的每个请求再各消耗一个；
+    // 超过限制时不读取请求体，回复 429 后关闭连接
+    if let (true, Some(limiter), Some(peer)) = (served > 1, &config.rate_limiter, context.peer) {
+        if let Err(retry_after) = limiter.check(peer) {
+            let response = retry_later(config, 429, retry_after);
+            write_response(reader.get_mut(), response, config, None, true, false)?;
+            return Ok(false);
+        }
+    }
+
+    // 客户端等待 `100 Continue` 才发送请求体；请求不会被接受时直接返回最终响应，
+    // 请求体不会被读取，之后也不能继续使用这个连接
+    if let Ok(request) = Request::from_bytes(&head) {
+        if let Some(response) = check_expectation(request.clone(), config, router) {
+            write_response(reader.get_mut(), response, config, None, true, false)?;
+            return Ok(false);
+        }
+        if expects_continue(&request) {
+            reader
+                .get_mut()
+                .write_all(b"HTTP/1.1 100 Continue\r\n\r\n")?;
+        }
+    }
+
+    let raw_request = match read_raw_body(reader, head, &config.limits) {
+        Ok(raw_request) => raw_request,
+        Err(err) => return reject_request(reader.get_mut(), err, config),
+    };
+    config.metrics.request_received(raw_request.len() as u64);
+
+    // 上游的响应以关闭连接结束，转发后不能继续使用这个连接；受保护的路径先认证再转发
+    if let Some(target) = &config.proxy {
+        match authorize_proxy_request(&raw_request, config) {
+            Ok(()) => proxy::proxy_request(reader.get_mut(), target, &raw_request, config)?,
+            Err(response) => {
+                write_response(reader.get_mut(), response, config, None, true, false)?;
             }
         }
+        return Ok(false);
+    }
+
+    let mut request = match Request::from_bytes(&raw_request) {
+        Ok(request) => request,
+        Err(err) => {
+            let response = error_response(&err.into(), config);
+            write_response(reader.get_mut(), response, config, None, true, false)?;
+            return Ok(false);
+        }
+    };
+    let mut keep_alive = served < config.max_keepalive_requests
+        && !config.keepalive_timeout.is_zero()
+        && !config.state.is_shutting_down()
+        && wants_keep_alive(&request);
+
+    // 日志记录客户端发来的原始请求行，路由时路径会被解码
+    let mut record = LogRecord {
+        ip: context.peer,
+        time: SystemTime::now(),
+        method: request.method.clone(),
+        path: match &request.query {
+            Some(query) => format!("{}?{}", request.path, query),
+            None => request.path.clone(),
+        },
+        protocol: request.version.clone(),
+        status: 0,
+        bytes: 0,
+        referer: request.header("referer").map(String::from),
+        user_agent: request.header("user-agent").map(String::from),
+        duration: Duration::ZERO,
+    };
+
+    // 认证失败的升级请求按普通请求处理，得到 401
+    if let Some(route) = &config.websocket {
+        if request.path == route.path
+            && websocket::is_upgrade_request(&request)
+            && authenticate(&mut request, config).is_ok()
+        {
+            return upgrade_to_websocket(reader, &request, route, config, context, record, started);
+        }
+    }
+
+    // HEAD 请求按 GET 处理，写出时去掉响应体
+    let head_only = request.method == "HEAD";
+    if head_only {
+        request.method = String::from("GET");
+    }
+
+    // 中间件拿到的是只读的请求，路由处理时解码路径、保存 JWT 声明需要修改，所以复制一份
+    let handler = |request: &Request| {
+        let mut request = request.clone();
+        respond(&mut request, config, router).unwrap_or_else(|err| error_response(&err, config))
+    };
+
+    // 处理函数 panic 时连接仍然可用，回复 500 后关闭连接，工作线程不受影响
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        context.middleware.call(&request, &handler)
+    }));
+    let mut response = match result {
+        Ok(response) => response,
+        Err(payload) => {
+            error!("handler panicked: {}", panic_message(&*payload));
+            keep_alive = false;
+            error_page(config, 500)
+        }
+    };
+    if request.version == "HTTP/1.0" {
+        response.downgrade_to_http_1_0();
+        keep_alive = keep_alive && !response.is_close_delimited();
+    }
+    record.status = response.status;
+    let accept_encoding = request.header("accept-encoding").unwrap_or("");
+    record.bytes = write_response(
+        reader.get_mut(),
+        response,
+        config,
+        Some(accept_encoding),
+        !head_only,
+        keep_alive,
+    )?;
+    finish_request(&mut record, started, config, context);
+    let route = router.matched_pattern(&request.method, &request.path);
+    config.metrics.record_route(route.unwrap_or(STATIC_ROUTE));
+
+    Ok(keep_alive)
+}
+
+/// 记录已经写出响应的请求：填上处理耗时，更新运行指标并写访问日志
+fn finish_request(
+    record: &mut LogRecord,
+    started: Instant,
+    config: &ServerConfig,
+    context: &RequestContext,
+) {
+    record.duration = started.elapsed();
+    config
+        .metrics
+        .record_request(record.status, record.bytes, record.duration);
+
+    let path = record.path.split('?').next().unwrap_or_default();
+    if !config.log_probes && PROBE_PATHS.contains(&path) {
+        return;
+    }
+
+    // 只在写日志时持有锁
+    if let Err(err) = context.logger.lock().unwrap().log(record) {
+        error!(error = %err, "failed to write access log");
+    }
+}
+
+/// 完成 WebSocket 握手后由 [`websocket::serve`] 接管连接，握手失败时回复 400；两种情况都不再复用连接
+fn upgrade_to_websocket<S: Connection>(
+    reader: &mut BufReader<S>,
+    request: &Request,
+    route: &WebSocketRoute,
+    config: &ServerConfig,
+    context: &RequestContext,
+    mut record: LogRecord,
+    started: Instant,
+) -> Result<bool, ServerError> {
+    let response =
+        websocket::handshake_response(request).unwrap_or_else(|| error_page(config, 400));
+    let upgraded = response.status == 101;
+    record.status = response.status;
+    record.bytes = write_response(reader.get_mut(), response, config, None, true, false)?;
+    finish_request(&mut record, started, config, context);
+    if !upgraded {
+        return Ok(false);
+    }
+
+    // 消息之间可以长时间没有数据，改用空闲超时，超时后由 [`websocket::serve`] 发送 Ping
+    reader
+        .get_ref()
+        .set_read_timeout(Some(route.idle_timeout))?;
+    match websocket::serve(reader, &route.handler, config.limits.max_body_size) {
+        Ok(()) => debug!("websocket closed"),
+        Err(websocket::WebSocketError::Io(err)) => return Err(err.into()),
+        Err(err) => debug!(error = %err, "websocket closed with error"),
+    }
+    Ok(false)
+}
+
+/// 读取请求失败时写出对应的错误响应，之后关闭连接
+fn reject_request(
+    stream: &mut impl Write,
+    err: RequestError,
+    config: &ServerConfig,
+) -> Result<bool, ServerError> {
+    let response = match err {
+        RequestError::HeaderTooLarge => error_page(config, 431),
+        RequestError::BodyTooLarge => error_page(config, 413),
+        RequestError::Timeout => error_page(config, 408),
+        // 客户端没有发送新的请求就关闭了连接
+        RequestError::Parse(ParseError::EmptyRequest) => return Ok(false),
+        RequestError::Io(err) => return Err(err.into()),
+        err => error_response(&err.into(), config),
     };
-    let response = format!(
-        "{}\r\nContent-Type: {}\r\nContent-Length: {}\r\n\r\n{}",
-        status_line,
-        content_type,
-        contents.len(),
-        contents
-    );
+    write_response(stream, response, config, None, true, false)?;
+    Ok(false)
+}
 
-    stream.write_all(response.as_bytes())?;
-    stream.flush()?;
+/// HTTP/1.1 请求带有 `Expect: 100-continue` 且有请求体
+fn expects_continue(request: &Request) -> bool {
+    request.version == "HTTP/1.1"
+        && request
+            .header("expect")
+            .is_some_and(|expect| expect.trim().eq_ignore_ascii_case("100-continue"))
+        && (request.content_length().is_ok_and(|length| length > 0)
+            || request.is_chunked().unwrap_or(false))
+}
+
+/// 在回复 `100 Continue` 之前判断请求能否被接受，不能接受时返回 400、404、405 或 417 的最终响应
+///
+/// 不认识的期望一律回复 417；路径先解码再查路由，与之后处理请求时一致；转发的请求由上游判断
+fn check_expectation(
+    mut request: Request,
+    config: &ServerConfig,
+    router: &Router,
+) -> Option<Response> {
+    let expect = request.header("expect")?;
+    if config.proxy.is_some() {
+        return None;
+    }
+    if !expect.trim().eq_ignore_ascii_case("100-continue") {
+        return Some(error_page(config, 417));
+    }
+    if !expects_continue(&request) {
+        return None;
+    }
 
+    if decode_request_path(&mut request).is_err() {
+        return Some(error_page(config, 400));
+    }
+    let allowed = router.allowed_methods(&request.path);
+    if allowed.contains(&request.method) {
+        None
+    } else if !allowed.is_empty() && resource_exists(&request, &allowed, router).unwrap_or(true) {
+        Some(error_response(
+            &ServerError::MethodNotAllowed(allowed),
+            config,
+        ))
+    } else {
+        Some(not_found(config))
+    }
+}
+
+/// HTTP/1.1 默认保持连接，除非请求带有 `Connection: close`；HTTP/1.0 需要显式的 `Connection: keep-alive`
+fn wants_keep_alive(request: &Request) -> bool {
+    let connection = request.header("connection").unwrap_or("");
+    let has_token = |token: &str| {
+        connection
+            .split(',')
+            .any(|value| value.trim().eq_ignore_ascii_case(token))
+    };
+
+    if request.version == "HTTP/1.0" {
+        has_token("keep-alive")
+    } else {
+        !has_token("close")
+    }
+}
+
+/// 写出全部响应后关闭连接：先关闭写方向发送 FIN，再读取并丢弃客户端还没被读取的数据，
+/// 直到客户端也关闭连接、超过 `linger` 或者超过数据量上限
+///
+/// 接收缓冲区中还有数据时直接关闭套接字会发送 RST，客户端可能因此丢掉还没读取的响应；
+/// `linger` 为 0 时只丢弃已经收到的数据，不等待客户端，用于不能阻塞的接受线程
+pub fn close_connection(stream: &TcpStream, linger: Duration) {
+    if stream.shutdown(Shutdown::Write).is_err() {
+        return;
+    }
+    if linger.is_zero() && stream.set_nonblocking(true).is_err() {
+        return;
+    }
+
+    let deadline = Instant::now() + linger;
+    let mut reader = stream;
+    let mut buffer = [0; 4096];
+    let mut drained = 0;
+    while drained < MAX_LINGER_BYTES {
+        if !linger.is_zero() {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() || stream.set_read_timeout(Some(remaining)).is_err() {
+                break;
+            }
+        }
+        match reader.read(&mut buffer) {
+            Ok(0) | Err(_) => break,
+            Ok(len) => drained += len,
+        }
+    }
+}
+
+/// 不交给线程池处理的连接直接回复错误状态并关闭，不读取请求，例如线程池繁忙时的 503
+pub fn reject_connection(
+    mut stream: impl Write,
+    status: u16,
+    retry_after: Duration,
+    config: &ServerConfig,
+) -> Result<(), ServerError> {
+    let response = retry_later(config, status, retry_after);
+    write_response(&mut stream, response, config, None, true, false)?;
+    Ok(())
+}
+
+/// 带 `Retry-After` 的错误页面，用于 429 和 503
+fn retry_later(config: &ServerConfig, status: u16, retry_after: Duration) -> Response {
+    // Retry-After 只能是整数秒，向上取整
+    let retry_after = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+    error_page(config, status).header("Retry-After", &retry_after.max(1).to_string())
+}
+
+/// 把处理请求时的错误转换为对应状态码的错误页面，服务器自身的错误同时记录下来
+fn error_response(err: &ServerError, config: &ServerConfig) -> Response {
+    let status = err.status();
+    if status >= 500 {
+        warn!(error = %err, "request failed");
+    }
+
+    let response = error_page(config, status);
+    match err {
+        ServerError::MethodNotAllowed(allowed) => response.header("Allow", &allowed.join(", ")),
+        _ => response,
+    }
+}
+
+/// 写出响应前加上所有响应共有的响应头，开启压缩时按请求的 `Accept-Encoding` 压缩响应体，
+/// 返回写出的响应体字节数
+fn write_response(
+    stream: &mut impl Write,
+    mut response: Response,
+    config: &ServerConfig,
+    accept_encoding: Option<&str>,
+    include_body: bool,
+    keep_alive: bool,
+) -> io::Result<u64> {
+    if let (true, Some(accept_encoding)) = (config.compress, accept_encoding) {
+        response.compress_if_accepted(accept_encoding);
+    }
+    if !config.server_banner.is_empty() {
+        response = response.header("Server", &config.server_banner);
+    }
+    // 101 响应的 `Connection: Upgrade` 由握手响应给出
+    if response.status != 101 {
+        let connection = if keep_alive { "keep-alive" } else { "close" };
+        response = response.header("Connection", connection);
+    }
+    match response.status {
+        500.. => warn!(status = response.status, "server error response"),
+        400..=499 => debug!(status = response.status, "client error response"),
+        _ => {}
+    }
+    match response.body {
+        ResponseBody::File {
+            ref path,
+            offset: 0,
+            length,
+        } if include_body
+            && response.status == 200
+            && length > STREAM_FILE_THRESHOLD
+            && response.version != "HTTP/1.0" =>
+        {
+            write_file_chunked(stream, &response, path, length)?
+        }
+        _ => response.write(stream, include_body)?,
+    }
+
+    Ok(if include_body { response.body.len() } else { 0 })
+}
+
+/// 用 [`stream_response`] 发送整个文件，每次读取 [`STREAM_CHUNK_SIZE`] 字节作为一个块
+///
+/// 读取出错时已经写出的块无法撤回，错误在结束块之后返回，调用方随后关闭连接
+fn write_file_chunked(
+    stream: &mut impl Write,
+    response: &Response,
+    path: &Path,
+    length: u64,
+) -> io::Result<()> {
+    let file = fs::File::open(path)?.take(length);
+    let mut reader = BufReader::with_capacity(STREAM_CHUNK_SIZE, file);
+    let mut error = None;
+    let chunks = iter::from_fn(|| match reader.fill_buf() {
+        Ok([]) => None,
+        Ok(chunk) => {
+            let chunk = chunk.to_vec();
+            reader.consume(chunk.len());
+            Some(chunk)
+        }
+        Err(err) => {
+            error = Some(err);
+            None
+        }
+    });
+
+    let headers: Vec<(&str, &str)> = response
+        .headers
+        .iter()
+        .map(|(name, value)| (name.as_str(), value.as_str()))
+        .collect();
+    stream_response(stream, response.status, &headers, chunks)?;
+    error.map_or(Ok(()), Err)
+}
+
+/// 为一个已解析的请求计算响应，不涉及写出
+fn respond(
+    request: &mut Request,
+    config: &ServerConfig,
+    router: &Router,
+) -> Result<Response, ServerError> {
+    if decode_request_path(request).is_err() {
+        return Ok(error_page(config, 400));
+    }
+
+    // CORS 预检请求不带凭据，不需要认证
+    if request.method == "OPTIONS" {
+        handle_options_request(request, config, router)
+    } else if let Err(challenge) = authenticate(request, config) {
+        Ok(error_page(config, 401).header("WWW-Authenticate", &challenge))
+    } else if !router.supports_method(&request.method)
+        || (!GENERAL_METHODS.contains(&request.method.as_str())
+            && router
+                .matched_pattern(&request.method, &request.path)
+                .is_none())
+    {
+        Ok(error_page(config, 501))
+    } else {
+        // 通配路由匹配任何路径，方法不被允许时先确认资源存在，不存在的资源回复 404 而不是 405
+        let allowed = router.allowed_methods(&request.path);
+        if !allowed.is_empty()
+            && !allowed.contains(&request.method)
+            && !resource_exists(request, &allowed, router)?
+        {
+            return Ok(not_found(config));
+        }
+        router.handle(request)
+    }
+}
+
+/// 检查 Basic 认证和 JWT，通过 JWT 认证时把声明保存到请求中，失败时返回 `WWW-Authenticate` 的取值
+fn authenticate(request: &mut Request, config: &ServerConfig) -> Result<(), String> {
+    if let Some(auth) = &config.auth {
+        if auth.protects(&request.path) && !auth.authorize(request) {
+            return Err(auth.challenge());
+        }
+    }
+
+    if let Some(jwt) = &config.jwt {
+        if jwt.protects(&request.path) {
+            request.claims = Some(jwt.verify(request).ok_or_else(|| String::from("Bearer"))?);
+        }
+    }
+
+    Ok(())
+}
+
+/// 转发前的认证，与本地处理请求时相同；失败时返回 400 或 401 响应
+fn authorize_proxy_request(raw_request: &[u8], config: &ServerConfig) -> Result<(), Response> {
+    if config.auth.is_none() && config.jwt.is_none() {
+        return Ok(());
+    }
+    let mut request = Request::from_bytes(raw_request).map_err(|_| error_page(config, 400))?;
+    decode_request_path(&mut request).map_err(|_| error_page(config, 400))?;
+    authenticate(&mut request, config)
+        .map_err(|challenge| error_page(config, 401).header("WWW-Authenticate", &challenge))
+}
+
+/// 在路由和拼接文件路径之前解码路径中的 `%XX`，查询字符串不在路径中，不受影响
+///
+/// 解码出的 `/` 会改变路径段的边界，视为错误
+fn decode_request_path(request: &mut Request) -> Result<(), url::DecodeError> {
+    let decoded = url::path_decode(&request.path)?;
+    if decoded.matches('/').count() != request.path.matches('/').count() {
+        return Err(url::DecodeError::EncodedSlash);
+    }
+    request.path = decoded;
     Ok(())
 }
 
-fn parse_request(buffer: &[u8]) -> Result<(String, String, String), Box<dyn Error>> {
-    let request = String::from_utf8_lossy(buffer);
-    let mut lines = request.lines();
+/// 根据路由表回答 OPTIONS 请求，配置了 CORS 时同时处理预检请求
+fn handle_options_request(
+    request: &Request,
+    config: &ServerConfig,
+    router: &Router,
+) -> Result<Response, ServerError> {
+    let mut allowed = router.allowed_methods(&request.path);
+    if allowed.is_empty() || !resource_exists(request, &allowed, router)? {
+        return Ok(not_found(config));
+    }
+    allowed.push(String::from("OPTIONS"));
 
-    let first_line = lines.next().ok_or("Empty request")?;
-    let mut parts = first_line.split_whitespace();
+    let response = Response::new(204).header("Allow", &allowed.join(", "));
 
-    let method = parts.next().ok_or("Invalid method")?.to_string();
-    let path = parts.next().ok_or("Invalid path")?.to_string();
-    let protocol = parts.next().ok_or("Invalid protocol")?.to_string();
+    Ok(match &config.cors {
+        Some(cors) => cors::preflight(response, request, &allowed, cors),
+        None => response,
+    })
+}
 
-    Ok((method, path, protocol))
+/// 只能 GET 的路径可能由通配路由匹配，需要实际处理一次 GET 才知道资源是否存在
+fn resource_exists(
+    request: &Request,
+    allowed: &[String],
+    router: &Router,
+) -> Result<bool, ServerError> {
+    if allowed
+        .iter()
+        .any(|method| method != "GET" && method != "HEAD")
+    {
+        return Ok(true);
+    }
+
+    let mut probe = request.clone();
+    probe.method = String::from("GET");
+    probe.headers.remove("range");
+    Ok(router.handle(&probe)?.status != 404)
 }
 
-fn read_static_file(path: &str) -> Result<(&'static str, &'static str, String), Box<dyn Error>> {
-    let contents = fs::read_to_string(path)?;
-    Ok(("HTTP/1.1 200 OK", detect_content_type(path), contents))
+fn read_static_file(
+    path: &Path,
+    request: &Request,
+    config: &ServerConfig,
+) -> Result<Response, ServerError> {
+    read_file_as(
+        path,
+        detect_content_type(&path.to_string_lossy()),
+        None,
+        request,
+        config,
+    )
 }
 
-fn detect_content_type(path: &str) -> &'static str {
-    match path {
-        _ if path.ends_with(".html") => "text/html",
-        _ if path.ends_with(".js") => "text/javascript",
-        _ if path.ends_with(".json") => "application/json",
-        _ if path.ends_with(".css") => "text/css",
-        _ => "text/plain",
+/// 以指定的 `Content-Type` 返回文件，文件不存在时返回 404，文件内容在写出时才分块读取
+///
+/// 超过 [`STREAM_FILE_THRESHOLD`] 的文件整体返回时由 [`write_response`] 分块发送，
+/// HEAD 请求仍然得到文件的 `Content-Length`
+///
+/// 客户端缓存仍然有效时返回 304；请求带有 `Range` 时只读取对应的区间并返回 206，
+/// 区间无法满足时返回 416；`encoding` 表示文件是预压缩的表示，响应加上对应的 `Content-Encoding`
+fn read_file_as(
+    path: &Path,
+    content_type: &str,
+    encoding: Option<&str>,
+    request: &Request,
+    config: &ServerConfig,
+) -> Result<Response, ServerError> {
+    let metadata = match fs::metadata(path) {
+        Ok(metadata) => metadata,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(not_found(config)),
+        Err(err) => return Err(err.into()),
+    };
+    let total = metadata.len();
+
+    let modified = metadata.modified().ok();
+    let last_modified = modified.map(datetime::format_http_date).unwrap_or_default();
+
+    let etag = match encoding {
+        Some(encoding) => cache::encoded_etag(&cache::etag_from_metadata(&metadata), encoding),
+        None => cache::etag_from_metadata(&metadata),
+    };
+    // 开启压缩时客户端缓存的可能是压缩后的表示，它的 ETag 带有 `-gzip` 后缀
+    let gzip_etag =
+        (encoding.is_none() && config.compress).then(|| cache::encoded_etag(&etag, "gzip"));
+    let cached = iter::once(&etag)
+        .chain(&gzip_etag)
+        .find(|etag| cache::is_not_modified(request, etag, modified));
+    if let Some(cached) = cached {
+        let response = Response::new(304).header("ETag", cached);
+        let response = match gzip_etag {
+            Some(_) => response.header("Vary", "Accept-Encoding"),
+            None => with_content_encoding(response, encoding),
+        };
+        return Ok(with_last_modified(response, &last_modified));
     }
+
+    let range = match request.header("range") {
+        Some(range) => range::parse_range(range, total),
+        None => Ok(None),
+    };
+
+    let response = match range {
+        Ok(Some((start, end))) => Response::new(206)
+            .header(
+                "Content-Range",
+                &format!("bytes {}-{}/{}", start, end, total),
+            )
+            .body_file(path, start, end - start + 1),
+        Ok(None) => Response::ok().body_file(path, 0, total),
+        Err(range::RangeNotSatisfiable) => {
+            return Ok(Response::new(416)
+                .header("Content-Range", &format!("bytes */{}", total))
+                .header("Accept-Ranges", "bytes"));
+        }
+    };
+
+    let response = response
+        .content_type(content_type)
+        .header("Accept-Ranges", "bytes")
+        .header("ETag", &etag);
+    let response = with_content_encoding(response, encoding);
+    Ok(with_last_modified(response, &last_modified))
 }
 
-fn handle_echo_request(
-    buffer: &[u8],
-) -> Result<(&'static str, &'static str, String), Box<dyn Error>> {
-    let body_string: String = String::from_utf8_lossy(buffer).to_string();
-    let body: Vec<&str> = body_string.trim_matches('\0').split("\r\n\r\n").collect();
+/// 预压缩文件的响应加上 `Content-Encoding` 和 `Vary`，304 没有响应体，只加 `Vary`
+fn with_content_encoding(response: Response, encoding: Option<&str>) -> Response {
+    match encoding {
+        Some(_) if response.status == 304 => response.header("Vary", "Accept-Encoding"),
+        Some(encoding) => response
+            .header("Content-Encoding", encoding)
+            .header("Vary", "Accept-Encoding"),
+        None => response,
+    }
+}
 
-    let data = if let Some(body) = body.get(1) {
-        *body
+/// 文件系统提供了修改时间时加上 `Last-Modified`
+fn with_last_modified(response: Response, last_modified: &str) -> Response {
+    if last_modified.is_empty() {
+        response
     } else {
-        return Err("No body found in the request".into());
+        response.header("Last-Modified", last_modified)
+    }
+}
+
+/// 提供根目录下任意已存在的文件，未知扩展名按 `application/octet-stream` 返回，
+/// 目录交给 [`handle_directory_request`]
+fn handle_static_request(
+    request: &Request,
+    config: &ServerConfig,
+) -> Result<Response, ServerError> {
+    let file = match sanitize_path(&config.root, &request.path) {
+        Ok(file) => file,
+        Err(err) => {
+            debug!(path = %request.path, error = %err, "rejected static path");
+            return Ok(error_page(config, 403));
+        }
     };
 
-    let re = Regex::new("id=[0-9]+&name=[a-zA-Z0-9]+")?;
+    if file.is_dir() {
+        if !request.path.ends_with('/') {
+            return Ok(redirect_to_directory(request));
+        }
+        return handle_directory_request(request, &file, config);
+    }
+    if !file.is_file() {
+        if config.spa && is_spa_route(request) {
+            let index = config.root.join("index.html");
+            let response = read_file_as(&index, "text/html", None, request, config)?;
+            return Ok(with_cache_control(
+                response,
+                &config.cache_rules,
+                "/index.html",
+            ));
+        }
+        return Ok(not_found(config));
+    }
 
-    match re.is_match(data) {
-        true => Ok((
-            "HTTP/1.1 200 OK",
-            "application/x-www-form-urlencoded",
-            format!("{data}"),
-        )),
-        false => Ok((
-            "HTTP/1.1 403 Data format error",
-            "text/plain",
-            fs::read_to_string("data/error.txt")?,
-        )),
+    let content_type = config.mime_types.lookup(&file.to_string_lossy());
+    let response = match precompressed_sibling(request, &config.root) {
+        Some((sibling, encoding)) => {
+            read_file_as(&sibling, content_type, Some(encoding), request, config)?
+        }
+        None => read_file_as(&file, content_type, None, request, config)?,
+    };
+    Ok(with_cache_control(
+        response,
+        &config.cache_rules,
+        &request.path,
+    ))
+}
+
+/// 客户端接受时查找同目录下预压缩的 `.br` 或 `.gz` 文件，brotli 优先，
+/// 返回文件路径和对应的 `Content-Encoding`
+fn precompressed_sibling(request: &Request, root: &Path) -> Option<(PathBuf, &'static str)> {
+    let accept_encoding = request.header("accept-encoding")?;
+
+    PRECOMPRESSED_EXTENSIONS
+        .iter()
+        .filter(|(encoding, _)| response::accepts_encoding(accept_encoding, encoding))
+        .find_map(|(encoding, extension)| {
+            let path = format!("{}.{}", &request.path, extension);
+            sanitize_path(root, &path)
+                .ok()
+                .filter(|file| file.is_file())
+                .map(|file| (file, *encoding))
+        })
+}
+
+/// 成功的文件响应按规则加上 `Cache-Control`，错误响应不缓存
+fn with_cache_control(response: Response, rules: &[CacheRule], path: &str) -> Response {
+    if !matches!(response.status, 200 | 206 | 304) {
+        return response;
+    }
+
+    match cache::cache_control(rules, path) {
+        Some(value) => response.header("Cache-Control", value),
+        None => response,
     }
 }
 
-fn handle_upload_request(
-    buffer: &[u8],
-) -> Result<(&'static str, &'static str, String), Box<dyn Error>> {
-    let content_type = extract_content_type(&buffer)?;
+/// 单页应用的前端路由：浏览器请求的页面，路径没有扩展名且不在 `/api/` 下
+fn is_spa_route(request: &Request) -> bool {
+    let path = &request.path;
+    let accepts_html = request
+        .header("accept")
+        .is_some_and(|accept| accept.contains("text/html"));
+    let has_extension = path.rsplit('/').next().unwrap_or("").contains('.');
 
-    let content_type = content_type.as_str();
+    accepts_html && !has_extension && !path.starts_with("/api/")
+}
 
-    match content_type {
-        "application/json" => {
-            let body_string: String = String::from_utf8_lossy(&buffer).to_string();
-            let body: Vec<&str> = body_string.trim_matches('\0').split("\r\n\r\n").collect();
-            let data = if let Some(body) = body.get(1) {
-                *body
-            } else {
-                return Err("No body found in the request".into());
-            };
+/// 目录路径缺少结尾的 `/` 时重定向，保证页面中的相对链接能正确解析
+fn redirect_to_directory(request: &Request) -> Response {
+    let path: Vec<String> = request
+        .path
+        .split('/')
+        .map(url::encode_path_segment)
+        .collect();
+    let location = match &request.query {
+        Some(query) => format!("{}/?{}", path.join("/"), query),
+        None => format!("{}/", path.join("/")),
+    };
 
-            Ok(("HTTP/1.1 200 OK", "application/json", data.to_string()))
+    Response::new(301).header("Location", &location)
+}
+
+/// 目录优先返回其中的 index.html，没有时按配置生成目录列表或返回 403
+fn handle_directory_request(
+    request: &Request,
+    dir: &Path,
+    config: &ServerConfig,
+) -> Result<Response, ServerError> {
+    let index = dir.join("index.html");
+    if index.is_file() {
+        let response = read_file_as(&index, "text/html", None, request, config)?;
+        let path = format!("{}index.html", &request.path);
+        return Ok(with_cache_control(response, &config.cache_rules, &path));
+    }
+
+    if !config.listing {
+        return Ok(error_page(config, 403));
+    }
+
+    let listing = listing::render_listing(dir, &config.root, &request.path)?;
+    Ok(Response::ok().content_type("text/html").body_text(listing))
+}
+
+/// 以指定状态码返回错误页面：优先使用 `--error-page` 指定的页面，其次是根目录下的
+/// `{status}.html` 和嵌入的默认页面，都没有时返回纯文本，错误页面本身不会再导致错误
+pub(crate) fn error_page(config: &ServerConfig, status: u16) -> Response {
+    let response = Response::new(status);
+    if let Some(page) = config.error_pages.get(&status) {
+        return response
+            .content_type(&page.content_type)
+            .body_bytes(page.body.clone());
+    }
+
+    match assets::error_page(&config.root, status) {
+        Some(contents) => response
+            .content_type("text/html")
+            .body_bytes(contents.into_owned()),
+        None => {
+            let body = format!("{} {}", status, response.reason);
+            response.content_type("text/plain").body_text(body)
         }
-        "application/x-www-form-urlencoded" => {
-            let body_string: String = String::from_utf8_lossy(&buffer).to_string();
-            let body_parts: Vec<&str> = body_string.trim_matches('\0').split("\r\n\r\n").collect();
+    }
+}
 
-            let data = if let Some(body) = body_parts.get(1) {
-                *body
-            } else {
-                return Err("No body found in the request".into());
-            };
+fn not_found(config: &ServerConfig) -> Response {
+    error_page(config, 404)
+}
 
-            let re = Regex::new(r"id=\d+&name=[a-zA-Z0-9]+")?;
+/// 原样返回请求体和 `Content-Type`，用于调试客户端
+fn handle_echo_request(request: &Request) -> Response {
+    let content_type = request
+        .header("content-type")
+        .unwrap_or("application/octet-stream");
 
-            if let Some(captures) = re.captures(data) {
-                let id = captures.get(1).map_or("", |m| m.as_str());
-                let name = captures.get(2).map_or("", |m| m.as_str());
+    Response::ok()
+        .content_type(content_type)
+        .header("X-Echo-Length", &request.body.len().to_string())
+        .body_bytes(request.body.as_slice())
+}
 
-                let response = json!({
-                    "id": id,
-                    "name": name
-                });
+/// 请求体是包含数字 `id` 和非空 `name` 的表单时原样返回，否则返回 403
+fn handle_echo_form_request(body: &[u8]) -> Result<Response, ServerError> {
+    match form_fields(body)? {
+        Some(_) => Ok(Response::ok()
+            .content_type("application/x-www-form-urlencoded")
+            .body_bytes(body)),
+        None => Ok(Response::new(403)
+            .reason("Data format error")
+            .content_type("text/plain")
+            .body_bytes(assets::data_file("data/error.txt").into_owned())),
+    }
+}
 
-                Ok(("HTTP/1.1 200 OK", "application/json", response.to_string()))
-            } else {
-                let response = fs::read_to_string("data/error.json")?;
-                Ok((
-                    "HTTP/1.1 403 Data format error",
-                    "application/json",
-                    response,
-                ))
+fn handle_upload_request(
+    request: &Request,
+    upload_dir: &Path,
+    items: &ItemStore,
+) -> Result<Response, ServerError> {
+    let content_type = extract_content_type(request)?;
+    let body = request.body.as_slice();
+
+    let content_type = content_type.as_str();
+
+    match content_type {
+        "application/json" => Ok(Response::ok()
+            .content_type("application/json")
+            .body_bytes(body)),
+        // 合法的表单记录保存到数据文件中，之后可以通过 /api/search 查到
+        "application/x-www-form-urlencoded" => {
+            match form_fields(body)?.and_then(|(id, name)| Some((id.parse::<u64>().ok()?, name))) {
+                Some((id, name)) => {
+                    let record = json!({ "id": id, "name": name });
+                    if !items.insert(record.clone())? {
+                        return Ok(json_error(409, "duplicate id"));
+                    }
+                    Ok(Response::new(201)
+                        .content_type("application/json")
+                        .header("Location", &format!("/api/search?id={}", id))
+                        .body_text(record.to_string()))
+                }
+                None => Ok(Response::new(403)
+                    .content_type("application/json")
+                    .body_bytes(assets::data_file("data/error.json").into_owned())),
             }
         }
 
-        _ => Ok((
-            "HTTP/1.1 404 NOT FOUND",
-            "text/html",
-            fs::read_to_string("static/404.html")?,
-        )),
+        "multipart/form-data" => {
+            let header = request.header("content-type").unwrap_or("");
+            let boundary = multipart::boundary(header).ok_or_else(|| {
+                ServerError::Parse(String::from("No boundary in multipart Content-Type"))
+            })?;
+
+            let mut received = Vec::new();
+            for part in multipart::parse(body, &boundary)? {
+                let mut entry = json!({
+                    "name": part.name,
+                    "size": part.data.len(),
+                });
+                if let Some(filename) = &part.filename {
+                    save_upload(upload_dir, &part.data)?;
+                    entry["filename"] = json!(filename);
+                }
+                received.push(entry);
+            }
+
+            Ok(Response::ok()
+                .content_type("application/json")
+                .body_text(Value::from(received).to_string()))
+        }
+
+        _ => Err(ServerError::UnsupportedMediaType(content_type.to_string())),
     }
 }
 
-fn handle_search_request(
-    path: &str,
-) -> Result<(&'static str, &'static str, String), Box<dyn Error>> {
-    let path_parts: Vec<&str> = path.split('?').collect();
+/// 把上传的文件写入上传目录下新建的临时文件，不使用客户端提供的文件名
+fn save_upload(upload_dir: &Path, data: &[u8]) -> io::Result<PathBuf> {
+    static NEXT_UPLOAD: AtomicUsize = AtomicUsize::new(0);
+
+    fs::create_dir_all(upload_dir)?;
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_nanos())
+        .unwrap_or(0);
+    let path = upload_dir.join(format!(
+        "upload-{:x}-{}",
+        nanos,
+        NEXT_UPLOAD.fetch_add(1, Ordering::SeqCst)
+    ));
+
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&path)?;
+    file.write_all(data)?;
+    Ok(path)
+}
 
-    let query_params = if path_parts.len() == 2 {
-        parse_query_params(path_parts[1])?
-    } else {
-        HashMap::new()
-    };
+#[instrument(level = "debug", skip(items))]
+fn handle_search_request(query: &str, items: &ItemStore) -> Result<Response, ServerError> {
+    let query_params = parse_query_params(query).map_err(ServerError::Parse)?;
 
-    let id = query_params.get("id").map_or("", |id| id.trim());
-    let name = query_params.get("name").map_or("", |name| name.trim());
+    let page = match Page::from_params(&query_params) {
+        Ok(page) => page,
+        Err(message) => return Ok(json_error(400, message)),
+    };
 
-    let data = fs::read_to_string("data/data.json")?;
-    let json_data: serde_json::Value = serde_json::from_str(&data)?;
+    // 每个参数单独过滤，同时给出时都要满足：id 和 name 精确匹配，name_contains 为子串匹配，
+    // name_prefix 为前缀匹配；名称默认不区分大小写，没有参数时返回全部
+    let id = match query_params.get("id").map(|id| id.trim().parse::<u64>()) {
+        Some(Ok(id)) => Some(id),
+        Some(Err(_)) => return Ok(json_error(400, "invalid id")),
+        None => None,
+    };
+    let case_sensitive = match query_params.get("case_sensitive").map(String::as_str) {
+        None | Some("false") => false,
+        Some("true") => true,
+        Some(_) => return Ok(json_error(400, "invalid case_sensitive")),
+    };
+    let fold = |name: &str| {
+        if case_sensitive {
+            name.to_string()
+        } else {
+            name.to_lowercase()
+        }
+    };
+    let name_filter = |key: &str| query_params.get(key).map(|name| fold(name.trim()));
+    let exact = name_filter("name");
+    let contains = name_filter("name_contains");
+    let prefix = name_filter("name_prefix");
 
-    let matching_objects: Vec<_> = json_data
-        .as_array()
-        .ok_or("JSON data is not an array")?
+    let items = items.read();
+    let matching_objects: Vec<_> = items
         .iter()
         .filter(|obj| {
-            obj.get("id")
-                .and_then(|id_val| id_val.as_u64())
-                .map_or(false, |id_val| id_val == id.parse::<u64>().unwrap())
-                && obj
-                    .get("name")
-                    .and_then(|name_val| name_val.as_str())
-                    .map_or(false, |name_val| name_val == name)
+            id.is_none_or(|id| {
+                obj.get("id")
+                    .and_then(|id_val| id_val.as_u64())
+                    .is_some_and(|id_val| id_val == id)
+            })
+        })
+        .filter(|obj| {
+            if exact.is_none() && contains.is_none() && prefix.is_none() {
+                return true;
+            }
+            let Some(name_val) = obj.get("name").and_then(|name_val| name_val.as_str()) else {
+                return false;
+            };
+            let name_val = fold(name_val);
+            exact.as_ref().is_none_or(|name| name_val == *name)
+                && contains
+                    .as_ref()
+                    .is_none_or(|name| name_val.contains(name.as_str()))
+                && prefix
+                    .as_ref()
+                    .is_none_or(|prefix| name_val.starts_with(prefix.as_str()))
         })
         .collect();
 
     if !matching_objects.is_empty() {
-        let response = serde_json::to_string(&matching_objects)?;
-        Ok(("HTTP/1.1 200 OK", "application/json", response))
+        page.respond(matching_objects)
     } else {
-        let response = fs::read_to_string("data/not_found.json")?;
-        Ok(("HTTP/1.1 404 NOT FOUND", "application/json", response))
+        Ok(Response::new(404)
+            .content_type("application/json")
+            .body_bytes(assets::data_file("data/not_found.json").into_owned()))
     }
 }
 
-fn extract_content_type(buffer: &[u8]) -> Result<String, Box<dyn Error>> {
-    let request = String::from_utf8_lossy(buffer);
-    let headers: Vec<&str> = request.trim_matches('\0').split("\r\n").collect();
+/// 分页返回全部对象
+fn handle_list_request(query: &str, items: &ItemStore) -> Result<Response, ServerError> {
+    let query_params = parse_query_params(query).map_err(ServerError::Parse)?;
+    let page = match Page::from_params(&query_params) {
+        Ok(page) => page,
+        Err(message) => return Ok(json_error(400, message)),
+    };
+
+    let items = items.read();
+    page.respond(items.iter().collect())
+}
 
-    let content_type_line = headers.get(4).ok_or("No Content-Type header found")?;
-    let content_type_parts: Vec<&str> = content_type_line.split(":").collect();
+/// 查询参数中的分页和排序：`limit`（默认 100，超过 1000 按 1000 处理）、`offset`、
+/// `sort=id|name`（默认 id）和 `order=asc|desc`（默认 asc）
+struct Page {
+    limit: usize,
+    offset: usize,
+    sort_by_name: bool,
+    descending: bool,
+}
 
-    if content_type_parts.len() != 2 || content_type_parts[0].trim() != "Content-Type" {
-        return Err("Invalid Content-Type header".into());
+impl Page {
+    fn from_params(params: &HashMap<String, String>) -> Result<Page, &'static str> {
+        let number = |key: &str, default: usize, message| match params.get(key) {
+            Some(value) => value.trim().parse::<usize>().map_err(|_| message),
+            None => Ok(default),
+        };
+        let limit = number("limit", DEFAULT_PAGE_LIMIT, "invalid limit")?;
+        let offset = number("offset", 0, "invalid offset")?;
+        let sort_by_name = match params.get("sort").map(String::as_str) {
+            None | Some("id") => false,
+            Some("name") => true,
+            Some(_) => return Err("invalid sort"),
+        };
+        let descending = match params.get("order").map(String::as_str) {
+            None | Some("asc") => false,
+            Some("desc") => true,
+            Some(_) => return Err("invalid order"),
+        };
+
+        Ok(Page {
+            limit: limit.min(MAX_PAGE_LIMIT),
+            offset,
+            sort_by_name,
+            descending,
+        })
     }
 
-    Ok(content_type_parts[1].trim().to_string())
+    /// 排序后返回当前页的 JSON 数组，`X-Total-Count` 为分页前的总数
+    fn respond(&self, mut items: Vec<&Value>) -> Result<Response, ServerError> {
+        let id = |item: &Value| item.get("id").and_then(Value::as_u64);
+        if self.sort_by_name {
+            let name = |item: &Value| item.get("name").and_then(Value::as_str).map(String::from);
+            items.sort_by_key(|item| (name(item), id(item)));
+        } else {
+            items.sort_by_key(|item| id(item));
+        }
+        if self.descending {
+            items.reverse();
+        }
+
+        let total = items.len();
+        let page: Vec<_> = items
+            .into_iter()
+            .skip(self.offset)
+            .take(self.limit)
+            .collect();
+        Ok(Response::ok()
+            .content_type("application/json")
+            .header("X-Total-Count", &total.to_string())
+            .body_text(serde_json::to_string(&page)?))
+    }
 }
 
-fn parse_query_params(query: &str) -> Result<HashMap<String, String>, String> {
-    let mut params = HashMap::new();
+/// 用请求体中的 JSON 对象替换 `:id` 对应的对象，返回保存后的对象
+fn handle_replace_item(
+    request: &Request,
+    params: &PathParams,
+    items: &ItemStore,
+) -> Result<Response, ServerError> {
+    let Some(id) = item_id(params) else {
+        return Ok(json_error(404, "item not found"));
+    };
+    match serde_json::from_slice::<Value>(&request.body) {
+        Ok(item) if item.is_object() => replace_item(id, item, items),
+        _ => Ok(json_error(400, "request body is not a JSON object")),
+    }
+}
 
-    for param in query.split('&') {
-        let parts: Vec<&str> = param.splitn(2, '=').collect();
-        if parts.len() != 2 {
-            return Err("Invalid query parameter format".to_string());
-        }
-        params.insert(parts[0].to_string(), parts[1].to_string());
+/// 按请求体中的 JSON Merge Patch 修改 `:id` 对应的对象，返回修改后的对象
+fn handle_patch_item(
+    request: &Request,
+    params: &PathParams,
+    items: &ItemStore,
+) -> Result<Response, ServerError> {
+    let content_type = extract_content_type(request).unwrap_or_default();
+    if content_type != "application/merge-patch+json" {
+        return Ok(json_error(
+            415,
+            "Content-Type must be application/merge-patch+json",
+        ));
     }
 
-    Ok(params)
+    let Some(id) = item_id(params) else {
+        return Ok(json_error(404, "item not found"));
+    };
+    let patch = match serde_json::from_slice::<Value>(&request.body) {
+        Ok(patch) if patch.is_object() => patch,
+        _ => return Ok(json_error(400, "request body is not a JSON object")),
+    };
+
+    match items.patch(id, &patch)? {
+        Some(item) => Ok(Response::ok()
+            .content_type("application/json")
+            .body_text(item.to_string())),
+        None => Ok(json_error(404, "item not found")),
+    }
 }
 
-fn _proxy_request(
-    mut _client_stream: TcpStream,
-    _proxy_address: String,
-) -> Result<(), Box<dyn Error>> {
-    Ok(())
+fn handle_delete_item(params: &PathParams, items: &ItemStore) -> Result<Response, ServerError> {
+    match item_id(params) {
+        Some(id) => delete_item(id, items),
+        None => Ok(json_error(404, "item not found")),
+    }
 }
 
-pub fn extract_proxy_address(proxy: &str) -> Result<String, Box<dyn Error>> {
-    let re = Regex::new(r"http://([^:/]+):?(\d+)?").unwrap();
+/// `PUT /api/items/:id` 的另一种写法：请求体是 `{"id": N, "name": "..."}`，`id` 取自请求体
+fn handle_replace_record(request: &Request, items: &ItemStore) -> Result<Response, ServerError> {
+    let Ok(record) = serde_json::from_slice::<Value>(&request.body) else {
+        return Ok(json_error(400, "request body is not a JSON object"));
+    };
+    let id = record.get("id").and_then(Value::as_u64);
+    let name = record.get("name").and_then(Value::as_str);
+    let (Some(id), Some(name)) = (id, name.filter(|name| !name.is_empty())) else {
+        return Ok(json_error(
+            400,
+            "record needs a numeric id and a non-empty name",
+        ));
+    };
 
-    if let Some(captures) = re.captures(proxy) {
-        let address = captures.get(1).unwrap().as_str();
-        let port_str = captures.get(2).map_or("80", |port| port.as_str());
+    replace_item(id, json!({ "id": id, "name": name }), items)
+}
 
-        let port: u16 = match port_str.parse() {
-            Ok(port) => port,
-            Err(_) => return Err("Failed to parse port".into()),
-        };
+/// `DELETE /api/items/:id` 的另一种写法，`id` 取自查询参数
+fn handle_delete_record(request: &Request, items: &ItemStore) -> Result<Response, ServerError> {
+    let id = parse_query_params(request.query())
+        .ok()
+        .and_then(|params| params.get("id")?.trim().parse::<u64>().ok());
+    match id {
+        Some(id) => delete_item(id, items),
+        None => Ok(json_error(400, "invalid id")),
+    }
+}
 
-        let addr = match (address, port).to_socket_addrs() {
-            Ok(mut addr_iter) => match addr_iter.next() {
-                Some(addr) => addr,
-                None => return Err("Failed to resolve address".into()),
-            },
-            Err(e) => return Err(format!("Socket address resolution error: {}", e).into()),
-        };
+/// 替换 `id` 对应的对象，`item` 中的 `id` 统一改为该 `id`
+fn replace_item(id: u64, mut item: Value, items: &ItemStore) -> Result<Response, ServerError> {
+    item["id"] = Value::from(id);
+    match items.replace(id, item.clone())? {
+        true => Ok(Response::ok()
+            .content_type("application/json")
+            .body_text(item.to_string())),
+        false => Ok(json_error(404, "item not found")),
+    }
+}
 
-        Ok(addr.to_string())
-    } else {
-        Err("Invalid proxy address format".into())
+fn delete_item(id: u64, items: &ItemStore) -> Result<Response, ServerError> {
+    match items.remove(id)? {
+        true => Ok(Response::new(204)),
+        false => Ok(json_error(404, "item not found")),
+    }
+}
+
+/// JSON 接口统一的错误响应 `{"error": "..."}`
+fn json_error(status: u16, message: &str) -> Response {
+    Response::new(status)
+        .content_type("application/json")
+        .body_text(json!({ "error": message }).to_string())
+}
+
+/// 不是非负整数的 `:id` 不可能对应任何对象，调用方按不存在处理
+fn item_id(params: &PathParams) -> Option<u64> {
+    params.get("id")?.parse().ok()
+}
+
+/// 返回去掉 `charset`、`boundary` 等参数后的媒体类型
+fn extract_content_type(request: &Request) -> Result<String, ServerError> {
+    let content_type = request
+        .header("content-type")
+        .ok_or_else(|| ServerError::Parse(String::from("No Content-Type header found")))?;
+
+    let media_type = content_type.split(';').next().unwrap_or("").trim();
+
+    Ok(media_type.to_ascii_lowercase())
+}
+
+/// 解析查询字符串或 `application/x-www-form-urlencoded` 请求体，忽略空的参数
+///
+/// 键和值都按 [`url::percent_decode`] 解码，没有 `=` 的参数取值为空字符串，重复的键保留最后一个值
+fn parse_query_params(query: &str) -> Result<HashMap<String, String>, String> {
+    let mut params = HashMap::new();
+
+    for param in query.split('&').filter(|param| !param.is_empty()) {
+        let (key, value) = param.split_once('=').unwrap_or((param, ""));
+        let key = url::percent_decode(key).map_err(|err| err.to_string())?;
+        let value = url::percent_decode(value).map_err(|err| err.to_string())?;
+        params.insert(key, value);
     }
+
+    Ok(params)
+}
+
+/// 从表单请求体中取出 `id` 和 `name`：字段顺序不限，允许多余的字段，`id` 必须是数字，
+/// `name` 不能为空，不满足时返回 `None`；转义序列无效时返回错误
+fn form_fields(body: &[u8]) -> Result<Option<(String, String)>, ServerError> {
+    let mut form =
+        parse_query_params(String::from_utf8_lossy(body).trim()).map_err(ServerError::Parse)?;
+
+    let id = form
+        .remove("id")
+        .filter(|id| !id.is_empty() && id.bytes().all(|byte| byte.is_ascii_digit()));
+    let name = form.remove("name").filter(|name| !name.is_empty());
+    Ok(id.zip(name))
 }
@@ -0,0 +1,112 @@
+use crate::{datetime, url};
+use std::{fs, io, path::Path, time::SystemTime};
+
+struct Entry {
+    name: String,
+    is_dir: bool,
+    size: u64,
+    modified: Option<SystemTime>,
+}
+
+/// 生成目录的 HTML 列表，`request_path` 是浏览器中的目录路径，用于拼接链接
+///
+/// 以 `.` 开头的隐藏文件和指向 `root` 之外的符号链接不会列出，目录排在文件之前
+pub fn render_listing(dir: &Path, root: &Path, request_path: &str) -> io::Result<String> {
+    let root = fs::canonicalize(root)?;
+    let mut entries = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name.starts_with('.') {
+            continue;
+        }
+
+        let mut metadata = entry.metadata()?;
+        if metadata.file_type().is_symlink() {
+            match fs::canonicalize(entry.path()) {
+                Ok(target) if target.starts_with(&root) => metadata = fs::metadata(target)?,
+                _ => continue,
+            }
+        }
+
+        entries.push(Entry {
+            name,
+            is_dir: metadata.is_dir(),
+            size: metadata.len(),
+            modified: metadata.modified().ok(),
+        });
+    }
+    entries.sort_by(|a, b| b.is_dir.cmp(&a.is_dir).then_with(|| a.name.cmp(&b.name)));
+
+    let base = if request_path.ends_with('/') {
+        request_path.to_string()
+    } else {
+        format!("{}/", request_path)
+    };
+    let title = html_escape(&format!("Index of {}", base));
+    let href_base: Vec<String> = base.split('/').map(url::encode_path_segment).collect();
+    let href_base = href_base.join("/");
+
+    let mut rows = String::new();
+    if base != "/" {
+        rows.push_str("        <tr><td><a href=\"../\">../</a></td><td>-</td><td>-</td></tr>\n");
+    }
+    for entry in &entries {
+        let suffix = if entry.is_dir { "/" } else { "" };
+        let size = if entry.is_dir {
+            String::from("-")
+        } else {
+            entry.size.to_string()
+        };
+        let modified = entry
+            .modified
+            .map(datetime::format_http_date)
+            .unwrap_or_else(|| String::from("-"));
+
+        rows.push_str(&format!(
+            "        <tr><td><a href=\"{}{}{}\">{}{}</a></td><td>{}</td><td>{}</td></tr>\n",
+            href_base,
+            url::encode_path_segment(&entry.name),
+            suffix,
+            html_escape(&entry.name),
+            suffix,
+            size,
+            modified,
+        ));
+    }
+
+    Ok(format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta charset="UTF-8">
+    <link rel="stylesheet" href="/styles.css"/>
+    <title>{title}</title>
+</head>
+<body>
+    <h1>
+        {title}
+    </h1>
+    <table>
+        <tr><th>Name</th><th>Size</th><th>Modified</th></tr>
+{rows}    </table>
+</body>
+</html>"#
+    ))
+}
+
+/// 转义 HTML 中有特殊含义的字符
+fn html_escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
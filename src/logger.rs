@@ -0,0 +1,272 @@
+use crate::datetime;
+use serde_json::json;
+use std::{
+    fmt,
+    fs::OpenOptions,
+    io::{self, Write},
+    net::IpAddr,
+    path::Path,
+    time::{Duration, SystemTime},
+};
+
+/// 访问日志的格式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Apache Combined Log Format，末尾加上处理耗时（毫秒）
+    #[default]
+    Combined,
+    /// 每行一个 JSON 对象
+    Json,
+}
+
+/// 运行日志（写到标准错误）的最低级别
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl LogLevel {
+    /// 对应的 `RUST_LOG` 过滤规则
+    pub fn as_directive(self) -> &'static str {
+        match self {
+            LogLevel::Error => "error",
+            LogLevel::Warn => "warn",
+            LogLevel::Info => "info",
+            LogLevel::Debug => "debug",
+            LogLevel::Trace => "trace",
+        }
+    }
+}
+
+/// 一条访问日志
+#[derive(Debug, Clone)]
+pub struct LogRecord {
+    pub ip: Option<IpAddr>,
+    pub time: SystemTime,
+    pub method: String,
+    pub path: String,
+    pub protocol: String,
+    pub status: u16,
+    /// 响应体的字节数
+    pub bytes: u64,
+    pub referer: Option<String>,
+    pub user_agent: Option<String>,
+    /// 从读完请求头到写完响应的时间
+    pub duration: Duration,
+}
+
+impl LogRecord {
+    /// JSON 格式的一行，缺少的字段为 `null`
+    pub fn to_json(&self) -> String {
+        json!({
+            "ip": self.ip.map(|ip| ip.to_string()),
+            "time": datetime::format_rfc3339(self.time),
+            "method": self.method,
+            "path": self.path,
+            "protocol": self.protocol,
+            "status": self.status,
+            "bytes": self.bytes,
+            "referer": self.referer,
+            "user_agent": self.user_agent,
+            "duration_ms": duration_ms(self.duration),
+        })
+        .to_string()
+    }
+}
+
+/// 按 Apache Combined Log Format 输出，缺少的字段写成 `-`，末尾是处理耗时（毫秒）
+impl fmt::Display for LogRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ip = self
+            .ip
+            .map(|ip| ip.to_string())
+            .unwrap_or_else(|| String::from("-"));
+        let bytes = match self.bytes {
+            0 => String::from("-"),
+            bytes => bytes.to_string(),
+        };
+
+        write!(
+            f,
+            "{} - - [{}] \"{} {} {}\" {} {} \"{}\" \"{}\" {}",
+            ip,
+            datetime::format_log_date(self.time),
+            escape(&self.method),
+            escape(&self.path),
+            escape(&self.protocol),
+            self.status,
+            bytes,
+            escape(self.referer.as_deref().unwrap_or("-")),
+            escape(self.user_agent.as_deref().unwrap_or("-")),
+            duration_ms(self.duration),
+        )
+    }
+}
+
+/// 访问日志的输出目标，每条记录写成一行
+pub struct Logger {
+    output: Box<dyn Write + Send>,
+    format: LogFormat,
+}
+
+impl Logger {
+    pub fn new(output: impl Write + Send + 'static) -> Logger {
+        Logger {
+            output: Box::new(output),
+            format: LogFormat::default(),
+        }
+    }
+
+    pub fn format(mut self, format: LogFormat) -> Logger {
+        self.format = format;
+        self
+    }
+
+    /// 写到标准输出
+    pub fn stdout() -> Logger {
+        Logger::new(io::stdout())
+    }
+
+    /// 追加写入文件，文件不存在时创建
+    pub fn to_file(path: &Path) -> io::Result<Logger> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Logger::new(file))
+    }
+
+    /// 格式化并写出一条记录，整行一次写出
+    pub fn log(&mut self, record: &LogRecord) -> io::Result<()> {
+        let line = match self.format {
+            LogFormat::Combined => format!("{}\n", record),
+            LogFormat::Json => format!("{}\n", record.to_json()),
+        };
+        self.output.write_all(line.as_bytes())?;
+        self.output.flush()
+    }
+}
+
+/// 精确到微秒的毫秒数
+fn duration_ms(duration: Duration) -> f64 {
+    duration.as_micros() as f64 / 1000.0
+}
+
+/// 转义请求中的引号、反斜杠和控制字符，客户端无法伪造日志行
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_control() => escaped.push_str(&format!("\\x{:02x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        sync::{Arc, Mutex},
+        time::UNIX_EPOCH,
+    };
+
+    /// 测试结束后还能读出内容的输出目标
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Shared {
+        fn text(&self) -> String {
+            String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+        }
+    }
+
+    fn record() -> LogRecord {
+        LogRecord {
+            ip: Some(IpAddr::from([192, 0, 2, 7])),
+            time: UNIX_EPOCH + Duration::from_millis(1_700_000_000_250),
+            method: String::from("GET"),
+            path: String::from("/search?q=\"a\\b\""),
+            protocol: String::from("HTTP/1.1"),
+            status: 200,
+            bytes: 512,
+            referer: Some(String::from("https://example.com/")),
+            user_agent: Some(String::from("curl/8.0\n\u{7}")),
+            duration: Duration::from_micros(12_345),
+        }
+    }
+
+    #[test]
+    fn combined_lines_escape_request_fields() {
+        let output = Shared::default();
+        let mut logger = Logger::new(output.clone());
+        logger.log(&record()).unwrap();
+
+        assert_eq!(
+            output.text(),
+            "192.0.2.7 - - [14/Nov/2023:22:13:20 +0000] \"GET /search?q=\\\"a\\\\b\\\" HTTP/1.1\" \
+             200 512 \"https://example.com/\" \"curl/8.0\\x0a\\x07\" 12.345\n"
+        );
+    }
+
+    #[test]
+    fn missing_fields_are_dashes_in_combined_and_null_in_json() {
+        let record = LogRecord {
+            ip: None,
+            bytes: 0,
+            referer: None,
+            user_agent: None,
+            ..record()
+        };
+
+        let output = Shared::default();
+        let mut logger = Logger::new(output.clone());
+        logger.log(&record).unwrap();
+        assert_eq!(
+            output.text(),
+            "- - - [14/Nov/2023:22:13:20 +0000] \"GET /search?q=\\\"a\\\\b\\\" HTTP/1.1\" \
+             200 - \"-\" \"-\" 12.345\n"
+        );
+
+        let output = Shared::default();
+        let mut logger = Logger::new(output.clone()).format(LogFormat::Json);
+        logger.log(&record).unwrap();
+        assert_eq!(
+            output.text(),
+            "{\"bytes\":0,\"duration_ms\":12.345,\"ip\":null,\"method\":\"GET\",\
+             \"path\":\"/search?q=\\\"a\\\\b\\\"\",\"protocol\":\"HTTP/1.1\",\"referer\":null,\
+             \"status\":200,\"time\":\"2023-11-14T22:13:20.250Z\",\"user_agent\":null}\n"
+        );
+    }
+
+    #[test]
+    fn json_lines_escape_quotes_and_control_characters() {
+        let output = Shared::default();
+        let mut logger = Logger::new(output.clone()).format(LogFormat::Json);
+        logger.log(&record()).unwrap();
+        logger.log(&record()).unwrap();
+
+        let line = "{\"bytes\":512,\"duration_ms\":12.345,\"ip\":\"192.0.2.7\",\"method\":\"GET\",\
+                    \"path\":\"/search?q=\\\"a\\\\b\\\"\",\"protocol\":\"HTTP/1.1\",\
+                    \"referer\":\"https://example.com/\",\"status\":200,\
+                    \"time\":\"2023-11-14T22:13:20.250Z\",\"user_agent\":\"curl/8.0\\n\\u0007\"}\n";
+        assert_eq!(output.text(), line.repeat(2));
+        assert_eq!(record().to_json(), line.trim_end());
+    }
+}
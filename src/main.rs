@@ -1,47 +1,272 @@
+use clap::{CommandFactory, FromArgMatches};
 use http_server::*;
-use std::{net::TcpListener, process::exit};
+use std::{
+    fs,
+    io::{self, Write},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener, TcpStream},
+    process::exit,
+    sync::{mpsc, Arc, Mutex},
+    thread,
+    time::Duration,
+};
+use tracing::{debug, error, info, warn};
+use tracing_subscriber::EnvFilter;
 
 fn main() {
-    let args = Args::parse();
+    let matches = Args::command().get_matches();
+    let mut args = Args::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
+    let file = match args.config.clone() {
+        Some(path) => FileConfig::from_toml_file(&path)
+            .and_then(|file| file.merge_into(&mut args, &matches).map(|()| file))
+            .unwrap_or_else(|err| {
+                exit_with_error(&format!("Invalid config file {}: {}", path.display(), err))
+            }),
+        None => FileConfig::default(),
+    };
+    let resolved = config::resolve(args, file)
+        .unwrap_or_else(|err| exit_with_error(&format!("Invalid configuration: {}", err)));
+    let args = &resolved.args;
 
-    let listener = TcpListener::bind(format!("{}:{}", args.ip, args.port))
+    // 日志级别由 --log-level 或 RUST_LOG 控制，默认 info；写到标准错误，标准输出留给访问日志
+    let filter = match args.log_level {
+        Some(level) => EnvFilter::new(level.as_directive()),
+        None => EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")),
+    };
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_thread_names(true)
+        .with_writer(io::stderr)
+        .init();
+
+    let listener = TcpListener::bind(format!("{}:{}", resolved.ip, resolved.port))
         .unwrap_or_else(|err| exit_with_error(&format!("{}", err)));
 
-    let pool = ThreadPool::new(args.threads as usize);
+    let pool = ThreadPool::new(resolved.threads as usize);
+
+    if let Err(err) = fs::read_dir(&args.root) {
+        exit_with_error(&format!(
+            "Cannot read root directory {}: {}",
+            args.root.display(),
+            err
+        ));
+    }
+
+    let config = Arc::new(
+        ServerConfig::from_resolved(&resolved)
+            .unwrap_or_else(|err| exit_with_error(&format!("{}", err))),
+    );
 
-    //let proxy_enabled = !args.proxy.is_empty();
+    let router = Arc::new(build_router(&config));
+    let middleware = Arc::new(build_middleware(&config));
 
-    // let proxy_address = if proxy_enabled {
-    //     match extract_proxy_address(&args.proxy) {
-    //         Ok(proxy_address) => proxy_address,
-    //         Err(err) => exit_with_error(&format!("{}", err)),
-    //     }
-    // } else {
-    //     String::new()
-    // };
+    let tls_config = match (&resolved.tls_cert, &resolved.tls_key) {
+        (Some(cert), Some(key)) => Some(
+            tls::load_tls_config(cert, key)
+                .unwrap_or_else(|err| exit_with_error(&format!("{}", err))),
+        ),
+        _ => None,
+    };
+
+    let connections = Arc::new(Semaphore::new(args.max_connections as usize));
+
+    let logger = Arc::new(Mutex::new(
+        match &args.log_file {
+            Some(path) => Logger::to_file(path).unwrap_or_else(|err| {
+                exit_with_error(&format!("Cannot open log file {}: {}", path.display(), err))
+            }),
+            None => Logger::stdout(),
+        }
+        .format(args.log_format),
+    ));
+
+    // 第一次收到 SIGINT/SIGTERM 时停止接受新连接，连接一次监听地址唤醒阻塞在 accept 中的主线程；
+    // 再次收到时立即退出
+    let wake_address = listener
+        .local_addr()
+        .map(wake_address)
+        .unwrap_or_else(|err| exit_with_error(&format!("{}", err)));
+    let state = Arc::clone(&config.state);
+    ctrlc::set_handler(move || {
+        if state.begin_shutdown() {
+            exit(1);
+        }
+        let _ = TcpStream::connect_timeout(&wake_address, Duration::from_secs(1));
+    })
+    .unwrap_or_else(|err| exit_with_error(&format!("Cannot install signal handler: {}", err)));
+
+    config.state.set_ready();
+    info!(
+        address = %listener.local_addr().map_or(resolved.port.to_string(), |addr| addr.to_string()),
+        threads = resolved.threads,
+        root = %args.root.display(),
+        tls = tls_config.is_some(),
+        "server started"
+    );
 
     for stream in listener.incoming() {
+        if config.state.is_shutting_down() {
+            break;
+        }
         match stream {
             Ok(stream) => {
-                //let proxy_address_clone = proxy_address.clone();
-
-                pool.execute(move || {
-                    handle_connection(stream).unwrap_or_else(|err| {
-                        exit_with_error(&format!("{}", err));
-                    })
-                })
-                .unwrap_or_else(|err| {
-                    exit_with_error(&format!("{}", err));
-                });
+                config.metrics.connection_accepted();
+                if let Err(err) = set_timeouts(&stream, args) {
+                    warn!(error = %err, "cannot set connection timeouts");
+                    continue;
+                }
+
+                // 超过速率限制的客户端在进入线程池之前就被拒绝，不占用队列和工作线程
+                let limited = match (&config.rate_limiter, stream.peer_addr()) {
+                    (Some(limiter), Ok(peer)) => limiter.check(peer.ip()).err(),
+                    _ => None,
+                };
+                if let Some(retry_after) = limited {
+                    if tls_config.is_none() {
+                        if let Err(err) = reject_connection(&stream, 429, retry_after, &config) {
+                            debug!(error = %err, "cannot send rejection");
+                        }
+                        close_connection(&stream, Duration::ZERO);
+                    }
+                    continue;
+                }
+
+                // 许可随任务一起移动，连接处理完或者任务被丢弃时归还
+                let Some(permit) = connections.try_acquire() else {
+                    if tls_config.is_none() {
+                        if let Err(err) =
+                            reject_connection(&stream, 503, Duration::from_secs(1), &config)
+                        {
+                            debug!(error = %err, "cannot send rejection");
+                        }
+                        close_connection(&stream, Duration::ZERO);
+                    }
+                    continue;
+                };
+
+                // 队列已满时任务连同连接一起被丢弃，用复制的句柄回复 503
+                let overflow = stream.try_clone();
+                let job = {
+                    let config = Arc::clone(&config);
+                    let router = Arc::clone(&router);
+                    let middleware = Arc::clone(&middleware);
+                    let tls_config = tls_config.clone();
+                    let logger = Arc::clone(&logger);
+                    move || {
+                        let _permit = permit;
+                        config.metrics.connection_dequeued();
+                        if let Err(err) =
+                            serve(stream, tls_config, &config, &router, &middleware, &logger)
+                        {
+                            warn!(error = %err, "connection failed");
+                        }
+                    }
+                };
+
+                config.metrics.connection_queued();
+                match pool.execute(job) {
+                    Ok(()) => {}
+                    Err(ThreadPoolError::QueueFull) => {
+                        config.metrics.connection_dequeued();
+                        // TLS 连接还没有握手，无法发送 HTTP 响应，只能直接关闭
+                        if let (Ok(stream), None) = (overflow, &tls_config) {
+                            if let Err(err) =
+                                reject_connection(&stream, 503, Duration::from_secs(1), &config)
+                            {
+                                debug!(error = %err, "cannot send rejection");
+                            }
+                            close_connection(&stream, Duration::ZERO);
+                        }
+                    }
+                    Err(err) => exit_with_error(&format!("{}", err)),
+                }
             }
             Err(err) => {
-                eprintln!("{}", err);
+                error!(error = %err, "cannot accept connection");
             }
         }
     }
+
+    // 线程池被丢弃时等待所有工作线程处理完手上的连接；
+    // SSE 和 WebSocket 连接不会自己结束，超过时限后直接退出
+    info!("shutting down, waiting for active requests");
+    drop(listener);
+    config.events.close();
+    let (done, finished) = mpsc::channel();
+    thread::spawn(move || {
+        drop(pool);
+        let _ = done.send(());
+    });
+    match finished.recv_timeout(SHUTDOWN_TIMEOUT) {
+        Ok(()) => info!("server stopped"),
+        Err(_) => warn!(
+            active = config.metrics.active_connections(),
+            "shutdown timed out, closing remaining connections"
+        ),
+    }
+
+    let stats = config.metrics.snapshot();
+    info!(
+        requests = stats.requests,
+        server_errors = stats.server_errors(),
+        connection_errors = stats.connection_errors,
+        connections = stats.connections,
+        bytes_received = stats.bytes_received,
+        bytes_sent = stats.bytes_sent,
+        mean_duration_ms = stats.mean_duration().as_micros() as f64 / 1000.0,
+        "served since startup"
+    );
 }
 
+/// 退出时等待处理中的连接的最长时间
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// 监听在任意地址上时用对应的回环地址连接自己
+fn wake_address(local: SocketAddr) -> SocketAddr {
+    let ip = match local.ip() {
+        IpAddr::V4(ip) if ip.is_unspecified() => IpAddr::V4(Ipv4Addr::LOCALHOST),
+        IpAddr::V6(ip) if ip.is_unspecified() => IpAddr::V6(Ipv6Addr::LOCALHOST),
+        ip => ip,
+    };
+    SocketAddr::new(ip, local.port())
+}
+
+fn serve(
+    mut stream: TcpStream,
+    tls_config: Option<Arc<rustls::ServerConfig>>,
+    config: &ServerConfig,
+    router: &Router,
+    middleware: &MiddlewareStack,
+    logger: &Mutex<Logger>,
+) -> Result<(), ServerError> {
+    match tls_config {
+        Some(tls_config) => {
+            let mut stream = tls::accept(tls_config, stream)?;
+            handle_connection(&mut stream, config, router, middleware, logger);
+            stream.conn.send_close_notify();
+            stream.flush()?;
+            close_connection(&stream.sock, LINGER_TIMEOUT);
+            Ok(())
+        }
+        None => {
+            handle_connection(&mut stream, config, router, middleware, logger);
+            close_connection(&stream, LINGER_TIMEOUT);
+            Ok(())
+        }
+    }
+}
+
+/// 在交给线程池之前设置读写超时，不发送数据的客户端不会一直占用工作线程
+fn set_timeouts(stream: &TcpStream, args: &Args) -> io::Result<()> {
+    stream.set_read_timeout(Some(Duration::from_millis(args.read_timeout)))?;
+    stream.set_write_timeout(Some(Duration::from_millis(args.write_timeout)))
+}
+
+/// 运行日志初始化之前（解析参数和配置文件时）的错误直接写到标准错误
 fn exit_with_error(msg: &str) -> ! {
-    eprintln!("{}", msg);
+    if tracing::dispatcher::has_been_set() {
+        error!("{}", msg);
+    } else {
+        eprintln!("{}", msg);
+    }
     exit(1);
 }
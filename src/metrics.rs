@@ -0,0 +1,368 @@
+use std::{
+    collections::HashMap,
+    fmt::Write,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, RwLock,
+    },
+    time::Duration,
+};
+
+/// 请求耗时直方图的桶上限（秒）
+pub const DURATION_BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// 嵌入 `handle_connection` 的程序读取运行指标用的句柄，即 [`ServerConfig::metrics`]，
+/// 复制的句柄共享同一组计数器
+///
+/// [`ServerConfig::metrics`]: crate::ServerConfig::metrics
+pub type ServerStats = Arc<Metrics>;
+
+/// 服务器运行指标，计数器都是原子变量，多个工作线程通过 `Arc` 共享时不需要加锁
+#[derive(Debug, Default)]
+pub struct Metrics {
+    requests_total: AtomicU64,
+    /// 按状态码类别（1xx 到 5xx）统计的响应数
+    responses_by_class: [AtomicU64; 5],
+    connections_total: AtomicU64,
+    connection_errors_total: AtomicU64,
+    active_connections: AtomicU64,
+    /// 已经交给线程池、还没有工作线程处理的连接数
+    queued_connections: AtomicU64,
+    bytes_received_total: AtomicU64,
+    bytes_sent_total: AtomicU64,
+    /// 按路由模式统计的请求数，路由是固定的，只有第一次遇到时需要写锁
+    route_requests: RwLock<HashMap<String, AtomicU64>>,
+    /// 每个桶统计耗时不超过上限的请求数，即累计值
+    duration_buckets: [AtomicU64; DURATION_BUCKETS.len()],
+    duration_sum_micros: AtomicU64,
+}
+
+impl Metrics {
+    pub fn new() -> Metrics {
+        Metrics::default()
+    }
+
+    /// 接受了一个新连接
+    pub fn connection_accepted(&self) {
+        self.connections_total.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn connection_opened(&self) {
+        self.active_connections.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn connection_closed(&self) {
+        self.active_connections.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// 连接因为读写失败或者请求无法解析而关闭
+    pub fn connection_error(&self) {
+        self.connection_errors_total.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// 读入了一个请求，`bytes` 包括请求头
+    pub fn request_received(&self, bytes: u64) {
+        self.bytes_received_total
+            .fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// 正在处理的连接数
+    pub fn active_connections(&self) -> u64 {
+        self.active_connections.load(Ordering::Relaxed)
+    }
+
+    /// 连接进入线程池的任务队列
+    pub fn connection_queued(&self) {
+        self.queued_connections.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// 连接离开任务队列，开始处理或者因为队列已满被丢弃
+    pub fn connection_dequeued(&self) {
+        self.queued_connections.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// 记录一个由 `route` 处理的请求，`route` 是注册路由时的模式
+    pub fn record_route(&self, route: &str) {
+        if let Some(counter) = self.route_requests.read().unwrap().get(route) {
+            counter.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        self.route_requests
+            .write()
+            .unwrap()
+            .entry(route.to_string())
+            .or_default()
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// 记录一个已经写出响应的请求
+    pub fn record_request(&self, status: u16, bytes: u64, duration: Duration) {
+        self.requests_total.fetch_add(1, Ordering::Relaxed);
+        if let Some(class) = (status as usize / 100).checked_sub(1) {
+            if let Some(counter) = self.responses_by_class.get(class) {
+                counter.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        self.bytes_sent_total.fetch_add(bytes, Ordering::Relaxed);
+
+        let seconds = duration.as_secs_f64();
+        for (bucket, le) in self.duration_buckets.iter().zip(DURATION_BUCKETS) {
+            if seconds <= le {
+                bucket.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        self.duration_sum_micros
+            .fetch_add(duration.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    /// 某一时刻全部指标的副本，各个计数器分别读取，彼此之间不保证一致
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+        let mut routes: Vec<(String, u64)> = self
+            .route_requests
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(route, counter)| (route.clone(), load(counter)))
+            .collect();
+        routes.sort();
+
+        MetricsSnapshot {
+            requests: load(&self.requests_total),
+            responses_by_class: self.responses_by_class.each_ref().map(load),
+            connection_errors: load(&self.connection_errors_total),
+            connections: load(&self.connections_total),
+            active_connections: load(&self.active_connections),
+            queued_connections: load(&self.queued_connections),
+            bytes_received: load(&self.bytes_received_total),
+            bytes_sent: load(&self.bytes_sent_total),
+            routes,
+            duration_buckets: self.duration_buckets.each_ref().map(load),
+            duration_sum: Duration::from_micros(load(&self.duration_sum_micros)),
+        }
+    }
+
+    /// 把累计的计数器清零，正在处理和排队的连接数不受影响
+    pub fn reset(&self) {
+        let counters = [
+            &self.requests_total,
+            &self.connection_errors_total,
+            &self.connections_total,
+            &self.bytes_received_total,
+            &self.bytes_sent_total,
+            &self.duration_sum_micros,
+        ];
+        let classes = self.responses_by_class.iter();
+        for counter in counters
+            .into_iter()
+            .chain(classes)
+            .chain(&self.duration_buckets)
+        {
+            counter.store(0, Ordering::Relaxed);
+        }
+        self.route_requests.write().unwrap().clear();
+    }
+
+    /// 按 Prometheus 文本格式输出全部指标
+    pub fn render(&self) -> String {
+        let snapshot = self.snapshot();
+        let mut out = String::new();
+
+        let simple = [
+            (
+                "http_requests_total",
+                "counter",
+                "Total number of HTTP requests answered.",
+                snapshot.requests,
+            ),
+            (
+                "http_connections_total",
+                "counter",
+                "Total number of accepted connections.",
+                snapshot.connections,
+            ),
+            (
+                "http_connection_errors_total",
+                "counter",
+                "Connections closed because of an I/O or protocol error.",
+                snapshot.connection_errors,
+            ),
+            (
+                "http_active_connections",
+                "gauge",
+                "Connections currently being served.",
+                snapshot.active_connections,
+            ),
+            (
+                "http_queued_connections",
+                "gauge",
+                "Connections waiting in the thread pool queue.",
+                snapshot.queued_connections,
+            ),
+            (
+                "http_request_bytes_total",
+                "counter",
+                "Total number of request bytes received, headers included.",
+                snapshot.bytes_received,
+            ),
+            (
+                "http_response_bytes_total",
+                "counter",
+                "Total number of response body bytes sent.",
+                snapshot.bytes_sent,
+            ),
+        ];
+        for (name, kind, help, value) in simple {
+            metric_header(&mut out, name, kind, help);
+            let _ = writeln!(out, "{} {}", name, value);
+        }
+
+        let name = "http_responses_total";
+        metric_header(&mut out, name, "counter", "HTTP responses by status class.");
+        for (i, count) in snapshot.responses_by_class.iter().enumerate() {
+            let _ = writeln!(out, "{}{{class=\"{}xx\"}} {}", name, i + 1, count);
+        }
+
+        let name = "http_route_requests_total";
+        metric_header(&mut out, name, "counter", "HTTP requests by matched route.");
+        for (route, count) in &snapshot.routes {
+            let _ = writeln!(out, "{}{{route=\"{}\"}} {}", name, route, count);
+        }
+
+        let name = "http_request_duration_seconds";
+        let help = "Time from reading the request head to writing the response.";
+        metric_header(&mut out, name, "histogram", help);
+        for (count, le) in snapshot.duration_buckets.iter().zip(DURATION_BUCKETS) {
+            let _ = writeln!(out, "{}_bucket{{le=\"{}\"}} {}", name, le, count);
+        }
+        let count = snapshot.requests;
+        let sum = snapshot.duration_sum.as_secs_f64();
+        let _ = writeln!(out, "{}_bucket{{le=\"+Inf\"}} {}", name, count);
+        let _ = writeln!(out, "{}_sum {}", name, sum);
+        let _ = writeln!(out, "{}_count {}", name, count);
+
+        out
+    }
+}
+
+/// [`Metrics::snapshot`] 返回的指标副本
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MetricsSnapshot {
+    /// 已经写出响应的请求数
+    pub requests: u64,
+    /// 按状态码类别（1xx 到 5xx）统计的响应数
+    pub responses_by_class: [u64; 5],
+    /// 因为读写失败或者请求无法解析而关闭的连接数，不包括超时和客户端断开
+    pub connection_errors: u64,
+    pub connections: u64,
+    pub active_connections: u64,
+    pub queued_connections: u64,
+    /// 收到的请求字节数，包括请求头
+    pub bytes_received: u64,
+    /// 写出的响应体字节数
+    pub bytes_sent: u64,
+    /// 按路由模式统计的请求数，按模式排序
+    pub routes: Vec<(String, u64)>,
+    /// 与 [`DURATION_BUCKETS`] 一一对应的累计请求数
+    pub duration_buckets: [u64; DURATION_BUCKETS.len()],
+    pub duration_sum: Duration,
+}
+
+impl MetricsSnapshot {
+    /// 5xx 响应数
+    pub fn server_errors(&self) -> u64 {
+        self.responses_by_class[4]
+    }
+
+    /// 平均处理耗时，没有请求时为 0
+    pub fn mean_duration(&self) -> Duration {
+        match self.requests {
+            0 => Duration::ZERO,
+            requests => Duration::from_secs_f64(self.duration_sum.as_secs_f64() / requests as f64),
+        }
+    }
+}
+
+fn metric_header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requests_fill_cumulative_buckets_and_byte_counters() {
+        let stats = ServerStats::default();
+        stats.request_received(120);
+        stats.record_request(200, 1000, Duration::from_millis(3));
+        stats.request_received(80);
+        stats.record_request(404, 20, Duration::from_millis(30));
+        stats.record_request(503, 0, Duration::from_secs(20));
+
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.requests, 3);
+        assert_eq!(snapshot.responses_by_class, [0, 1, 0, 1, 1]);
+        assert_eq!(snapshot.server_errors(), 1);
+        assert_eq!(snapshot.bytes_received, 200);
+        assert_eq!(snapshot.bytes_sent, 1020);
+        // 3ms 落在所有桶中，30ms 从 0.05 开始，20s 超过了最大的桶
+        assert_eq!(snapshot.duration_buckets, [1, 1, 1, 2, 2, 2, 2, 2, 2, 2, 2]);
+        assert_eq!(snapshot.duration_sum, Duration::from_micros(20_033_000));
+
+        let rendered = stats.render();
+        assert!(rendered.contains("http_request_duration_seconds_bucket{le=\"0.05\"} 2\n"));
+        assert!(rendered.contains("http_request_duration_seconds_bucket{le=\"+Inf\"} 3\n"));
+    }
+
+    #[test]
+    fn clones_share_counters() {
+        let stats = ServerStats::default();
+        let handle = Arc::clone(&stats);
+        handle.record_route("/api/list");
+        handle.record_route("/api/list");
+        handle.record_route("static");
+        assert_eq!(
+            stats.snapshot().routes,
+            [(String::from("/api/list"), 2), (String::from("static"), 1)]
+        );
+    }
+
+    #[test]
+    fn reset_keeps_active_and_queued_connections() {
+        let stats = ServerStats::default();
+        stats.connection_accepted();
+        stats.connection_accepted();
+        stats.connection_opened();
+        stats.connection_queued();
+        stats.connection_error();
+        stats.request_received(50);
+        stats.record_route("static");
+        stats.record_request(500, 10, Duration::from_millis(1));
+
+        stats.reset();
+
+        let snapshot = stats.snapshot();
+        assert_eq!(
+            snapshot,
+            MetricsSnapshot {
+                active_connections: 1,
+                queued_connections: 1,
+                ..MetricsSnapshot::default()
+            }
+        );
+
+        // 清零后继续计数，离开的连接照常减少
+        stats.connection_closed();
+        stats.connection_dequeued();
+        stats.record_request(200, 5, Duration::from_millis(1));
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.active_connections, 0);
+        assert_eq!(snapshot.queued_connections, 0);
+        assert_eq!(snapshot.requests, 1);
+        assert_eq!(snapshot.duration_buckets[0], 1);
+    }
+}
@@ -0,0 +1,213 @@
+use crate::{cors, response, CorsConfig, Request, Response};
+use std::fmt;
+
+/// 包在路由处理外层的中间件，可以修改请求和响应，或者不调用 `next` 直接返回响应
+pub trait Middleware {
+    fn call(&self, request: &Request, next: &dyn Fn(&Request) -> Response) -> Response;
+}
+
+/// 按加入顺序依次调用的中间件，先加入的在最外层
+#[derive(Default)]
+pub struct MiddlewareStack {
+    middlewares: Vec<Box<dyn Middleware + Send + Sync>>,
+}
+
+impl fmt::Debug for MiddlewareStack {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MiddlewareStack")
+            .field("len", &self.middlewares.len())
+            .finish()
+    }
+}
+
+impl MiddlewareStack {
+    pub fn new() -> MiddlewareStack {
+        MiddlewareStack::default()
+    }
+
+    pub fn push(&mut self, middleware: impl Middleware + Send + Sync + 'static) -> &mut Self {
+        self.middlewares.push(Box::new(middleware));
+        self
+    }
+
+    fn call_from(
+        &self,
+        index: usize,
+        request: &Request,
+        next: &dyn Fn(&Request) -> Response,
+    ) -> Response {
+        match self.middlewares.get(index) {
+            Some(middleware) => {
+                middleware.call(request, &|request| self.call_from(index + 1, request, next))
+            }
+            None => next(request),
+        }
+    }
+}
+
+impl Middleware for MiddlewareStack {
+    fn call(&self, request: &Request, next: &dyn Fn(&Request) -> Response) -> Response {
+        self.call_from(0, request, next)
+    }
+}
+
+/// 为普通的跨域请求加上 CORS 响应头，见 [`cors::apply`]
+#[derive(Debug, Clone)]
+pub struct Cors(pub CorsConfig);
+
+impl Middleware for Cors {
+    fn call(&self, request: &Request, next: &dyn Fn(&Request) -> Response) -> Response {
+        let mut response = next(request);
+        cors::apply(&mut response, request, &self.0);
+        response
+    }
+}
+
+/// 补全路由处理返回的内容相关响应头：非空的响应体没有 `Content-Type` 时按二进制内容处理，
+/// 文本类型补上字符集；`Content-Length` 和 `Transfer-Encoding` 在写出时按响应体生成，
+/// 处理函数自己设置的会被去掉，避免重复或者互相矛盾
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ContentHeaders;
+
+impl Middleware for ContentHeaders {
+    fn call(&self, request: &Request, next: &dyn Fn(&Request) -> Response) -> Response {
+        let mut response = next(request);
+        response.headers.retain(|(name, _)| {
+            !name.eq_ignore_ascii_case("Content-Length")
+                && !name.eq_ignore_ascii_case("Transfer-Encoding")
+        });
+
+        match response
+            .headers
+            .iter_mut()
+            .find(|(name, _)| name.eq_ignore_ascii_case("Content-Type"))
+        {
+            Some((_, content_type)) => {
+                *content_type = response::with_charset(content_type).into_owned();
+            }
+            None if !response.body.is_empty() => {
+                response = response.content_type("application/octet-stream");
+            }
+            None => {}
+        }
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    fn request() -> Request {
+        Request::from_bytes(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap()
+    }
+
+    /// 记录调用顺序的中间件，`stop` 为真时不调用 `next`
+    struct Record {
+        name: &'static str,
+        calls: Arc<Mutex<Vec<&'static str>>>,
+        stop: bool,
+    }
+
+    impl Middleware for Record {
+        fn call(&self, request: &Request, next: &dyn Fn(&Request) -> Response) -> Response {
+            self.calls.lock().unwrap().push(self.name);
+            if self.stop {
+                return Response::new(403);
+            }
+            let response = next(request);
+            self.calls.lock().unwrap().push(self.name);
+            response
+        }
+    }
+
+    #[test]
+    fn empty_stack_calls_next() {
+        let response = MiddlewareStack::new().call(&request(), &|_| Response::new(204));
+        assert_eq!(response.status, 204);
+    }
+
+    #[test]
+    fn first_pushed_runs_outermost() {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let mut stack = MiddlewareStack::new();
+        for name in ["outer", "inner"] {
+            stack.push(Record {
+                name,
+                calls: Arc::clone(&calls),
+                stop: false,
+            });
+        }
+
+        let response = stack.call(&request(), &|_| {
+            calls.lock().unwrap().push("handler");
+            Response::ok()
+        });
+        assert_eq!(response.status, 200);
+        assert_eq!(
+            *calls.lock().unwrap(),
+            ["outer", "inner", "handler", "inner", "outer"]
+        );
+    }
+
+    #[test]
+    fn middleware_that_skips_next_stops_the_chain() {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let mut stack = MiddlewareStack::new();
+        stack
+            .push(Record {
+                name: "outer",
+                calls: Arc::clone(&calls),
+                stop: false,
+            })
+            .push(Record {
+                name: "guard",
+                calls: Arc::clone(&calls),
+                stop: true,
+            })
+            .push(Record {
+                name: "inner",
+                calls: Arc::clone(&calls),
+                stop: false,
+            });
+
+        let response = stack.call(&request(), &|_| {
+            calls.lock().unwrap().push("handler");
+            Response::ok()
+        });
+        assert_eq!(response.status, 403);
+        assert_eq!(*calls.lock().unwrap(), ["outer", "guard", "outer"]);
+    }
+
+    #[test]
+    fn content_headers_fill_in_the_type_and_drop_framing_headers() {
+        let response = ContentHeaders.call(&request(), &|_| {
+            Response::ok()
+                .header("Content-Length", "1")
+                .header("transfer-encoding", "chunked")
+                .body_bytes(vec![0, 1, 2])
+        });
+        assert_eq!(
+            response.headers,
+            [(
+                String::from("Content-Type"),
+                String::from("application/octet-stream")
+            )]
+        );
+
+        let response = ContentHeaders.call(&request(), &|_| {
+            Response::ok().content_type("text/plain").body_text("hi")
+        });
+        assert_eq!(
+            response.headers,
+            [(
+                String::from("Content-Type"),
+                String::from("text/plain; charset=utf-8")
+            )]
+        );
+
+        let response = ContentHeaders.call(&request(), &|_| Response::new(204));
+        assert!(response.headers.is_empty());
+    }
+}
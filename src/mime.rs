@@ -0,0 +1,155 @@
+use std::collections::HashMap;
+
+/// 未知扩展名使用的 MIME 类型
+pub const FALLBACK_MIME_TYPE: &str = "application/octet-stream";
+
+/// 默认的扩展名到 MIME 类型的映射
+const DEFAULT_MIME_TYPES: &[(&str, &str)] = &[
+    ("html", "text/html"),
+    ("htm", "text/html"),
+    ("css", "text/css"),
+    ("js", "text/javascript"),
+    ("mjs", "text/javascript"),
+    ("json", "application/json"),
+    ("map", "application/json"),
+    ("txt", "text/plain"),
+    ("csv", "text/csv"),
+    ("xml", "application/xml"),
+    ("pdf", "application/pdf"),
+    ("png", "image/png"),
+    ("jpg", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("gif", "image/gif"),
+    ("svg", "image/svg+xml"),
+    ("ico", "image/x-icon"),
+    ("webp", "image/webp"),
+    ("avif", "image/avif"),
+    ("woff", "font/woff"),
+    ("woff2", "font/woff2"),
+    ("ttf", "font/ttf"),
+    ("otf", "font/otf"),
+    ("wasm", "application/wasm"),
+    ("mp3", "audio/mpeg"),
+    ("mp4", "video/mp4"),
+    ("webm", "video/webm"),
+    ("zip", "application/zip"),
+    ("gz", "application/gzip"),
+];
+
+/// 扩展名到 MIME 类型的映射表，启动时可以添加或覆盖默认映射
+#[derive(Clone, Debug, Default)]
+pub struct MimeTypes {
+    overrides: HashMap<String, String>,
+}
+
+impl MimeTypes {
+    /// 添加或覆盖一个映射，扩展名不区分大小写，可以带前导 `.`
+    pub fn insert(&mut self, extension: &str, mime_type: &str) {
+        let extension = extension.trim_start_matches('.').to_ascii_lowercase();
+        self.overrides.insert(extension, mime_type.to_string());
+    }
+
+    /// 按路径的扩展名查找 MIME 类型，未知扩展名返回 `None`
+    pub fn get(&self, path: &str) -> Option<&str> {
+        let extension = extension(path)?;
+        match self.overrides.get(&extension) {
+            Some(mime_type) => Some(mime_type),
+            None => default_mime_type(&extension),
+        }
+    }
+
+    /// 按路径的扩展名查找 MIME 类型，未知扩展名返回 `application/octet-stream`
+    pub fn lookup(&self, path: &str) -> &str {
+        self.get(path).unwrap_or(FALLBACK_MIME_TYPE)
+    }
+}
+
+/// 解析 `ext=type` 形式的映射，用于 `--mime-type` 参数
+pub fn parse_mapping(spec: &str) -> Result<(String, String), String> {
+    match spec.split_once('=') {
+        Some((extension, mime_type)) if !extension.trim().is_empty() && mime_type.contains('/') => {
+            Ok((extension.trim().to_string(), mime_type.trim().to_string()))
+        }
+        _ => Err(format!(
+            "Invalid MIME type mapping `{}`, expected ext=type",
+            spec
+        )),
+    }
+}
+
+/// 只使用默认映射查找 MIME 类型
+pub fn detect_content_type(path: &str) -> &'static str {
+    extension(path)
+        .and_then(|extension| default_mime_type(&extension))
+        .unwrap_or(FALLBACK_MIME_TYPE)
+}
+
+fn default_mime_type(extension: &str) -> Option<&'static str> {
+    DEFAULT_MIME_TYPES
+        .iter()
+        .find(|(known, _)| *known == extension)
+        .map(|(_, mime_type)| *mime_type)
+}
+
+/// 小写的扩展名，文件名中没有 `.` 时返回 `None`
+fn extension(path: &str) -> Option<String> {
+    let file_name = path.rsplit('/').next().unwrap_or(path);
+    let (_, extension) = file_name.rsplit_once('.')?;
+    Some(extension.to_ascii_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_binary_types() {
+        let cases = [
+            ("/logo.png", "image/png"),
+            ("/photo.JPG", "image/jpeg"),
+            ("/a.gif", "image/gif"),
+            ("/a.webp", "image/webp"),
+            ("/icons/a.svg", "image/svg+xml"),
+            ("/favicon.ico", "image/x-icon"),
+            ("/app.wasm", "application/wasm"),
+            ("/doc.pdf", "application/pdf"),
+            ("/f.woff", "font/woff"),
+            ("/f.woff2", "font/woff2"),
+            ("/f.ttf", "font/ttf"),
+            ("/v.mp4", "video/mp4"),
+            ("/v.webm", "video/webm"),
+        ];
+        for (path, expected) in cases {
+            assert_eq!(detect_content_type(path), expected, "{}", path);
+        }
+    }
+
+    #[test]
+    fn unknown_or_missing_extensions_fall_back() {
+        assert_eq!(detect_content_type("/README"), FALLBACK_MIME_TYPE);
+        assert_eq!(detect_content_type("/a.unknown"), FALLBACK_MIME_TYPE);
+        assert_eq!(detect_content_type("/dir.d/file"), FALLBACK_MIME_TYPE);
+    }
+
+    #[test]
+    fn overrides_take_precedence() {
+        let mut types = MimeTypes::default();
+        types.insert(".WASM", "application/x-custom");
+        types.insert("md", "text/markdown");
+
+        assert_eq!(types.lookup("/app.wasm"), "application/x-custom");
+        assert_eq!(types.lookup("/notes.md"), "text/markdown");
+        assert_eq!(types.lookup("/logo.png"), "image/png");
+    }
+
+    #[test]
+    fn parses_mappings() {
+        assert_eq!(
+            parse_mapping("md=text/markdown"),
+            Ok((String::from("md"), String::from("text/markdown")))
+        );
+        assert!(parse_mapping("md").is_err());
+        assert!(parse_mapping("=text/plain").is_err());
+        assert!(parse_mapping("md=markdown").is_err());
+    }
+}
@@ -0,0 +1,227 @@
+use std::{error::Error, fmt};
+
+/// `multipart/form-data` 请求体格式错误
+#[derive(Debug, Clone, PartialEq)]
+pub struct MultipartError(String);
+
+impl fmt::Display for MultipartError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid multipart body: {}", self.0)
+    }
+}
+
+impl Error for MultipartError {}
+
+/// 请求体中的一个部分
+#[derive(Debug, Clone, PartialEq)]
+pub struct Part {
+    pub name: String,
+    /// 文件字段的原始文件名，普通字段为 `None`
+    pub filename: Option<String>,
+    /// 没有 `Content-Type` 时按规范为 `text/plain`
+    pub content_type: String,
+    pub data: Vec<u8>,
+}
+
+/// 从 `Content-Type` 请求头中取出 `boundary` 参数，可以带引号
+pub fn boundary(content_type: &str) -> Option<String> {
+    split_params(content_type)
+        .into_iter()
+        .skip(1)
+        .find_map(|param| {
+            let (key, value) = param.split_once('=')?;
+            key.trim()
+                .eq_ignore_ascii_case("boundary")
+                .then(|| unquote(value.trim()))
+        })
+        .filter(|boundary| !boundary.is_empty())
+}
+
+/// 按 `--{boundary}` 拆分请求体，解析每个部分的请求头和内容
+pub fn parse(body: &[u8], boundary: &str) -> Result<Vec<Part>, MultipartError> {
+    let delimiter = format!("--{}", boundary).into_bytes();
+    let error = |message: &str| MultipartError(message.to_string());
+
+    // 第一个分隔符之前的内容是前言，忽略
+    let mut pos = find(body, &delimiter, 0).ok_or_else(|| error("missing boundary"))?;
+    let mut parts = Vec::new();
+
+    loop {
+        pos += delimiter.len();
+        if body[pos..].starts_with(b"--") {
+            return Ok(parts);
+        }
+        if !body[pos..].starts_with(b"\r\n") {
+            return Err(error("boundary is not followed by CRLF"));
+        }
+        pos += 2;
+
+        // 部分的内容到下一个 `\r\n--{boundary}` 为止
+        let mut next_delimiter = b"\r\n".to_vec();
+        next_delimiter.extend_from_slice(&delimiter);
+        let end =
+            find(body, &next_delimiter, pos).ok_or_else(|| error("missing closing boundary"))?;
+
+        parts.push(parse_part(&body[pos..end])?);
+        pos = end + 2;
+    }
+}
+
+fn parse_part(part: &[u8]) -> Result<Part, MultipartError> {
+    let header_end = find(part, b"\r\n\r\n", 0)
+        .ok_or_else(|| MultipartError(String::from("part has no header terminator")))?;
+    let head = String::from_utf8_lossy(&part[..header_end]);
+
+    let mut disposition = None;
+    let mut content_type = String::from("text/plain");
+    for line in head.split("\r\n").filter(|line| !line.is_empty()) {
+        let (name, value) = line
+            .split_once(':')
+            .ok_or_else(|| MultipartError(format!("invalid part header `{}`", line)))?;
+        match name.trim().to_ascii_lowercase().as_str() {
+            "content-disposition" => disposition = Some(value.trim().to_string()),
+            "content-type" => content_type = value.trim().to_string(),
+            _ => {}
+        }
+    }
+
+    let disposition = disposition
+        .ok_or_else(|| MultipartError(String::from("part has no Content-Disposition")))?;
+    let mut name = None;
+    let mut filename = None;
+    for param in split_params(&disposition).into_iter().skip(1) {
+        if let Some((key, value)) = param.split_once('=') {
+            match key.trim().to_ascii_lowercase().as_str() {
+                "name" => name = Some(unquote(value.trim())),
+                "filename" => filename = Some(unquote(value.trim())),
+                _ => {}
+            }
+        }
+    }
+
+    Ok(Part {
+        name: name.ok_or_else(|| MultipartError(String::from("part has no name")))?,
+        filename,
+        content_type,
+        data: part[header_end + 4..].to_vec(),
+    })
+}
+
+/// 按 `;` 拆分请求头的参数，引号中的 `;` 不拆分
+fn split_params(value: &str) -> Vec<&str> {
+    let mut params = Vec::new();
+    let mut in_quotes = false;
+    let mut start = 0;
+    for (i, c) in value.char_indices() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            ';' if !in_quotes => {
+                params.push(value[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    params.push(value[start..].trim());
+    params
+}
+
+/// 去掉引号，并处理引号中的 `\` 转义
+fn unquote(value: &str) -> String {
+    match value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+    {
+        Some(quoted) => {
+            let mut unquoted = String::with_capacity(quoted.len());
+            let mut chars = quoted.chars();
+            while let Some(c) = chars.next() {
+                match c {
+                    '\\' => unquoted.extend(chars.next()),
+                    _ => unquoted.push(c),
+                }
+            }
+            unquoted
+        }
+        None => value.to_string(),
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8], from: usize) -> Option<usize> {
+    haystack
+        .get(from..)?
+        .windows(needle.len())
+        .position(|window| window == needle)
+        .map(|pos| pos + from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BODY: &[u8] = b"preamble\r\n\
+--XyZ\r\n\
+Content-Disposition: form-data; name=\"title\"\r\n\
+\r\n\
+hello\r\n\
+--XyZ\r\n\
+Content-Disposition: form-data; name=\"file\"; filename=\"a;b \\\"c\\\".bin\"\r\n\
+Content-Type: application/octet-stream\r\n\
+\r\n\
+\x00\x01\r\n--X\xff\r\n\
+--XyZ--\r\n";
+
+    #[test]
+    fn boundary_can_be_quoted() {
+        assert_eq!(
+            boundary("multipart/form-data; boundary=XyZ").as_deref(),
+            Some("XyZ")
+        );
+        assert_eq!(
+            boundary("multipart/form-data; charset=utf-8; Boundary=\"a;b\"").as_deref(),
+            Some("a;b")
+        );
+        assert_eq!(boundary("multipart/form-data"), None);
+        assert_eq!(boundary("multipart/form-data; boundary=\"\""), None);
+    }
+
+    #[test]
+    fn parses_fields_and_files() {
+        let parts = parse(BODY, "XyZ").unwrap();
+        assert_eq!(
+            parts,
+            [
+                Part {
+                    name: String::from("title"),
+                    filename: None,
+                    content_type: String::from("text/plain"),
+                    data: b"hello".to_vec(),
+                },
+                Part {
+                    name: String::from("file"),
+                    filename: Some(String::from("a;b \"c\".bin")),
+                    content_type: String::from("application/octet-stream"),
+                    data: b"\x00\x01\r\n--X\xff".to_vec(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_bodies() {
+        assert!(parse(b"no boundary here", "XyZ").is_err());
+        assert!(parse(b"--XyZ\r\n\r\nunterminated", "XyZ").is_err());
+        assert!(parse(b"--XyZ junk\r\n--XyZ--", "XyZ").is_err());
+        assert!(parse(
+            b"--XyZ\r\nContent-Type: text/plain\r\n\r\nx\r\n--XyZ--",
+            "XyZ"
+        )
+        .is_err());
+        assert!(parse(
+            b"--XyZ\r\nContent-Disposition: form-data\r\n\r\nx\r\n--XyZ--",
+            "XyZ"
+        )
+        .is_err());
+        assert_eq!(parse(b"--XyZ--\r\n", "XyZ"), Ok(Vec::new()));
+    }
+}
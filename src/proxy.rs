@@ -0,0 +1,524 @@
+use crate::{error_page, ServerConfig, ServerError};
+use regex::Regex;
+use rustls::{crypto::ring::default_provider, pki_types::ServerName, ClientConfig, RootCertStore};
+use std::{
+    error::Error,
+    fmt,
+    io::{self, prelude::*},
+    net::{SocketAddr, TcpStream, ToSocketAddrs},
+    sync::{Arc, OnceLock},
+    time::Duration,
+};
+
+/// 代理的上游地址
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProxyTarget {
+    Http(SocketAddr),
+    /// 第二个字段是用于 SNI 和证书校验的主机名
+    Https(SocketAddr, String),
+    /// 请求经 SOCKS5 代理转发到后两个字段配置的主机和端口，不使用请求中的 `Host`，
+    /// 客户端无法借代理连接任意主机
+    Socks5(SocketAddr, String, u16),
+}
+
+/// 解析代理地址失败的原因
+#[derive(Debug)]
+pub enum ProxyError {
+    InvalidFormat(String),
+    InvalidPort(String),
+    Resolve(String, io::Error),
+    NoAddress(String),
+    /// `socks5://` 代理没有配置目标主机
+    MissingSocksTarget,
+    /// 目标主机不是 `host:port` 格式
+    InvalidSocksTarget(String),
+}
+
+impl fmt::Display for ProxyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProxyError::InvalidFormat(proxy) => {
+                write!(f, "Invalid proxy address format: {}", proxy)
+            }
+            ProxyError::InvalidPort(port) => write!(f, "Failed to parse port: {}", port),
+            ProxyError::Resolve(host, err) => {
+                write!(f, "Socket address resolution error for {}: {}", host, err)
+            }
+            ProxyError::NoAddress(host) => write!(f, "Failed to resolve address: {}", host),
+            ProxyError::MissingSocksTarget => {
+                write!(f, "A socks5:// proxy requires a target host:port")
+            }
+            ProxyError::InvalidSocksTarget(target) => {
+                write!(f, "Invalid SOCKS5 target, expected host:port: {}", target)
+            }
+        }
+    }
+}
+
+impl Error for ProxyError {}
+
+/// 解析 `http://`、`https://` 或 `socks5://` 形式的代理地址，端口缺省分别为 80、443、1080
+///
+/// `socks5://` 代理必须同时给出 `socks_target`，即经代理连接的 `host:port`，其他代理忽略它
+pub fn parse_proxy_target(
+    proxy: &str,
+    socks_target: Option<&str>,
+) -> Result<ProxyTarget, ProxyError> {
+    let re =
+        Regex::new(r"^(https?|socks5)://(\[[0-9a-fA-F:.]+\]|[^:/\[\]]+)(?::(\d+))?/?$").unwrap();
+
+    let captures = re
+        .captures(proxy)
+        .ok_or_else(|| ProxyError::InvalidFormat(proxy.to_string()))?;
+    let scheme = &captures[1];
+    let host = captures[2].trim_start_matches('[').trim_end_matches(']');
+
+    let port = match captures.get(3) {
+        Some(port) => port
+            .as_str()
+            .parse()
+            .map_err(|_| ProxyError::InvalidPort(port.as_str().to_string()))?,
+        None => match scheme {
+            "http" => 80,
+            "https" => 443,
+            _ => 1080,
+        },
+    };
+
+    let addr = (host, port)
+        .to_socket_addrs()
+        .map_err(|err| ProxyError::Resolve(host.to_string(), err))?
+        .next()
+        .ok_or_else(|| ProxyError::NoAddress(host.to_string()))?;
+
+    Ok(match scheme {
+        "http" => ProxyTarget::Http(addr),
+        "https" => ProxyTarget::Https(addr, host.to_string()),
+        _ => {
+            let target = socks_target.ok_or(ProxyError::MissingSocksTarget)?;
+            let (host, port) = split_host_port(target)
+                .ok_or_else(|| ProxyError::InvalidSocksTarget(target.to_string()))?;
+            ProxyTarget::Socks5(addr, host, port)
+        }
+    })
+}
+
+/// 拆分 `host:port`，IPv6 地址写在方括号中；主机名由 SOCKS5 代理解析，这里不解析
+fn split_host_port(target: &str) -> Option<(String, u16)> {
+    let (host, port) = match target.strip_prefix('[') {
+        Some(rest) => {
+            let (host, rest) = rest.split_once(']')?;
+            (host, rest.strip_prefix(':')?)
+        }
+        None => target.rsplit_once(':')?,
+    };
+    if host.is_empty()
+        || host.contains(['/', ' '])
+        || (!target.starts_with('[') && host.contains(':'))
+    {
+        return None;
+    }
+    Some((
+        host.to_string(),
+        port.parse().ok().filter(|&port| port != 0)?,
+    ))
+}
+
+/// 把原始请求转发给代理，并把上游的响应原样写回客户端
+///
+/// 在向客户端写出任何数据之前出错时返回 502
+pub fn proxy_request(
+    mut client_stream: impl Read + Write,
+    target: &ProxyTarget,
+    raw_request: &[u8],
+    config: &ServerConfig,
+) -> Result<(), ServerError> {
+    let mut forwarded = 0;
+
+    match forward_request(
+        &mut client_stream,
+        target,
+        raw_request,
+        config.proxy_timeout,
+        &mut forwarded,
+    ) {
+        Ok(()) => Ok(()),
+        Err(_) if forwarded == 0 => {
+            let response = error_page(config, 502);
+            Ok(response.write_to(&mut client_stream)?)
+        }
+        Err(err) => Err(err.into()),
+    }
+}
+
+fn forward_request(
+    client_stream: &mut impl Write,
+    target: &ProxyTarget,
+    raw_request: &[u8],
+    timeout: Duration,
+    forwarded: &mut usize,
+) -> io::Result<()> {
+    // 上游发完响应后关闭连接，才能知道响应在哪里结束
+    let raw_request = with_connection_close(raw_request);
+
+    match target {
+        ProxyTarget::Http(addr) => {
+            let mut upstream = connect(addr, timeout)?;
+            exchange(&mut upstream, client_stream, &raw_request, forwarded)
+        }
+        ProxyTarget::Https(addr, host) => {
+            let server_name = ServerName::try_from(host.clone())
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+            let connection = rustls::ClientConnection::new(client_tls_config(), server_name)
+                .map_err(io::Error::other)?;
+            let mut upstream = rustls::StreamOwned::new(connection, connect(addr, timeout)?);
+            exchange(&mut upstream, client_stream, &raw_request, forwarded)
+        }
+        ProxyTarget::Socks5(addr, host, port) => {
+            let mut upstream = connect(addr, timeout)?;
+            socks5_connect(&mut upstream, host, *port)?;
+            exchange(&mut upstream, client_stream, &raw_request, forwarded)
+        }
+    }
+}
+
+fn connect(addr: &SocketAddr, timeout: Duration) -> io::Result<TcpStream> {
+    let stream = TcpStream::connect_timeout(addr, timeout)?;
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+    Ok(stream)
+}
+
+/// 发送请求，再把上游返回的字节全部复制给客户端
+fn exchange(
+    upstream: &mut (impl Read + Write),
+    client_stream: &mut impl Write,
+    raw_request: &[u8],
+    forwarded: &mut usize,
+) -> io::Result<()> {
+    upstream.write_all(raw_request)?;
+    upstream.flush()?;
+
+    let mut buffer = [0; 8192];
+    loop {
+        let len = match upstream.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(len) => len,
+            // 不少 HTTPS 服务器不发送 close_notify 就直接断开
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof && *forwarded > 0 => break,
+            Err(err) => return Err(err),
+        };
+        client_stream.write_all(&buffer[..len])?;
+        *forwarded += len;
+    }
+
+    client_stream.flush()
+}
+
+/// 去掉原有的 `Connection` 请求头，改为 `Connection: close`；其余请求头按字节原样转发
+fn with_connection_close(raw_request: &[u8]) -> Vec<u8> {
+    let header_end = raw_request
+        .windows(4)
+        .position(|window| window == b"\r\n\r\n")
+        .unwrap_or(raw_request.len());
+    let head = &raw_request[..header_end];
+
+    let mut request = Vec::with_capacity(raw_request.len());
+    let mut rest = head;
+    loop {
+        let (line, next) = match rest.windows(2).position(|window| window == b"\r\n") {
+            Some(end) => (&rest[..end], Some(&rest[end + 2..])),
+            None => (rest, None),
+        };
+        let is_connection = line
+            .iter()
+            .position(|&byte| byte == b':')
+            .is_some_and(|colon| {
+                line[..colon]
+                    .trim_ascii()
+                    .eq_ignore_ascii_case(b"connection")
+            });
+        if !is_connection {
+            request.extend_from_slice(line);
+            request.extend_from_slice(b"\r\n");
+        }
+        match next {
+            Some(next) => rest = next,
+            None => break,
+        }
+    }
+    request.extend_from_slice(b"Connection: close\r\n");
+
+    request.extend_from_slice(raw_request.get(header_end + 2..).unwrap_or(b"\r\n"));
+    request
+}
+
+/// 无认证的 SOCKS5 握手，随后请求代理连接到 `host:port`
+fn socks5_connect(stream: &mut TcpStream, host: &str, port: u16) -> io::Result<()> {
+    let socks_error = |message: &str| io::Error::other(message.to_string());
+
+    stream.write_all(&[0x05, 0x01, 0x00])?;
+    let mut reply = [0; 2];
+    stream.read_exact(&mut reply)?;
+    if reply != [0x05, 0x00] {
+        return Err(socks_error(
+            "SOCKS5 proxy requires unsupported authentication",
+        ));
+    }
+
+    let host = host.as_bytes();
+    let host_len = u8::try_from(host.len()).map_err(|_| socks_error("Host name too long"))?;
+    let mut connect = vec![0x05, 0x01, 0x00, 0x03, host_len];
+    connect.extend_from_slice(host);
+    connect.extend_from_slice(&port.to_be_bytes());
+    stream.write_all(&connect)?;
+
+    let mut reply = [0; 4];
+    stream.read_exact(&mut reply)?;
+    if reply[0] != 0x05 || reply[2] != 0x00 {
+        return Err(socks_error("Invalid SOCKS5 reply"));
+    }
+    if reply[1] != 0x00 {
+        return Err(socks_error(&format!(
+            "SOCKS5 connect failed with code {}",
+            reply[1]
+        )));
+    }
+
+    // 跳过代理返回的绑定地址和端口
+    let address_len = match reply[3] {
+        0x01 => 4,
+        0x04 => 16,
+        0x03 => {
+            let mut len = [0; 1];
+            stream.read_exact(&mut len)?;
+            len[0] as usize
+        }
+        _ => return Err(socks_error("Invalid SOCKS5 address type")),
+    };
+    let mut bound = vec![0; address_len + 2];
+    stream.read_exact(&mut bound)?;
+
+    Ok(())
+}
+
+/// 使用 webpki 内置根证书的客户端 TLS 配置，只构建一次
+fn client_tls_config() -> Arc<ClientConfig> {
+    static CONFIG: OnceLock<Arc<ClientConfig>> = OnceLock::new();
+
+    CONFIG
+        .get_or_init(|| {
+            let roots = RootCertStore {
+                roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+            };
+            let config = ClientConfig::builder_with_provider(Arc::new(default_provider()))
+                .with_safe_default_protocol_versions()
+                .expect("default protocol versions are supported")
+                .with_root_certificates(roots)
+                .with_no_client_auth();
+            Arc::new(config)
+        })
+        .clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{net::TcpListener, thread};
+
+    fn addr(s: &str) -> SocketAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_each_scheme_with_default_ports() {
+        assert_eq!(
+            parse_proxy_target("http://127.0.0.1", None).unwrap(),
+            ProxyTarget::Http(addr("127.0.0.1:80"))
+        );
+        assert_eq!(
+            parse_proxy_target("https://127.0.0.1", None).unwrap(),
+            ProxyTarget::Https(addr("127.0.0.1:443"), String::from("127.0.0.1"))
+        );
+        assert_eq!(
+            parse_proxy_target("socks5://127.0.0.1", Some("example.com:80")).unwrap(),
+            ProxyTarget::Socks5(addr("127.0.0.1:1080"), String::from("example.com"), 80)
+        );
+    }
+
+    #[test]
+    fn explicit_ports_and_ipv6_hosts() {
+        assert_eq!(
+            parse_proxy_target("http://127.0.0.1:8080/", None).unwrap(),
+            ProxyTarget::Http(addr("127.0.0.1:8080"))
+        );
+        assert_eq!(
+            parse_proxy_target("https://[::1]:8443", None).unwrap(),
+            ProxyTarget::Https(addr("[::1]:8443"), String::from("::1"))
+        );
+    }
+
+    #[test]
+    fn hostnames_are_resolved() {
+        let ProxyTarget::Http(resolved) =
+            parse_proxy_target("http://localhost:3000", None).unwrap()
+        else {
+            panic!("expected an http target");
+        };
+        assert!(resolved.ip().is_loopback());
+        assert_eq!(resolved.port(), 3000);
+
+        // SNI 使用原始主机名而不是解析出的地址
+        let target = parse_proxy_target("https://localhost", None).unwrap();
+        assert!(
+            matches!(target, ProxyTarget::Https(addr, host) if addr.port() == 443 && host == "localhost")
+        );
+
+        assert!(matches!(
+            parse_proxy_target("http://no-such-host.invalid", None),
+            Err(ProxyError::Resolve(..) | ProxyError::NoAddress(_))
+        ));
+    }
+
+    #[test]
+    fn malformed_targets_are_rejected() {
+        for proxy in [
+            "ftp://x",
+            "http://",
+            "localhost:8080",
+            "http://host:port",
+            "http://host/path",
+            "",
+        ] {
+            assert!(
+                matches!(
+                    parse_proxy_target(proxy, None),
+                    Err(ProxyError::InvalidFormat(_))
+                ),
+                "{}",
+                proxy
+            );
+        }
+        assert!(matches!(
+            parse_proxy_target("http://127.0.0.1:99999", None),
+            Err(ProxyError::InvalidPort(port)) if port == "99999"
+        ));
+    }
+
+    #[test]
+    fn socks5_requires_a_host_and_port_target() {
+        assert!(matches!(
+            parse_proxy_target("socks5://127.0.0.1", None),
+            Err(ProxyError::MissingSocksTarget)
+        ));
+        assert_eq!(
+            parse_proxy_target("socks5://127.0.0.1:9050", Some("[::1]:8080")).unwrap(),
+            ProxyTarget::Socks5(addr("127.0.0.1:9050"), String::from("::1"), 8080)
+        );
+        for target in [
+            "example.com",
+            "example.com:",
+            ":80",
+            "example.com:0",
+            "::1:80",
+            "a/b:80",
+        ] {
+            assert!(
+                matches!(
+                    parse_proxy_target("socks5://127.0.0.1", Some(target)),
+                    Err(ProxyError::InvalidSocksTarget(_))
+                ),
+                "{}",
+                target
+            );
+        }
+    }
+
+    #[test]
+    fn connection_header_is_replaced_and_other_bytes_are_kept() {
+        let raw_request =
+            b"GET / HTTP/1.1\r\nX-Name: caf\xe9\r\nconnection : keep-alive\r\n\r\nbody";
+        assert_eq!(
+            with_connection_close(raw_request),
+            b"GET / HTTP/1.1\r\nX-Name: caf\xe9\r\nConnection: close\r\n\r\nbody"
+        );
+    }
+
+    #[test]
+    fn malformed_socks5_replies_are_rejected() {
+        for reply in [
+            [0x04, 0x00, 0x00, 0x01],
+            [0x05, 0x00, 0x01, 0x01],
+            [0x05, 0x01, 0x00, 0x01],
+        ] {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let proxy = listener.local_addr().unwrap();
+            thread::spawn(move || {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut greeting = [0; 3];
+                stream.read_exact(&mut greeting).unwrap();
+                stream.write_all(&[0x05, 0x00]).unwrap();
+                let mut connect = [0; 5 + 9 + 2];
+                stream.read_exact(&mut connect).unwrap();
+                stream.write_all(&reply).unwrap();
+                stream.write_all(&[0, 0, 0, 0, 0, 0]).unwrap();
+            });
+
+            let mut stream = TcpStream::connect(proxy).unwrap();
+            assert!(
+                socks5_connect(&mut stream, "localhost", 80).is_err(),
+                "{:?}",
+                reply
+            );
+        }
+    }
+
+    #[test]
+    fn socks5_connects_to_the_configured_target_not_the_host_header() {
+        // 只完成握手的 SOCKS5 代理，把 CONNECT 请求中的目标发到通道
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let proxy = listener.local_addr().unwrap();
+        let (sender, received) = std::sync::mpsc::channel();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut greeting = [0; 3];
+            stream.read_exact(&mut greeting).unwrap();
+            stream.write_all(&[0x05, 0x00]).unwrap();
+
+            let mut header = [0; 5];
+            stream.read_exact(&mut header).unwrap();
+            let mut host = vec![0; header[4] as usize];
+            stream.read_exact(&mut host).unwrap();
+            let mut port = [0; 2];
+            stream.read_exact(&mut port).unwrap();
+            sender
+                .send((String::from_utf8(host).unwrap(), u16::from_be_bytes(port)))
+                .unwrap();
+
+            stream
+                .write_all(&[0x05, 0x00, 0x00, 0x01, 0, 0, 0, 0, 0, 0])
+                .unwrap();
+            stream
+                .write_all(b"HTTP/1.1 204 No Content\r\nConnection: close\r\n\r\n")
+                .unwrap();
+        });
+
+        let target = ProxyTarget::Socks5(proxy, String::from("backend.internal"), 8080);
+        let raw_request = b"GET / HTTP/1.1\r\nHost: 169.254.169.254:22\r\n\r\n";
+        let mut response = Vec::new();
+        forward_request(
+            &mut response,
+            &target,
+            raw_request,
+            Duration::from_secs(5),
+            &mut 0,
+        )
+        .unwrap();
+
+        assert_eq!(
+            received.recv().unwrap(),
+            (String::from("backend.internal"), 8080)
+        );
+        assert!(response.starts_with(b"HTTP/1.1 204"));
+    }
+}
@@ -0,0 +1,110 @@
+use std::{error::Error, fmt};
+
+/// `Range` 请求头中的区间都落在文件之外
+#[derive(Debug, Clone, PartialEq)]
+pub struct RangeNotSatisfiable;
+
+impl fmt::Display for RangeNotSatisfiable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Range not satisfiable")
+    }
+}
+
+impl Error for RangeNotSatisfiable {}
+
+/// 解析 `bytes=start-end`、`bytes=start-` 和 `bytes=-suffix` 形式的单个区间
+///
+/// 成功时返回闭区间 `(start, end)`；语法无法识别或包含多个区间时返回 `None`，
+/// 按规范忽略该请求头并返回完整内容
+pub fn parse_range(header: &str, total: u64) -> Result<Option<(u64, u64)>, RangeNotSatisfiable> {
+    let spec = match header.trim().strip_prefix("bytes=") {
+        Some(spec) if !spec.contains(',') => spec.trim(),
+        _ => return Ok(None),
+    };
+    let (start, end) = match spec.split_once('-') {
+        Some(bounds) => bounds,
+        None => return Ok(None),
+    };
+
+    let range = match (start.trim(), end.trim()) {
+        ("", "") => return Ok(None),
+        ("", suffix) => {
+            let suffix: u64 = match suffix.parse() {
+                Ok(suffix) => suffix,
+                Err(_) => return Ok(None),
+            };
+            if suffix == 0 || total == 0 {
+                return Err(RangeNotSatisfiable);
+            }
+            (total.saturating_sub(suffix), total - 1)
+        }
+        (start, end) => {
+            let start: u64 = match start.parse() {
+                Ok(start) => start,
+                Err(_) => return Ok(None),
+            };
+            let end: u64 = match end {
+                "" => u64::MAX,
+                end => match end.parse() {
+                    Ok(end) => end,
+                    Err(_) => return Ok(None),
+                },
+            };
+            if end < start {
+                return Ok(None);
+            }
+            if start >= total {
+                return Err(RangeNotSatisfiable);
+            }
+            (start, end.min(total - 1))
+        }
+    };
+
+    Ok(Some(range))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_the_three_single_range_forms() {
+        assert_eq!(parse_range("bytes=0-99", 1000), Ok(Some((0, 99))));
+        assert_eq!(parse_range("bytes=900-", 1000), Ok(Some((900, 999))));
+        assert_eq!(parse_range("bytes=-100", 1000), Ok(Some((900, 999))));
+        assert_eq!(parse_range(" bytes= 5 - 5 ", 10), Ok(Some((5, 5))));
+    }
+
+    #[test]
+    fn clamps_to_the_end_of_the_file() {
+        assert_eq!(parse_range("bytes=990-2000", 1000), Ok(Some((990, 999))));
+        assert_eq!(parse_range("bytes=-5000", 1000), Ok(Some((0, 999))));
+    }
+
+    #[test]
+    fn ranges_outside_the_file_are_not_satisfiable() {
+        assert_eq!(parse_range("bytes=1000-", 1000), Err(RangeNotSatisfiable));
+        assert_eq!(
+            parse_range("bytes=1000-1001", 1000),
+            Err(RangeNotSatisfiable)
+        );
+        assert_eq!(parse_range("bytes=-0", 1000), Err(RangeNotSatisfiable));
+        assert_eq!(parse_range("bytes=-1", 0), Err(RangeNotSatisfiable));
+        assert_eq!(parse_range("bytes=0-", 0), Err(RangeNotSatisfiable));
+    }
+
+    #[test]
+    fn unrecognised_or_multiple_ranges_are_ignored() {
+        for header in [
+            "items=0-1",
+            "bytes=0-1,5-6",
+            "bytes=5",
+            "bytes=-",
+            "bytes=a-b",
+            "bytes=5-4",
+            "bytes=-x",
+        ] {
+            assert_eq!(parse_range(header, 1000), Ok(None), "{}", header);
+        }
+    }
+}
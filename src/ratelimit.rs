@@ -0,0 +1,159 @@
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// 超过这个数量的客户端时立即清理空闲的令牌桶
+const MAX_TRACKED_CLIENTS: usize = 10_000;
+
+/// 定期清理空闲令牌桶的间隔
+const SWEEP_INTERVAL: Duration = Duration::from_secs(10);
+
+/// 单个客户端的令牌桶
+#[derive(Debug, Clone, Copy)]
+struct TokenBucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+#[derive(Debug)]
+struct Buckets {
+    clients: HashMap<IpAddr, TokenBucket>,
+    last_sweep: Instant,
+}
+
+/// 按客户端 IP 限制每秒请求数，允许的突发量为每秒上限的 2 倍
+#[derive(Debug)]
+pub struct RateLimiter {
+    rate: f64,
+    burst: f64,
+    buckets: Mutex<Buckets>,
+}
+
+impl RateLimiter {
+    pub fn new(per_second: u32) -> RateLimiter {
+        assert!(per_second > 0);
+
+        RateLimiter {
+            rate: per_second as f64,
+            burst: per_second as f64 * 2.0,
+            buckets: Mutex::new(Buckets {
+                clients: HashMap::new(),
+                last_sweep: Instant::now(),
+            }),
+        }
+    }
+
+    /// 消耗客户端的一个令牌，令牌不足时返回还需要等待的时间
+    pub fn check(&self, ip: IpAddr) -> Result<(), Duration> {
+        self.check_at(ip, Instant::now())
+    }
+
+    fn check_at(&self, ip: IpAddr, now: Instant) -> Result<(), Duration> {
+        let mut buckets = self.buckets.lock().unwrap();
+
+        let crowded =
+            buckets.clients.len() >= MAX_TRACKED_CLIENTS && !buckets.clients.contains_key(&ip);
+        if crowded || now.saturating_duration_since(buckets.last_sweep) >= SWEEP_INTERVAL {
+            self.sweep(&mut buckets, now);
+        }
+
+        let bucket = buckets.clients.entry(ip).or_insert(TokenBucket {
+            tokens: self.burst,
+            last_refill: now,
+        });
+        let tokens = refill(bucket, now, self.rate, self.burst);
+
+        if tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - tokens) / self.rate))
+        }
+    }
+
+    /// 删除空闲到令牌已经回满的客户端，它们再次出现时与新客户端没有区别
+    fn sweep(&self, buckets: &mut Buckets, now: Instant) {
+        let (rate, burst) = (self.rate, self.burst);
+        buckets
+            .clients
+            .retain(|_, bucket| refill(bucket, now, rate, burst) < burst);
+        buckets.last_sweep = now;
+    }
+
+    /// 正在跟踪的客户端数
+    pub fn tracked_clients(&self) -> usize {
+        self.buckets.lock().unwrap().clients.len()
+    }
+}
+
+/// 按经过的时间补充令牌，不超过突发上限，返回补充后的令牌数
+fn refill(bucket: &mut TokenBucket, now: Instant, rate: f64, burst: f64) -> f64 {
+    let elapsed = now
+        .saturating_duration_since(bucket.last_refill)
+        .as_secs_f64();
+    bucket.tokens = (bucket.tokens + elapsed * rate).min(burst);
+    bucket.last_refill = now;
+    bucket.tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    fn ip(last: u8) -> IpAddr {
+        IpAddr::V4(Ipv4Addr::new(10, 0, 0, last))
+    }
+
+    #[test]
+    fn allows_a_burst_of_twice_the_rate() {
+        let limiter = RateLimiter::new(5);
+        let now = Instant::now();
+
+        for _ in 0..10 {
+            assert!(limiter.check_at(ip(1), now).is_ok());
+        }
+        let retry_after = limiter.check_at(ip(1), now).unwrap_err();
+        assert_eq!(retry_after, Duration::from_millis(200));
+        assert!(limiter.check_at(ip(2), now).is_ok());
+    }
+
+    #[test]
+    fn tokens_refill_at_the_configured_rate() {
+        let limiter = RateLimiter::new(10);
+        let now = Instant::now();
+        for _ in 0..20 {
+            limiter.check_at(ip(1), now).unwrap();
+        }
+
+        let later = now + Duration::from_millis(300);
+        let allowed = (0..10)
+            .filter(|_| limiter.check_at(ip(1), later).is_ok())
+            .count();
+        assert_eq!(allowed, 3);
+    }
+
+    #[test]
+    fn idle_clients_are_forgotten() {
+        let limiter = RateLimiter::new(1);
+        let now = Instant::now();
+        limiter.check_at(ip(1), now).unwrap();
+        limiter.check_at(ip(2), now).unwrap();
+        assert_eq!(limiter.tracked_clients(), 2);
+
+        // 第一个客户端之后一直活跃，第二个空闲到令牌回满
+        let later = now + SWEEP_INTERVAL;
+        for _ in 0..2 {
+            let _ = limiter.check_at(ip(1), later - Duration::from_millis(1));
+        }
+        limiter.check_at(ip(3), later).unwrap();
+
+        assert_eq!(limiter.tracked_clients(), 2);
+        let buckets = limiter.buckets.lock().unwrap();
+        assert!(buckets.clients.contains_key(&ip(1)));
+        assert!(!buckets.clients.contains_key(&ip(2)));
+    }
+}
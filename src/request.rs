@@ -0,0 +1,658 @@
+use crate::{jwt::Claims, Limits};
+use std::{collections::HashMap, error::Error, fmt, io, io::prelude::*};
+use tracing::instrument;
+
+/// chunked 请求体中块大小行和 trailer 行的长度上限
+const MAX_CHUNK_LINE: usize = 4096;
+
+/// 一次 HTTP 请求
+#[derive(Debug, Clone)]
+pub struct Request {
+    pub method: String,
+    /// 请求目标中 `?` 之前的部分，路由前解码
+    pub path: String,
+    /// `?` 之后的查询字符串，保持原样，由读取参数的处理函数解码
+    pub query: Option<String>,
+    pub version: String,
+    /// 请求头，键统一为小写
+    pub headers: HashMap<String, String>,
+    pub body: Vec<u8>,
+    /// 通过 JWT 认证后 token 中的声明
+    pub claims: Option<Claims>,
+}
+
+/// 请求报文格式错误
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseError {
+    EmptyRequest,
+    InvalidRequestLine(String),
+    InvalidHeader(String),
+    InvalidContentLength(String),
+    InvalidTransferEncoding(String),
+    /// POST 请求既没有 `Content-Length` 也不是 chunked 编码
+    MissingContentLength,
+    /// chunked 请求体中的块大小行或块结尾格式错误
+    InvalidChunk(String),
+    /// 同时带有 `Transfer-Encoding` 和 `Content-Length`，请求体的边界有歧义
+    ConflictingFraming,
+    /// 格式正确但不支持的协议版本，只支持 HTTP/1.0 和 HTTP/1.1
+    UnsupportedVersion(String),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::EmptyRequest => write!(f, "Empty request"),
+            ParseError::InvalidRequestLine(line) => write!(f, "Invalid request line: {}", line),
+            ParseError::InvalidHeader(line) => write!(f, "Invalid header line: {}", line),
+            ParseError::InvalidContentLength(value) => {
+                write!(f, "Invalid Content-Length header: {}", value)
+            }
+            ParseError::InvalidTransferEncoding(value) => {
+                write!(f, "Unsupported Transfer-Encoding: {}", value)
+            }
+            ParseError::InvalidChunk(line) => write!(f, "Invalid chunk: {}", line),
+            ParseError::ConflictingFraming => {
+                write!(f, "Both Transfer-Encoding and Content-Length are present")
+            }
+            ParseError::MissingContentLength => write!(f, "Missing Content-Length header"),
+            ParseError::UnsupportedVersion(version) => {
+                write!(f, "Unsupported HTTP version: {}", version)
+            }
+        }
+    }
+}
+
+impl Error for ParseError {}
+
+/// 读取或解析请求失败的原因
+#[derive(Debug)]
+pub enum RequestError {
+    HeaderTooLarge,
+    BodyTooLarge,
+    /// 收到部分请求后读取超时
+    Timeout,
+    Parse(ParseError),
+    Io(io::Error),
+}
+
+impl fmt::Display for RequestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RequestError::HeaderTooLarge => write!(f, "Request header fields too large"),
+            RequestError::BodyTooLarge => write!(f, "Payload too large"),
+            RequestError::Timeout => write!(f, "Request timeout"),
+            RequestError::Parse(err) => write!(f, "{}", err),
+            RequestError::Io(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl Error for RequestError {}
+
+impl From<io::Error> for RequestError {
+    fn from(err: io::Error) -> Self {
+        RequestError::Io(err)
+    }
+}
+
+impl From<ParseError> for RequestError {
+    fn from(err: ParseError) -> Self {
+        RequestError::Parse(err)
+    }
+}
+
+impl Request {
+    /// 从连接中读取并解析一个完整的请求
+    pub fn parse(reader: &mut impl BufRead, limits: &Limits) -> Result<Request, RequestError> {
+        let raw_request = read_raw_request(reader, limits)?;
+        Ok(Request::from_bytes(&raw_request)?)
+    }
+
+    /// 从字节中解析请求，请求头结束标志之后的字节全部作为请求体
+    #[instrument(level = "debug", skip_all, fields(len = buf.len()))]
+    pub fn from_bytes(buf: &[u8]) -> Result<Request, ParseError> {
+        let header_end = find_header_end(buf).unwrap_or(buf.len());
+        let head = String::from_utf8_lossy(&buf[..header_end]);
+
+        let mut lines = head.split("\r\n");
+        let (method, target, version) = parse_request_line(lines.next().unwrap_or(""))?;
+        let headers = parse_headers(lines)?;
+        let (path, query) = match target.split_once('?') {
+            Some((path, query)) => (path.to_string(), Some(query.to_string())),
+            None => (target, None),
+        };
+
+        Ok(Request {
+            method,
+            path,
+            query,
+            version,
+            headers,
+            body: buf[header_end..].to_vec(),
+            claims: None,
+        })
+    }
+
+    /// `Content-Length` 请求头的值，缺省为 0
+    pub fn content_length(&self) -> Result<usize, ParseError> {
+        match self.header("content-length") {
+            Some(value) => value
+                .parse()
+                .map_err(|_| ParseError::InvalidContentLength(value.to_string())),
+            None => Ok(0),
+        }
+    }
+
+    /// 请求体是否使用 chunked 编码；`Transfer-Encoding` 的最后一项不是 chunked 时无法确定请求体的长度
+    pub fn is_chunked(&self) -> Result<bool, ParseError> {
+        match self.header("transfer-encoding") {
+            Some(value) => match value.rsplit(',').next() {
+                Some(last) if last.trim().eq_ignore_ascii_case("chunked") => Ok(true),
+                _ => Err(ParseError::InvalidTransferEncoding(value.to_string())),
+            },
+            None => Ok(false),
+        }
+    }
+
+    /// 按名称查找请求头，不区分大小写
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .get(&name.to_ascii_lowercase())
+            .map(|value| value.as_str())
+    }
+
+    /// 查询字符串，没有时为空
+    pub fn query(&self) -> &str {
+        self.query.as_deref().unwrap_or("")
+    }
+}
+
+/// 读取一个完整请求的原始字节，包括请求头和 `Content-Length` 指定长度的请求体
+///
+/// 请求之后多余的字节不会被读取，保留给同一连接上的下一个请求
+pub fn read_raw_request(
+    reader: &mut impl BufRead,
+    limits: &Limits,
+) -> Result<Vec<u8>, RequestError> {
+    let head = read_raw_head(reader, limits)?;
+    read_raw_body(reader, head, limits)
+}
+
+/// 只读取请求头的原始字节，并在读取请求体之前检查声明的长度，超出限制的请求体不会被读取
+///
+/// 同时带有 `Transfer-Encoding` 和 `Content-Length` 的请求可能被前后两个服务器按不同的边界解析，
+/// 按 RFC 9112 §6.3 直接拒绝
+pub fn read_raw_head(reader: &mut impl BufRead, limits: &Limits) -> Result<Vec<u8>, RequestError> {
+    let head = read_request_head(reader, limits.max_header_size)?;
+
+    let request = Request::from_bytes(&head)?;
+    if request.header("transfer-encoding").is_some() && request.header("content-length").is_some() {
+        return Err(ParseError::ConflictingFraming.into());
+    }
+    let content_length = request.content_length()?;
+    if content_length > limits.max_body_size
+        || head.len().saturating_add(content_length) > limits.max_request_size
+    {
+        return Err(RequestError::BodyTooLarge);
+    }
+
+    Ok(head)
+}
+
+/// 在 [`read_raw_head`] 读到的请求头之后读取请求体，返回完整请求的原始字节
+///
+/// chunked 编码的请求体解码后返回，请求头中的 `Transfer-Encoding` 换成对应的 `Content-Length`，
+/// 之后的处理和转发与普通请求体相同
+pub fn read_raw_body(
+    reader: &mut impl BufRead,
+    head: Vec<u8>,
+    limits: &Limits,
+) -> Result<Vec<u8>, RequestError> {
+    let request = Request::from_bytes(&head)?;
+
+    let mut raw_request;
+    let body = if request.is_chunked()? {
+        let max_size = limits
+            .max_body_size
+            .min(limits.max_request_size.saturating_sub(head.len()));
+        let body = read_chunked_body(reader, max_size)?;
+        raw_request = with_content_length(&head, body.len());
+        body
+    } else if request.method == "POST" && request.header("content-length").is_none() {
+        return Err(ParseError::MissingContentLength.into());
+    } else {
+        raw_request = head;
+        read_request_body(reader, request.content_length()?)?
+    };
+    raw_request.extend_from_slice(&body);
+
+    Ok(raw_request)
+}
+
+fn parse_request_line(line: &str) -> Result<(String, String, String), ParseError> {
+    if line.is_empty() {
+        return Err(ParseError::EmptyRequest);
+    }
+
+    let mut parts = line.split_whitespace();
+
+    match (parts.next(), parts.next(), parts.next(), parts.next()) {
+        (Some(method), Some(path), Some(version), None) => {
+            check_version(version, line)?;
+            Ok((method.to_string(), path.to_string(), version.to_string()))
+        }
+        _ => Err(ParseError::InvalidRequestLine(line.to_string())),
+    }
+}
+
+/// 协议版本必须是 `HTTP/x.y`，其中只接受 1.0 和 1.1，其他格式的版本号视为请求行格式错误
+fn check_version(version: &str, line: &str) -> Result<(), ParseError> {
+    let is_digit = |part: &str| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit());
+    match version
+        .strip_prefix("HTTP/")
+        .and_then(|number| number.split_once('.'))
+    {
+        Some((major, minor)) if is_digit(major) && is_digit(minor) => match version {
+            "HTTP/1.0" | "HTTP/1.1" => Ok(()),
+            _ => Err(ParseError::UnsupportedVersion(version.to_string())),
+        },
+        _ => Err(ParseError::InvalidRequestLine(line.to_string())),
+    }
+}
+
+/// 解析请求头，重复出现的请求头按出现顺序用 `, ` 连接
+fn parse_headers<'a>(
+    lines: impl Iterator<Item = &'a str>,
+) -> Result<HashMap<String, String>, ParseError> {
+    let mut headers: HashMap<String, String> = HashMap::new();
+
+    for line in lines.take_while(|line| !line.is_empty()) {
+        let (name, value) = line
+            .split_once(':')
+            .ok_or_else(|| ParseError::InvalidHeader(line.to_string()))?;
+
+        let name = name.trim().to_ascii_lowercase();
+        let value = value.trim();
+
+        headers
+            .entry(name)
+            .and_modify(|existing| {
+                existing.push_str(", ");
+                existing.push_str(value);
+            })
+            .or_insert_with(|| value.to_string());
+    }
+
+    Ok(headers)
+}
+
+/// 持续读取直到遇到请求头结束标志 `\r\n\r\n`
+fn read_request_head(
+    reader: &mut impl BufRead,
+    max_header_size: usize,
+) -> Result<Vec<u8>, RequestError> {
+    let mut buffer = Vec::new();
+
+    loop {
+        let available = match reader.fill_buf() {
+            Ok(available) => available,
+            Err(err) if is_timeout(&err) && !buffer.is_empty() => {
+                return Err(RequestError::Timeout)
+            }
+            Err(err) => return Err(err.into()),
+        };
+        let len = available.len();
+        if len == 0 {
+            break;
+        }
+
+        let search_start = buffer.len().saturating_sub(3);
+        buffer.extend_from_slice(available);
+
+        // 只消费到请求头结束为止，之后的字节留给请求体和同一连接上的下一个请求
+        if let Some(end) = find_header_end(&buffer[search_start..]) {
+            let end = search_start + end;
+            reader.consume(len - (buffer.len() - end));
+            buffer.truncate(end);
+            if end > max_header_size {
+                return Err(RequestError::HeaderTooLarge);
+            }
+            break;
+        }
+        reader.consume(len);
+
+        if buffer.len() > max_header_size {
+            return Err(RequestError::HeaderTooLarge);
+        }
+    }
+
+    Ok(buffer)
+}
+
+/// 读取 `content_length` 字节的请求体
+fn read_request_body(
+    reader: &mut impl Read,
+    content_length: usize,
+) -> Result<Vec<u8>, RequestError> {
+    let mut body = vec![0; content_length];
+    reader
+        .read_exact(&mut body)
+        .map_err(|err| match is_timeout(&err) {
+            true => RequestError::Timeout,
+            false => err.into(),
+        })?;
+
+    Ok(body)
+}
+
+/// 解码 chunked 请求体：逐块读取直到大小为 0 的块，忽略块扩展和结尾的 trailer
+///
+/// 解码后的总长度超过 `max_size` 时停止读取
+fn read_chunked_body(reader: &mut impl BufRead, max_size: usize) -> Result<Vec<u8>, RequestError> {
+    let mut body = Vec::new();
+
+    loop {
+        let line = read_chunk_line(reader)?;
+        let size = line.split(';').next().unwrap_or("").trim();
+        let size =
+            usize::from_str_radix(size, 16).map_err(|_| ParseError::InvalidChunk(line.clone()))?;
+
+        if size == 0 {
+            // trailer 以空行结束
+            while !read_chunk_line(reader)?.is_empty() {}
+            return Ok(body);
+        }
+        if body.len().saturating_add(size) > max_size {
+            return Err(RequestError::BodyTooLarge);
+        }
+
+        body.extend_from_slice(&read_request_body(reader, size)?);
+        if !read_chunk_line(reader)?.is_empty() {
+            return Err(
+                ParseError::InvalidChunk(String::from("missing CRLF after chunk data")).into(),
+            );
+        }
+    }
+}
+
+/// 读取以 CRLF 结尾的一行，长度超过 [`MAX_CHUNK_LINE`] 时视为格式错误
+fn read_chunk_line(reader: &mut impl BufRead) -> Result<String, RequestError> {
+    let mut line = Vec::new();
+    reader
+        .take(MAX_CHUNK_LINE as u64)
+        .read_until(b'\n', &mut line)
+        .map_err(|err| match is_timeout(&err) {
+            true => RequestError::Timeout,
+            false => err.into(),
+        })?;
+
+    match line.strip_suffix(b"\r\n") {
+        Some(line) => Ok(String::from_utf8_lossy(line).into_owned()),
+        None => Err(ParseError::InvalidChunk(String::from_utf8_lossy(&line).into_owned()).into()),
+    }
+}
+
+/// 去掉请求头中的 `Transfer-Encoding` 和 `Content-Length`，换成解码后请求体的长度
+fn with_content_length(head: &[u8], length: usize) -> Vec<u8> {
+    let head = head.strip_suffix(b"\r\n\r\n").unwrap_or(head);
+
+    let mut rewritten = Vec::with_capacity(head.len() + 32);
+    for (i, line) in head.split(|&b| b == b'\n').enumerate() {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        let name = line.split(|&b| b == b':').next().unwrap_or(b"");
+        if i > 0
+            && (name.eq_ignore_ascii_case(b"transfer-encoding")
+                || name.eq_ignore_ascii_case(b"content-length"))
+        {
+            continue;
+        }
+        rewritten.extend_from_slice(line);
+        rewritten.extend_from_slice(b"\r\n");
+    }
+    rewritten.extend_from_slice(format!("Content-Length: {}\r\n\r\n", length).as_bytes());
+
+    rewritten
+}
+
+/// 读取超时在不同平台上分别表现为 `WouldBlock` 和 `TimedOut`
+pub(crate) fn is_timeout(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+    )
+}
+
+/// 返回请求头结束标志之后的下标
+fn find_header_end(buffer: &[u8]) -> Option<usize> {
+    buffer
+        .windows(4)
+        .position(|window| window == b"\r\n\r\n")
+        .map(|pos| pos + 4)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufReader, Cursor};
+
+    /// 每次最多返回 `chunk` 字节的读取端，模拟分多次到达的数据
+    struct Trickle<'a> {
+        data: &'a [u8],
+        chunk: usize,
+    }
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let len = self.chunk.min(buf.len()).min(self.data.len());
+            buf[..len].copy_from_slice(&self.data[..len]);
+            self.data = &self.data[len..];
+            Ok(len)
+        }
+    }
+
+    fn parse(raw: &[u8]) -> Result<Request, RequestError> {
+        Request::parse(&mut Cursor::new(raw), &Limits::default())
+    }
+
+    #[test]
+    fn reads_headers_longer_than_one_read() {
+        let raw = format!(
+            "GET / HTTP/1.1\r\nCookie: {}\r\nAccept: */*\r\n\r\n",
+            "c".repeat(3 * 1024)
+        );
+        let mut reader = BufReader::with_capacity(
+            64,
+            Trickle {
+                data: raw.as_bytes(),
+                chunk: 100,
+            },
+        );
+
+        let request = Request::parse(&mut reader, &Limits::default()).unwrap();
+
+        assert_eq!(request.header("cookie").unwrap().len(), 3 * 1024);
+        assert_eq!(request.header("accept"), Some("*/*"));
+    }
+
+    #[test]
+    fn rejects_headers_over_the_limit() {
+        let raw = format!("GET / HTTP/1.1\r\nCookie: {}\r\n\r\n", "c".repeat(2048));
+        let limits = Limits {
+            max_header_size: 1024,
+            ..Limits::default()
+        };
+
+        let result = Request::parse(&mut Cursor::new(raw.as_bytes()), &limits);
+
+        assert!(matches!(result, Err(RequestError::HeaderTooLarge)));
+    }
+
+    #[test]
+    fn leaves_pipelined_requests_unread() {
+        let raw = b"GET /a HTTP/1.1\r\n\r\nGET /b HTTP/1.1\r\n\r\n";
+        let mut reader = Cursor::new(&raw[..]);
+
+        let first = Request::parse(&mut reader, &Limits::default()).unwrap();
+        let second = Request::parse(&mut reader, &Limits::default()).unwrap();
+
+        assert_eq!(first.path, "/a");
+        assert_eq!(second.path, "/b");
+    }
+
+    #[test]
+    fn empty_input_is_an_empty_request() {
+        assert!(matches!(
+            parse(b""),
+            Err(RequestError::Parse(ParseError::EmptyRequest))
+        ));
+    }
+
+    #[test]
+    fn query_is_kept_apart_from_the_path() {
+        let request = parse(b"GET /a%3Fb/c?x=%3F&y HTTP/1.1\r\n\r\n").unwrap();
+        assert_eq!(request.path, "/a%3Fb/c");
+        assert_eq!(request.query.as_deref(), Some("x=%3F&y"));
+
+        let request = parse(b"GET /a? HTTP/1.1\r\n\r\n").unwrap();
+        assert_eq!(request.path, "/a");
+        assert_eq!(request.query(), "");
+
+        assert_eq!(parse(b"GET /a HTTP/1.1\r\n\r\n").unwrap().query, None);
+    }
+
+    #[test]
+    fn chunked_body_is_decoded_and_reframed() {
+        let raw = b"POST /echo HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n\
+            5;ext=1\r\nhello\r\n7\r\n, world\r\n0\r\nX-Trailer: yes\r\n\r\nGET /next";
+        let mut reader = Cursor::new(&raw[..]);
+
+        let raw_request = read_raw_request(&mut reader, &Limits::default()).unwrap();
+        let request = Request::from_bytes(&raw_request).unwrap();
+
+        assert_eq!(request.body, b"hello, world");
+        assert_eq!(request.header("content-length"), Some("12"));
+        assert_eq!(request.header("transfer-encoding"), None);
+        let mut rest = String::new();
+        reader.read_to_string(&mut rest).unwrap();
+        assert_eq!(rest, "GET /next");
+    }
+
+    #[test]
+    fn malformed_chunks_are_rejected() {
+        for body in [
+            &b"zz\r\nhello\r\n0\r\n\r\n"[..],
+            b"5\r\nhelloX\r\n0\r\n\r\n",
+            b"5\r\nhel",
+            b"5\nhello\r\n0\r\n\r\n",
+        ] {
+            let raw = [
+                &b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n"[..],
+                body,
+            ]
+            .concat();
+            let result = parse(&raw);
+            assert!(
+                matches!(
+                    result,
+                    Err(RequestError::Parse(ParseError::InvalidChunk(_)) | RequestError::Io(_))
+                ),
+                "{:?}: {:?}",
+                String::from_utf8_lossy(body),
+                result
+            );
+        }
+    }
+
+    #[test]
+    fn chunked_body_over_the_limit_is_rejected() {
+        let limits = Limits {
+            max_body_size: 8,
+            ..Limits::default()
+        };
+        let raw = b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n\
+            5\r\nhello\r\n5\r\nworld\r\n0\r\n\r\n";
+
+        let result = Request::parse(&mut Cursor::new(&raw[..]), &limits);
+        assert!(matches!(result, Err(RequestError::BodyTooLarge)));
+    }
+
+    #[test]
+    fn chunked_body_over_the_request_size_is_rejected() {
+        let raw = b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n\
+            5\r\nhello\r\n5\r\nworld\r\n0\r\n\r\n";
+        let limits = Limits {
+            max_request_size: raw.len() - 20,
+            ..Limits::default()
+        };
+
+        let result = Request::parse(&mut Cursor::new(&raw[..]), &limits);
+        assert!(matches!(result, Err(RequestError::BodyTooLarge)));
+    }
+
+    #[test]
+    fn chunked_requests_with_content_length_are_rejected() {
+        for headers in [
+            "Transfer-Encoding: chunked\r\nContent-Length: 5",
+            "Content-Length: 5\r\nTransfer-Encoding: chunked",
+        ] {
+            let raw = format!(
+                "POST / HTTP/1.1\r\n{}\r\n\r\n5\r\nhello\r\n0\r\n\r\n",
+                headers
+            );
+            assert!(matches!(
+                parse(raw.as_bytes()),
+                Err(RequestError::Parse(ParseError::ConflictingFraming))
+            ));
+        }
+    }
+
+    #[test]
+    fn transfer_encoding_must_end_with_chunked() {
+        let raw = b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked, gzip\r\n\r\n";
+        assert!(matches!(
+            parse(raw),
+            Err(RequestError::Parse(ParseError::InvalidTransferEncoding(_)))
+        ));
+    }
+
+    #[test]
+    fn header_names_are_case_insensitive_and_values_trimmed() {
+        let request = parse(
+            b"GET / HTTP/1.1\r\ncontent-type:application/json\r\nX-MiXeD-Case:   spaced out \t\r\n\r\n",
+        )
+        .unwrap();
+
+        assert_eq!(request.header("Content-Type"), Some("application/json"));
+        assert_eq!(request.header("CONTENT-TYPE"), Some("application/json"));
+        assert_eq!(request.header("x-mixed-case"), Some("spaced out"));
+        assert_eq!(request.header("missing"), None);
+    }
+
+    #[test]
+    fn header_values_may_contain_colons() {
+        let request =
+            parse(b"GET / HTTP/1.1\r\nReferer: http://x:8080/a?b=c:d\r\nHost: x:8080\r\n\r\n")
+                .unwrap();
+
+        assert_eq!(request.header("referer"), Some("http://x:8080/a?b=c:d"));
+        assert_eq!(request.header("host"), Some("x:8080"));
+    }
+
+    #[test]
+    fn repeated_headers_are_joined_in_order() {
+        let request =
+            parse(b"GET / HTTP/1.1\r\nAccept: text/html\r\naccept: */*\r\nEmpty:\r\n\r\n").unwrap();
+
+        assert_eq!(request.header("accept"), Some("text/html, */*"));
+        assert_eq!(request.header("empty"), Some(""));
+    }
+
+    #[test]
+    fn header_lines_without_a_colon_are_rejected() {
+        assert!(matches!(
+            parse(b"GET / HTTP/1.1\r\nNoColonHere\r\n\r\n"),
+            Err(RequestError::Parse(ParseError::InvalidHeader(_)))
+        ));
+    }
+}
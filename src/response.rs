@@ -0,0 +1,606 @@
+use crate::{cache, datetime};
+use flate2::{write::GzEncoder, Compression};
+use std::{
+    borrow::Cow,
+    fmt,
+    fs::File,
+    io::{self, prelude::*, SeekFrom},
+    path::PathBuf,
+    sync::Arc,
+};
+
+/// 写出文件响应体时每次读取的块大小
+const FILE_CHUNK_SIZE: usize = 64 * 1024;
+
+/// 小于这个大小的响应体压缩收益不大，保持原样
+const MIN_COMPRESSED_SIZE: u64 = 1024;
+
+/// 不超过这个大小的文件响应体才会被读入内存压缩
+const MAX_COMPRESSED_FILE_SIZE: u64 = 1024 * 1024;
+
+/// 响应体，文本和二进制内容都按字节写出
+#[derive(Debug, Clone)]
+pub enum ResponseBody {
+    Text(String),
+    Bytes(Vec<u8>),
+    /// 写出时才打开文件，从 `offset` 开始按块读取 `length` 字节
+    File {
+        path: PathBuf,
+        offset: u64,
+        length: u64,
+    },
+    /// 长度未知的响应体，写出时调用生成函数并按 chunked 编码分块发送
+    Stream(StreamBody),
+}
+
+/// 生成流式响应体的函数，写入的数据会立即作为一个块发送
+pub type Producer = dyn Fn(&mut dyn Write) -> io::Result<()> + Send + Sync;
+
+#[derive(Clone)]
+pub struct StreamBody(Arc<Producer>);
+
+impl fmt::Debug for StreamBody {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("StreamBody")
+    }
+}
+
+impl ResponseBody {
+    /// 内存中的内容，文件响应体返回 `None`
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            ResponseBody::Text(text) => Some(text.as_bytes()),
+            ResponseBody::Bytes(bytes) => Some(bytes),
+            ResponseBody::File { .. } | ResponseBody::Stream(_) => None,
+        }
+    }
+
+    /// 字节长度，流式响应体的长度未知，返回 0
+    pub fn len(&self) -> u64 {
+        match self {
+            ResponseBody::File { length, .. } => *length,
+            _ => self.as_bytes().map_or(0, |bytes| bytes.len() as u64),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// 读出全部内容，文件会读入内存；流式响应体可能永远不会结束，不能读入内存
+    fn to_bytes(&self) -> io::Result<Cow<'_, [u8]>> {
+        match self {
+            ResponseBody::Stream(_) => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "Stream bodies cannot be buffered",
+            )),
+            ResponseBody::File { .. } => {
+                let mut contents = Vec::with_capacity(self.len() as usize);
+                self.write_to(&mut contents)?;
+                Ok(Cow::Owned(contents))
+            }
+            _ => Ok(Cow::Borrowed(self.as_bytes().unwrap_or_default())),
+        }
+    }
+
+    fn write_to(&self, stream: &mut impl Write) -> io::Result<()> {
+        let (path, offset, length) = match self {
+            ResponseBody::File {
+                path,
+                offset,
+                length,
+            } => (path, *offset, *length),
+            ResponseBody::Stream(StreamBody(producer)) => {
+                let mut writer = ChunkedWriter::new(stream);
+                producer(&mut writer)?;
+                return writer.finish();
+            }
+            _ => return stream.write_all(self.as_bytes().unwrap_or_default()),
+        };
+
+        let mut file = File::open(path)?;
+        file.seek(SeekFrom::Start(offset))?;
+        let mut file = file.take(length);
+
+        let mut chunk = vec![0; FILE_CHUNK_SIZE];
+        let mut written = 0;
+        loop {
+            let len = file.read(&mut chunk)?;
+            if len == 0 {
+                break;
+            }
+            stream.write_all(&chunk[..len])?;
+            written += len as u64;
+        }
+
+        // 文件在响应头写出后被截短时，已经无法再修正 Content-Length
+        if written < length {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "File shrank while it was being sent",
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// 按 chunked 编码写出响应体：每次写入作为一个块，`finish` 或者被丢弃时写出结束块 `0\r\n\r\n`
+pub struct ChunkedWriter<W: Write> {
+    inner: W,
+    finished: bool,
+}
+
+impl<W: Write> ChunkedWriter<W> {
+    pub fn new(inner: W) -> ChunkedWriter<W> {
+        ChunkedWriter {
+            inner,
+            finished: false,
+        }
+    }
+
+    /// 写出结束块
+    pub fn finish(mut self) -> io::Result<()> {
+        self.finished = true;
+        self.inner.write_all(b"0\r\n\r\n")?;
+        self.inner.flush()
+    }
+}
+
+impl<W: Write> Write for ChunkedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // 长度为 0 的块表示响应体结束，空的写入直接忽略
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        self.inner
+            .write_all(format!("{:x}\r\n", buf.len()).as_bytes())?;
+        self.inner.write_all(buf)?;
+        self.inner.write_all(b"\r\n")?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<W: Write> Drop for ChunkedWriter<W> {
+    fn drop(&mut self) {
+        if !self.finished {
+            let _ = self.inner.write_all(b"0\r\n\r\n");
+        }
+    }
+}
+
+/// 直接写出长度未知的响应：状态行、`headers`、`Transfer-Encoding: chunked`，
+/// 然后把 `body_iter` 产生的每段数据作为一个块写出
+pub fn stream_response(
+    stream: &mut impl Write,
+    status: u16,
+    headers: &[(&str, &str)],
+    body_iter: impl Iterator<Item = Vec<u8>>,
+) -> io::Result<()> {
+    // 响应体只用来让响应头带上 chunked，实际内容来自 `body_iter`
+    let mut response = Response::new(status).body_stream(|_| Ok(()));
+    response.headers = headers
+        .iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect();
+    response.write_head(stream)?;
+
+    let mut writer = ChunkedWriter::new(&mut *stream);
+    for chunk in body_iter {
+        writer.write_all(&chunk)?;
+    }
+    writer.finish()
+}
+
+impl Default for ResponseBody {
+    fn default() -> Self {
+        ResponseBody::Bytes(Vec::new())
+    }
+}
+
+/// 一次 HTTP 响应
+#[derive(Debug, Clone)]
+pub struct Response {
+    /// 状态行中的协议版本，默认为 `HTTP/1.1`
+    pub version: &'static str,
+    pub status: u16,
+    pub reason: &'static str,
+    pub headers: Vec<(String, String)>,
+    pub body: ResponseBody,
+}
+
+impl Response {
+    pub fn new(status: u16) -> Response {
+        Response {
+            version: "HTTP/1.1",
+            status,
+            reason: reason_phrase(status),
+            headers: Vec::new(),
+            body: ResponseBody::default(),
+        }
+    }
+
+    pub fn ok() -> Response {
+        Response::new(200)
+    }
+
+    /// 修改状态码，状态描述同时恢复为该状态码的默认值
+    pub fn status(mut self, status: u16) -> Response {
+        self.status = status;
+        self.reason = reason_phrase(status);
+        self
+    }
+
+    /// 覆盖默认的状态描述
+    pub fn reason(mut self, reason: &'static str) -> Response {
+        self.reason = reason;
+        self
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Response {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn content_type(self, content_type: &str) -> Response {
+        self.header("Content-Type", content_type)
+    }
+
+    pub fn body_text(mut self, body: impl Into<String>) -> Response {
+        self.body = ResponseBody::Text(body.into());
+        self
+    }
+
+    pub fn body_bytes(mut self, body: impl Into<Vec<u8>>) -> Response {
+        self.body = ResponseBody::Bytes(body.into());
+        self
+    }
+
+    /// 长度未知、边生成边写出的响应，`producer` 写入的数据按 chunked 编码发送
+    pub fn stream<F>(status: u16, content_type: &str, producer: F) -> Response
+    where
+        F: Fn(&mut dyn Write) -> io::Result<()> + Send + Sync + 'static,
+    {
+        Response::new(status)
+            .content_type(content_type)
+            .body_stream(producer)
+    }
+
+    /// 响应体由 `producer` 在写出时生成，按 chunked 编码发送
+    pub fn body_stream<F>(mut self, producer: F) -> Response
+    where
+        F: Fn(&mut dyn Write) -> io::Result<()> + Send + Sync + 'static,
+    {
+        self.body = ResponseBody::Stream(StreamBody(Arc::new(producer)));
+        self
+    }
+
+    /// 响应体为文件中从 `offset` 开始的 `length` 字节，写出时分块读取
+    pub fn body_file(mut self, path: impl Into<PathBuf>, offset: u64, length: u64) -> Response {
+        self.body = ResponseBody::File {
+            path: path.into(),
+            offset,
+            length,
+        };
+        self
+    }
+
+    /// 按 HTTP/1.0 回复：状态行使用 1.0；HTTP/1.0 不支持 chunked 编码，
+    /// 流式响应体不分块直接写出，以关闭连接表示结束
+    pub(crate) fn downgrade_to_http_1_0(&mut self) {
+        self.version = "HTTP/1.0";
+    }
+
+    /// 响应体以关闭连接结束，写出后不能继续使用这个连接
+    pub(crate) fn is_close_delimited(&self) -> bool {
+        self.version == "HTTP/1.0" && matches!(self.body, ResponseBody::Stream(_))
+    }
+
+    /// 客户端的 `Accept-Encoding` 接受 gzip 时压缩文本类型的响应体，并设置 `Content-Encoding`
+    ///
+    /// 小于 1 KB 或超过 1 MB 的响应体、二进制类型、204、206、304 和已经设置过
+    /// `Content-Encoding` 的响应保持原样；压缩后 `ETag` 加上 `-gzip` 后缀
+    pub fn compress_if_accepted(&mut self, accept_encoding: &str) -> &mut Self {
+        if matches!(self.status, 204 | 206 | 304)
+            || matches!(self.body, ResponseBody::Stream(_))
+            || self.has_header("Content-Encoding")
+        {
+            return self;
+        }
+        let length = self.body.len();
+        if length < MIN_COMPRESSED_SIZE
+            || matches!(self.body, ResponseBody::File { .. } if length > MAX_COMPRESSED_FILE_SIZE)
+        {
+            return self;
+        }
+        let is_text = self
+            .headers
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case("Content-Type"))
+            .is_some_and(|(_, content_type)| is_text_type(content_type));
+        if !is_text {
+            return self;
+        }
+
+        self.headers
+            .push((String::from("Vary"), String::from("Accept-Encoding")));
+        if !accepts_encoding(accept_encoding, "gzip") {
+            return self;
+        }
+
+        let Ok(body) = self.body.to_bytes() else {
+            return self;
+        };
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        if encoder.write_all(&body).is_ok() {
+            if let Ok(compressed) = encoder.finish() {
+                self.body = ResponseBody::Bytes(compressed);
+                self.headers
+                    .push((String::from("Content-Encoding"), String::from("gzip")));
+                // 压缩后是另一个表示，不能与未压缩的响应共用强 ETag
+                if let Some((_, etag)) = self
+                    .headers
+                    .iter_mut()
+                    .find(|(name, _)| name.eq_ignore_ascii_case("ETag"))
+                {
+                    *etag = cache::encoded_etag(etag, "gzip");
+                }
+            }
+        }
+        self
+    }
+
+    fn has_header(&self, name: &str) -> bool {
+        self.headers
+            .iter()
+            .any(|(existing, _)| existing.eq_ignore_ascii_case(name))
+    }
+
+    /// 写出状态行、响应头、`Content-Length` 和响应体
+    pub fn write_to(&self, stream: &mut impl Write) -> io::Result<()> {
+        self.write(stream, true)
+    }
+
+    /// 只写出状态行和响应头，用于 HEAD 请求，`Content-Length` 仍为响应体的长度
+    pub fn write_head_to(&self, stream: &mut impl Write) -> io::Result<()> {
+        self.write(stream, false)
+    }
+
+    /// `include_body` 为假时只写出状态行和响应头
+    pub fn write(&self, stream: &mut impl Write, include_body: bool) -> io::Result<()> {
+        self.write_head(stream)?;
+        if include_body {
+            match &self.body {
+                ResponseBody::Stream(StreamBody(producer)) if self.is_close_delimited() => {
+                    producer(stream)?
+                }
+                body => body.write_to(stream)?,
+            }
+        }
+        stream.flush()
+    }
+
+    fn write_head(&self, stream: &mut impl Write) -> io::Result<()> {
+        let mut head = format!("{} {} {}\r\n", self.version, self.status, self.reason);
+        if self.status >= 200 && !self.has_header("Date") {
+            head.push_str(&format!("Date: {}\r\n", datetime::now_as_http_date()));
+        }
+        for (name, value) in &self.headers {
+            if name.eq_ignore_ascii_case("Content-Type") {
+                head.push_str(&format!("{}: {}\r\n", name, with_charset(value)));
+            } else {
+                head.push_str(&format!("{}: {}\r\n", name, value));
+            }
+        }
+        // 1xx 和 204 响应不能带 Content-Length，304 没有响应体；
+        // 以关闭连接结束的响应体两者都不带
+        if self.status >= 200
+            && self.status != 204
+            && self.status != 304
+            && !self.is_close_delimited()
+        {
+            match self.body {
+                ResponseBody::Stream(_) => head.push_str("Transfer-Encoding: chunked\r\n"),
+                _ => head.push_str(&format!("Content-Length: {}\r\n", self.body.len())),
+            }
+        }
+        head.push_str("\r\n");
+
+        stream.write_all(head.as_bytes())
+    }
+}
+
+/// 文本类型没有声明字符集时补上 `; charset=utf-8`，二进制类型保持原样
+pub(crate) fn with_charset(content_type: &str) -> Cow<'_, str> {
+    if is_text_type(content_type) && !content_type.to_ascii_lowercase().contains("charset=") {
+        Cow::Owned(format!("{}; charset=utf-8", content_type))
+    } else {
+        Cow::Borrowed(content_type)
+    }
+}
+
+/// 是否为文本类型，图片、压缩包等二进制类型返回 `false`
+fn is_text_type(content_type: &str) -> bool {
+    let media_type = content_type.split(';').next().unwrap_or("").trim();
+    media_type.starts_with("text/")
+        || [
+            "application/json",
+            "application/javascript",
+            "application/xml",
+            "image/svg+xml",
+        ]
+        .contains(&media_type)
+}
+
+/// `Accept-Encoding` 中指定编码的权重是否大于 0；没有列出该编码时按 `*` 的权重，
+/// 两者都没有时不接受
+pub(crate) fn accepts_encoding(accept_encoding: &str, encoding: &str) -> bool {
+    let mut wildcard = None;
+    for coding in accept_encoding.split(',') {
+        let mut params = coding.split(';');
+        let name = params.next().unwrap_or("").trim();
+        let quality = params
+            .filter_map(|param| param.trim().strip_prefix("q="))
+            .next()
+            .and_then(|q| q.trim().parse::<f32>().ok())
+            .unwrap_or(1.0);
+
+        if name.eq_ignore_ascii_case(encoding) {
+            return quality > 0.0;
+        }
+        if name == "*" {
+            wildcard = Some(quality);
+        }
+    }
+    wildcard.is_some_and(|quality| quality > 0.0)
+}
+
+fn reason_phrase(status: u16) -> &'static str {
+    match status {
+        101 => "Switching Protocols",
+        200 => "OK",
+        201 => "Created",
+        204 => "No Content",
+        206 => "Partial Content",
+        301 => "Moved Permanently",
+        304 => "Not Modified",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        408 => "Request Timeout",
+        409 => "Conflict",
+        413 => "Payload Too Large",
+        415 => "Unsupported Media Type",
+        416 => "Range Not Satisfiable",
+        417 => "Expectation Failed",
+        429 => "Too Many Requests",
+        431 => "Request Header Fields Too Large",
+        500 => "Internal Server Error",
+        501 => "Not Implemented",
+        502 => "Bad Gateway",
+        503 => "Service Unavailable",
+        505 => "HTTP Version Not Supported",
+        _ => "",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn explicit_coding_wins_over_wildcard() {
+        assert!(!accepts_encoding("gzip;q=0, *", "gzip"));
+        assert!(!accepts_encoding("*, gzip;q=0", "gzip"));
+        assert!(accepts_encoding("gzip;q=0.5, *;q=0", "gzip"));
+    }
+
+    #[test]
+    fn wildcard_applies_to_unlisted_codings() {
+        assert!(accepts_encoding("br, *", "gzip"));
+        assert!(!accepts_encoding("br, *;q=0", "gzip"));
+        assert!(!accepts_encoding("br", "gzip"));
+        assert!(!accepts_encoding("", "gzip"));
+    }
+
+    #[test]
+    fn coding_names_are_case_insensitive() {
+        assert!(accepts_encoding("GZIP", "gzip"));
+        assert!(accepts_encoding("deflate, gzip ; q=0.8", "gzip"));
+    }
+
+    fn etag(response: &Response) -> Option<&str> {
+        response
+            .headers
+            .iter()
+            .find(|(name, _)| name == "ETag")
+            .map(|(_, value)| value.as_str())
+    }
+
+    #[test]
+    fn compressed_body_gets_its_own_etag() {
+        let text = "x".repeat(4096);
+        let mut response = Response::ok()
+            .content_type("text/plain")
+            .header("ETag", "\"abc\"")
+            .body_text(text.clone());
+
+        response.compress_if_accepted("gzip");
+
+        assert!(response.has_header("Content-Encoding"));
+        assert_eq!(etag(&response), Some("\"abc-gzip\""));
+
+        let mut identity = Response::ok()
+            .content_type("text/plain")
+            .header("ETag", "\"abc\"")
+            .body_text(text);
+        identity.compress_if_accepted("br");
+        assert_eq!(etag(&identity), Some("\"abc\""));
+    }
+
+    fn streaming() -> Response {
+        Response::stream(200, "text/event-stream", |stream| {
+            stream.write_all(b"one")?;
+            stream.write_all(b"two")
+        })
+    }
+
+    #[test]
+    fn stream_is_chunked_on_http_1_1() {
+        let mut out = Vec::new();
+        streaming().write(&mut out, true).unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        assert!(out.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(out.contains("Transfer-Encoding: chunked\r\n"));
+        assert!(out.ends_with("\r\n\r\n3\r\none\r\n3\r\ntwo\r\n0\r\n\r\n"));
+    }
+
+    #[test]
+    fn stream_response_writes_each_item_as_a_chunk() {
+        let mut out = Vec::new();
+        let chunks = vec![b"hello".to_vec(), Vec::new(), b"world!".to_vec()];
+        stream_response(&mut out, 200, &[("ETag", "\"x\"")], chunks.into_iter()).unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        assert!(out.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(out.contains("ETag: \"x\"\r\n"));
+        assert!(out.contains("Transfer-Encoding: chunked\r\n"));
+        assert!(!out.contains("Content-Length"));
+        assert!(out.ends_with("\r\n\r\n5\r\nhello\r\n6\r\nworld!\r\n0\r\n\r\n"));
+    }
+
+    #[test]
+    fn stream_is_close_delimited_on_http_1_0() {
+        let mut response = streaming();
+        response.downgrade_to_http_1_0();
+        assert!(response.is_close_delimited());
+
+        let mut out = Vec::new();
+        response.write(&mut out, true).unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        assert!(out.starts_with("HTTP/1.0 200 OK\r\n"));
+        assert!(!out.contains("Transfer-Encoding"));
+        assert!(!out.contains("Content-Length"));
+        assert!(out.ends_with("\r\n\r\nonetwo"));
+    }
+
+    #[test]
+    fn fixed_bodies_keep_content_length_on_http_1_0() {
+        let mut response = Response::ok().body_text("hello");
+        response.downgrade_to_http_1_0();
+        assert!(!response.is_close_delimited());
+
+        let mut out = Vec::new();
+        response.write(&mut out, true).unwrap();
+        assert!(String::from_utf8(out)
+            .unwrap()
+            .contains("Content-Length: 5\r\n"));
+    }
+}
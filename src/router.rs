@@ -0,0 +1,366 @@
+use crate::{Request, Response, ServerError};
+use std::collections::HashMap;
+
+/// 从路径中捕获的 `:name` 参数
+pub type PathParams = HashMap<String, String>;
+
+pub type Handler =
+    Box<dyn Fn(&Request, &PathParams) -> Result<Response, ServerError> + Send + Sync>;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Static(String),
+    Param(String),
+    Wildcard,
+}
+
+/// 路由匹配模式
+///
+/// 按 `/` 分段：普通段精确匹配，`:name` 段捕获为参数，末尾的 `*` 匹配剩余的全部路径
+#[derive(Debug, Clone)]
+pub struct Pattern {
+    segments: Vec<Segment>,
+}
+
+impl Pattern {
+    pub fn new(pattern: &str) -> Pattern {
+        let segments = split_segments(pattern)
+            .map(|segment| match segment {
+                "*" => Segment::Wildcard,
+                _ => match segment.strip_prefix(':') {
+                    Some(name) => Segment::Param(name.to_string()),
+                    None => Segment::Static(segment.to_string()),
+                },
+            })
+            .collect();
+
+        Pattern { segments }
+    }
+
+    /// 匹配成功时返回捕获到的参数
+    pub fn matches(&self, path: &str) -> Option<PathParams> {
+        let mut params = PathParams::new();
+        let mut parts = split_segments(path);
+
+        for segment in &self.segments {
+            match segment {
+                Segment::Wildcard => return Some(params),
+                Segment::Static(expected) => {
+                    if parts.next()? != expected {
+                        return None;
+                    }
+                }
+                Segment::Param(name) => {
+                    params.insert(name.clone(), parts.next()?.to_string());
+                }
+            }
+        }
+
+        match parts.next() {
+            Some(_) => None,
+            None => Some(params),
+        }
+    }
+
+    /// 匹配优先级，静态段优先于参数段，参数段优先于通配符
+    fn specificity(&self) -> Vec<u8> {
+        self.segments
+            .iter()
+            .map(|segment| match segment {
+                Segment::Static(_) => 2,
+                Segment::Param(_) => 1,
+                Segment::Wildcard => 0,
+            })
+            .collect()
+    }
+}
+
+fn split_segments(path: &str) -> std::str::Split<'_, char> {
+    path.strip_prefix('/').unwrap_or(path).split('/')
+}
+
+pub struct Route {
+    method: String,
+    /// 注册时的原始模式，用作运行指标中的路由名
+    source: String,
+    pattern: Pattern,
+    handler: Handler,
+}
+
+/// 路由表
+///
+/// 静态路由优先于带参数的路由，优先级相同时按注册顺序匹配
+pub struct Router {
+    routes: Vec<Route>,
+    fallback: Handler,
+    method_not_allowed: Handler,
+}
+
+impl Router {
+    pub fn new() -> Router {
+        Router {
+            routes: Vec::new(),
+            fallback: Box::new(|_, _| {
+                Ok(Response::new(404)
+                    .content_type("text/plain")
+                    .body_text("404 Not Found"))
+            }),
+            method_not_allowed: Box::new(|_, _| {
+                Ok(Response::new(405)
+                    .content_type("text/plain")
+                    .body_text("405 Method Not Allowed"))
+            }),
+        }
+    }
+
+    pub fn route<F>(&mut self, method: &str, pattern: &str, handler: F) -> &mut Self
+    where
+        F: Fn(&Request, &PathParams) -> Result<Response, ServerError> + Send + Sync + 'static,
+    {
+        self.routes.push(Route {
+            method: method.to_string(),
+            source: pattern.to_string(),
+            pattern: Pattern::new(pattern),
+            handler: Box::new(handler),
+        });
+        self
+    }
+
+    pub fn get<F>(&mut self, pattern: &str, handler: F) -> &mut Self
+    where
+        F: Fn(&Request, &PathParams) -> Result<Response, ServerError> + Send + Sync + 'static,
+    {
+        self.route("GET", pattern, handler)
+    }
+
+    pub fn post<F>(&mut self, pattern: &str, handler: F) -> &mut Self
+    where
+        F: Fn(&Request, &PathParams) -> Result<Response, ServerError> + Send + Sync + 'static,
+    {
+        self.route("POST", pattern, handler)
+    }
+
+    pub fn put<F>(&mut self, pattern: &str, handler: F) -> &mut Self
+    where
+        F: Fn(&Request, &PathParams) -> Result<Response, ServerError> + Send + Sync + 'static,
+    {
+        self.route("PUT", pattern, handler)
+    }
+
+    pub fn delete<F>(&mut self, pattern: &str, handler: F) -> &mut Self
+    where
+        F: Fn(&Request, &PathParams) -> Result<Response, ServerError> + Send + Sync + 'static,
+    {
+        self.route("DELETE", pattern, handler)
+    }
+
+    pub fn patch<F>(&mut self, pattern: &str, handler: F) -> &mut Self
+    where
+        F: Fn(&Request, &PathParams) -> Result<Response, ServerError> + Send + Sync + 'static,
+    {
+        self.route("PATCH", pattern, handler)
+    }
+
+    /// 没有任何路由匹配时使用的处理函数
+    pub fn fallback<F>(&mut self, handler: F) -> &mut Self
+    where
+        F: Fn(&Request, &PathParams) -> Result<Response, ServerError> + Send + Sync + 'static,
+    {
+        self.fallback = Box::new(handler);
+        self
+    }
+
+    /// 路径存在但请求方法不匹配时使用的处理函数，响应会自动带上 `Allow` 头
+    pub fn method_not_allowed<F>(&mut self, handler: F) -> &mut Self
+    where
+        F: Fn(&Request, &PathParams) -> Result<Response, ServerError> + Send + Sync + 'static,
+    {
+        self.method_not_allowed = Box::new(handler);
+        self
+    }
+
+    /// 能够匹配该路径的全部请求方法，按注册顺序排列
+    ///
+    /// 只统计优先级最高的那些路由，避免通配路由把其他路径都算作自己的；
+    /// 注册了 GET 的路径同时支持 HEAD
+    pub fn allowed_methods(&self, path: &str) -> Vec<String> {
+        let matched = self.matching_routes(path);
+        let mut methods: Vec<String> = Vec::new();
+
+        for (route, _) in matched {
+            if !methods.contains(&route.method) {
+                methods.push(route.method.clone());
+                if route.method == "GET" && !methods.iter().any(|method| method == "HEAD") {
+                    methods.push(String::from("HEAD"));
+                }
+            }
+        }
+
+        methods
+    }
+
+    /// 匹配该路径且优先级最高的路由及其参数，按注册顺序排列
+    fn matching_routes(&self, path: &str) -> Vec<(&Route, PathParams)> {
+        let mut best: Vec<(&Route, PathParams)> = Vec::new();
+        let mut best_specificity = Vec::new();
+
+        for route in &self.routes {
+            if let Some(params) = route.pattern.matches(path) {
+                let specificity = route.pattern.specificity();
+                if best.is_empty() || specificity > best_specificity {
+                    best.clear();
+                    best_specificity = specificity;
+                } else if specificity < best_specificity {
+                    continue;
+                }
+                best.push((route, params));
+            }
+        }
+
+        best
+    }
+
+    /// 是否为该请求方法注册过路由
+    pub fn supports_method(&self, method: &str) -> bool {
+        self.routes.iter().any(|route| route.method == method)
+    }
+
+    /// 处理该请求的路由注册时的模式，交给默认处理函数时返回 `None`
+    pub fn matched_pattern(&self, method: &str, path: &str) -> Option<&str> {
+        self.matching_routes(path)
+            .into_iter()
+            .find(|(route, _)| route.method == method)
+            .map(|(route, _)| route.source.as_str())
+    }
+
+    pub fn handle(&self, request: &Request) -> Result<Response, ServerError> {
+        let path = request.path.as_str();
+
+        let matched = self.matching_routes(path);
+        if matched.is_empty() {
+            return (self.fallback)(request, &PathParams::new());
+        }
+
+        if let Some((route, params)) = matched
+            .iter()
+            .find(|(route, _)| route.method == request.method)
+        {
+            return (route.handler)(request, params);
+        }
+
+        let allowed = self.allowed_methods(path);
+        let response = (self.method_not_allowed)(request, &PathParams::new())?;
+        Ok(response.header("Allow", &allowed.join(", ")))
+    }
+}
+
+impl Default for Router {
+    fn default() -> Self {
+        Router::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get(path: &str) -> Request {
+        Request::from_bytes(format!("GET {} HTTP/1.1\r\n\r\n", path).as_bytes()).unwrap()
+    }
+
+    /// 返回匹配的路由名和捕获的参数，参数按名称排序
+    fn named(
+        name: &'static str,
+    ) -> impl Fn(&Request, &PathParams) -> Result<Response, ServerError> {
+        move |_, params| {
+            let mut params: Vec<_> = params.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+            params.sort();
+            Ok(Response::ok().body_text(format!("{} {}", name, params.join(","))))
+        }
+    }
+
+    fn body(router: &Router, path: &str) -> String {
+        let response = router.handle(&get(path)).unwrap();
+        String::from_utf8(response.body.as_bytes().unwrap().to_vec()).unwrap()
+    }
+
+    #[test]
+    fn captures_nested_parameters() {
+        let mut router = Router::new();
+        router.get("/api/users/:user/items/:item/details", named("details"));
+
+        assert_eq!(
+            body(&router, "/api/users/7/items/42/details"),
+            "details item=42,user=7"
+        );
+        assert_eq!(
+            router.handle(&get("/api/users/7/items/42")).unwrap().status,
+            404
+        );
+        assert_eq!(
+            router
+                .handle(&get("/api/users/7/items/42/details/more"))
+                .unwrap()
+                .status,
+            404
+        );
+    }
+
+    #[test]
+    fn static_segments_win_over_parameters() {
+        // 注册顺序与优先级无关
+        let mut router = Router::new();
+        router
+            .get("/api/items/:id", named("param"))
+            .get("/api/items/new", named("static"))
+            .get("/api/*", named("wildcard"));
+
+        assert_eq!(body(&router, "/api/items/new"), "static ");
+        assert_eq!(body(&router, "/api/items/5"), "param id=5");
+        assert_eq!(body(&router, "/api/other/path"), "wildcard ");
+    }
+
+    #[test]
+    fn parameters_take_the_segment_as_given() {
+        // 路径在路由之前解码，路由本身不再处理百分号编码
+        let mut router = Router::new();
+        router.get("/files/:name", named("file"));
+
+        assert_eq!(body(&router, "/files/caf%C3%A9"), "file name=caf%C3%A9");
+        assert_eq!(body(&router, "/files/café"), "file name=café");
+    }
+
+    #[test]
+    fn query_string_is_ignored_when_matching() {
+        let mut router = Router::new();
+        router.get("/search", named("search"));
+
+        assert_eq!(body(&router, "/search?q=1"), "search ");
+    }
+
+    #[test]
+    fn wrong_method_gets_405_with_allow() {
+        let mut router = Router::new();
+        router
+            .get("/things/:id", named("get"))
+            .delete("/things/:id", named("delete"));
+        let request = Request::from_bytes(b"POST /things/1 HTTP/1.1\r\n\r\n").unwrap();
+
+        let response = router.handle(&request).unwrap();
+
+        assert_eq!(response.status, 405);
+        assert_eq!(
+            response
+                .headers
+                .iter()
+                .find(|(name, _)| name == "Allow")
+                .map(|(_, v)| v.as_str()),
+            Some("GET, HEAD, DELETE")
+        );
+        assert_eq!(
+            router.matched_pattern("DELETE", "/things/1"),
+            Some("/things/:id")
+        );
+        assert_eq!(router.matched_pattern("POST", "/things/1"), None);
+    }
+}
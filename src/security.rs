@@ -0,0 +1,176 @@
+use crate::url;
+use std::{
+    error::Error,
+    fmt, fs,
+    path::{Component, Path, PathBuf},
+};
+
+/// 请求的路径不能映射到根目录下的原因
+#[derive(Debug, Clone, PartialEq)]
+pub enum SecurityError {
+    /// 路径中含有 NUL 或反斜杠
+    InvalidCharacter,
+    /// `..` 越出了根目录
+    Traversal,
+    /// 文件的真实路径（解析符号链接后）不在根目录下
+    SymlinkEscape,
+}
+
+impl fmt::Display for SecurityError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SecurityError::InvalidCharacter => write!(f, "Path contains NUL or backslash"),
+            SecurityError::Traversal => write!(f, "Path escapes the root directory"),
+            SecurityError::SymlinkEscape => {
+                write!(f, "Path resolves outside the root directory")
+            }
+        }
+    }
+}
+
+impl Error for SecurityError {}
+
+/// 把已解码的请求路径映射为 `root` 下的文件路径
+///
+/// 先按路径段处理 `.` 和 `..`，去掉开头的 `/` 后拼接到 `root`；文件存在时再比较真实路径，
+/// 确认没有经由符号链接逃出根目录。文件不存在时返回拼接的路径，由调用方回复 404
+pub fn sanitize_path(root: &Path, requested: &str) -> Result<PathBuf, SecurityError> {
+    if requested.contains('\0') || requested.contains('\\') {
+        return Err(SecurityError::InvalidCharacter);
+    }
+
+    let normalized = url::normalize_path(requested).ok_or(SecurityError::Traversal)?;
+    let relative = Path::new(normalized.trim_start_matches('/'));
+    // 盘符等特殊路径段会让 `join` 丢掉根目录
+    if !relative
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)))
+    {
+        return Err(SecurityError::Traversal);
+    }
+
+    let file = root.join(relative);
+    if let (Ok(root), Ok(real)) = (fs::canonicalize(root), fs::canonicalize(&file)) {
+        if !real.starts_with(root) {
+            return Err(SecurityError::SymlinkEscape);
+        }
+    }
+    Ok(file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// `root` 下有 `index.html`，`root` 旁边有 `secret.txt`
+    fn tree() -> (TempDir, PathBuf) {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path().join("root");
+        fs::create_dir(&root).unwrap();
+        fs::write(root.join("index.html"), "index").unwrap();
+        fs::write(dir.path().join("secret.txt"), "secret").unwrap();
+        (dir, root)
+    }
+
+    #[test]
+    fn plain_paths_stay_under_the_root() {
+        let (_dir, root) = tree();
+        assert_eq!(
+            sanitize_path(&root, "/index.html"),
+            Ok(root.join("index.html"))
+        );
+        assert_eq!(
+            sanitize_path(&root, "/a/./b/../missing.txt"),
+            Ok(root.join("a/missing.txt"))
+        );
+        assert_eq!(sanitize_path(&root, "/"), Ok(root.clone()));
+    }
+
+    #[test]
+    fn dot_dot_cannot_leave_the_root() {
+        let (_dir, root) = tree();
+        for path in [
+            "/..",
+            "/../secret.txt",
+            "/a/../../secret.txt",
+            "../secret.txt",
+        ] {
+            assert_eq!(
+                sanitize_path(&root, path),
+                Err(SecurityError::Traversal),
+                "{}",
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn encoded_dot_dot_is_caught_after_decoding() {
+        let (_dir, root) = tree();
+        for path in [
+            "/%2e%2e/secret.txt",
+            "/%2E%2E/secret.txt",
+            "/.%2e/secret.txt",
+        ] {
+            let decoded = url::path_decode(path).unwrap();
+            assert_eq!(
+                sanitize_path(&root, &decoded),
+                Err(SecurityError::Traversal),
+                "{}",
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn absolute_paths_are_taken_relative_to_the_root() {
+        let (dir, root) = tree();
+        let secret = dir.path().join("secret.txt");
+        let requested = secret.to_str().unwrap();
+        assert_eq!(
+            sanitize_path(&root, requested),
+            Ok(root.join(requested.trim_start_matches('/')))
+        );
+        assert_eq!(
+            sanitize_path(&root, "//etc/passwd"),
+            Ok(root.join("etc/passwd"))
+        );
+    }
+
+    #[test]
+    fn nul_and_backslash_are_rejected() {
+        let (_dir, root) = tree();
+        for path in ["/index.html\0.txt", "/\0", "/..\\secret.txt"] {
+            assert_eq!(
+                sanitize_path(&root, path),
+                Err(SecurityError::InvalidCharacter),
+                "{:?}",
+                path
+            );
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_out_of_the_root_are_rejected() {
+        use std::os::unix::fs::symlink;
+
+        let (dir, root) = tree();
+        symlink(dir.path().join("secret.txt"), root.join("direct")).unwrap();
+        // 先指向根目录内的链接，再由它指向根目录外
+        symlink(root.join("direct"), root.join("chain")).unwrap();
+        symlink(dir.path(), root.join("parent")).unwrap();
+        symlink(root.join("index.html"), root.join("inside")).unwrap();
+
+        for path in ["/direct", "/chain", "/parent/secret.txt"] {
+            assert_eq!(
+                sanitize_path(&root, path),
+                Err(SecurityError::SymlinkEscape),
+                "{}",
+                path
+            );
+        }
+        assert_eq!(sanitize_path(&root, "/inside"), Ok(root.join("inside")));
+    }
+}
@@ -0,0 +1,70 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc, Condvar, Mutex,
+};
+
+/// 计数信号量，限制同时持有许可的数量，例如同时处理的连接数
+#[derive(Debug)]
+pub struct Semaphore {
+    available: AtomicUsize,
+    /// 只用来配合 `released` 等待，许可数量本身由 `available` 记录
+    lock: Mutex<()>,
+    released: Condvar,
+}
+
+impl Semaphore {
+    pub fn new(permits: usize) -> Semaphore {
+        Semaphore {
+            available: AtomicUsize::new(permits),
+            lock: Mutex::new(()),
+            released: Condvar::new(),
+        }
+    }
+
+    /// 立即尝试获取一个许可，没有剩余许可时返回 `None`
+    pub fn try_acquire(self: &Arc<Self>) -> Option<SemaphoreGuard> {
+        self.available
+            .fetch_update(Ordering::Acquire, Ordering::Relaxed, |available| {
+                available.checked_sub(1)
+            })
+            .ok()?;
+        Some(SemaphoreGuard {
+            semaphore: Arc::clone(self),
+        })
+    }
+
+    /// 获取一个许可，没有剩余许可时阻塞到有许可被释放
+    pub fn acquire(self: &Arc<Self>) -> SemaphoreGuard {
+        let mut lock = self.lock.lock().unwrap();
+        loop {
+            if let Some(guard) = self.try_acquire() {
+                return guard;
+            }
+            lock = self.released.wait(lock).unwrap();
+        }
+    }
+
+    /// 当前剩余的许可数
+    pub fn available(&self) -> usize {
+        self.available.load(Ordering::Relaxed)
+    }
+
+    fn release(&self) {
+        self.available.fetch_add(1, Ordering::Release);
+        // 持有锁再通知，避免等待者检查完许可、还没开始等待时错过通知
+        let _lock = self.lock.lock().unwrap();
+        self.released.notify_one();
+    }
+}
+
+/// 持有的许可，被丢弃时归还
+#[derive(Debug)]
+pub struct SemaphoreGuard {
+    semaphore: Arc<Semaphore>,
+}
+
+impl Drop for SemaphoreGuard {
+    fn drop(&mut self) {
+        self.semaphore.release();
+    }
+}
@@ -0,0 +1,86 @@
+use crate::Response;
+use std::{
+    io::{self, Write},
+    sync::{mpsc, Arc, Mutex},
+    time::Duration,
+};
+
+/// 没有事件时发送注释行的间隔，用来及时发现已断开的连接，释放工作线程
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(15);
+
+/// 向所有订阅的 Server-Sent Events 连接广播事件，复制后共享同一组订阅者
+#[derive(Debug, Clone, Default)]
+pub struct SseBroadcaster {
+    senders: Arc<Mutex<Vec<mpsc::Sender<String>>>>,
+}
+
+impl SseBroadcaster {
+    pub fn new() -> SseBroadcaster {
+        SseBroadcaster::default()
+    }
+
+    /// 加入一个订阅者，返回接收事件的一端
+    pub fn subscribe(&self) -> mpsc::Receiver<String> {
+        let (sender, receiver) = mpsc::channel();
+        self.senders.lock().unwrap().push(sender);
+        receiver
+    }
+
+    /// 把事件发给所有订阅者，可以在任意线程调用；连接已断开的订阅者同时被移除
+    pub fn send(&self, event: &str) {
+        self.senders
+            .lock()
+            .unwrap()
+            .retain(|sender| sender.send(event.to_string()).is_ok());
+    }
+
+    /// 断开所有订阅者，它们的响应在写完已收到的事件后正常结束，例如在服务器退出时
+    pub fn close(&self) {
+        self.senders.lock().unwrap().clear();
+    }
+
+    /// 当前订阅者数量，包括已断开但还没有在发送时被移除的连接
+    pub fn subscribers(&self) -> usize {
+        self.senders.lock().unwrap().len()
+    }
+
+    /// `text/event-stream` 响应：订阅后持续把收到的事件写给客户端，直到写出失败（客户端断开）；
+    /// 没有事件时定期写出心跳
+    pub fn response(&self) -> Response {
+        let broadcaster = self.clone();
+        Response::stream(200, "text/event-stream", move |stream| {
+            let events = broadcaster.subscribe();
+            // 先写出一个注释行，客户端立即收到响应头和第一个块
+            stream.write_all(b": connected\n\n")?;
+            stream.flush()?;
+            loop {
+                match events.recv_timeout(HEARTBEAT_INTERVAL) {
+                    Ok(event) => write_event(stream, &event)?,
+                    Err(mpsc::RecvTimeoutError::Timeout) => {
+                        stream.write_all(b": ping\n\n")?;
+                        stream.flush()?;
+                    }
+                    Err(mpsc::RecvTimeoutError::Disconnected) => return Ok(()),
+                }
+            }
+        })
+        .header("Cache-Control", "no-cache")
+    }
+}
+
+/// 按 `data: ...` 格式写出一个事件，多行内容每行一个 `data:` 字段
+fn write_event(stream: &mut dyn Write, event: &str) -> io::Result<()> {
+    let mut message = String::new();
+    for line in event.lines() {
+        message.push_str("data: ");
+        message.push_str(line);
+        message.push('\n');
+    }
+    if message.is_empty() {
+        message.push_str("data: \n");
+    }
+    message.push('\n');
+
+    stream.write_all(message.as_bytes())?;
+    stream.flush()
+}
@@ -0,0 +1,69 @@
+use rustls::{
+    crypto::ring::default_provider,
+    pki_types::{CertificateDer, PrivateKeyDer},
+    ServerConfig, ServerConnection, StreamOwned,
+};
+use std::{error::Error, fmt, fs::File, io, io::BufReader, net::TcpStream, path::Path, sync::Arc};
+
+/// 经过 TLS 加密的连接
+pub type TlsStream = StreamOwned<ServerConnection, TcpStream>;
+
+/// 加载证书或建立 TLS 连接失败的原因
+#[derive(Debug)]
+pub enum TlsError {
+    Io(io::Error),
+    Rustls(rustls::Error),
+    NoCertificates,
+    NoPrivateKey,
+}
+
+impl fmt::Display for TlsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TlsError::Io(err) => write!(f, "{}", err),
+            TlsError::Rustls(err) => write!(f, "TLS error: {}", err),
+            TlsError::NoCertificates => write!(f, "No certificates found in certificate file"),
+            TlsError::NoPrivateKey => write!(f, "No private key found in key file"),
+        }
+    }
+}
+
+impl Error for TlsError {}
+
+impl From<io::Error> for TlsError {
+    fn from(err: io::Error) -> Self {
+        TlsError::Io(err)
+    }
+}
+
+impl From<rustls::Error> for TlsError {
+    fn from(err: rustls::Error) -> Self {
+        TlsError::Rustls(err)
+    }
+}
+
+/// 从 PEM 格式的证书链和私钥文件构建服务端 TLS 配置
+pub fn load_tls_config(cert: &Path, key: &Path) -> Result<Arc<ServerConfig>, TlsError> {
+    let certs = rustls_pemfile::certs(&mut BufReader::new(File::open(cert)?))
+        .collect::<Result<Vec<CertificateDer<'static>>, _>>()?;
+    if certs.is_empty() {
+        return Err(TlsError::NoCertificates);
+    }
+
+    let key: PrivateKeyDer<'static> =
+        rustls_pemfile::private_key(&mut BufReader::new(File::open(key)?))?
+            .ok_or(TlsError::NoPrivateKey)?;
+
+    let config = ServerConfig::builder_with_provider(Arc::new(default_provider()))
+        .with_safe_default_protocol_versions()?
+        .with_no_client_auth()
+        .with_single_cert(certs, key)?;
+
+    Ok(Arc::new(config))
+}
+
+/// 在已接受的 TCP 连接上建立 TLS 会话，握手在第一次读写时完成
+pub fn accept(config: Arc<ServerConfig>, stream: TcpStream) -> Result<TlsStream, TlsError> {
+    let connection = ServerConnection::new(config)?;
+    Ok(StreamOwned::new(connection, stream))
+}
@@ -0,0 +1,206 @@
+use std::{error::Error, fmt};
+
+/// 百分号解码失败的原因
+#[derive(Debug, Clone, PartialEq)]
+pub enum DecodeError {
+    /// `%` 之后不足两个字符
+    IncompleteSequence(usize),
+    /// `%` 之后不是合法的十六进制数
+    InvalidHex(usize),
+    /// 解码后的字节不是合法的 UTF-8
+    InvalidUtf8,
+    /// 路径中编码的 `/`，解码后会改变路径段的边界
+    EncodedSlash,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::IncompleteSequence(pos) => {
+                write!(f, "Incomplete percent-encoding at byte {}", pos)
+            }
+            DecodeError::InvalidHex(pos) => write!(f, "Invalid percent-encoding at byte {}", pos),
+            DecodeError::InvalidUtf8 => write!(f, "Percent-decoded bytes are not valid UTF-8"),
+            DecodeError::EncodedSlash => write!(f, "Encoded `/` in path"),
+        }
+    }
+}
+
+impl Error for DecodeError {}
+
+/// 按查询字符串的规则解码：`%XX` 转为对应字节，`+` 转为空格
+pub fn percent_decode(s: &str) -> Result<String, DecodeError> {
+    decode(s, true)
+}
+
+/// 按路径的规则解码：只处理 `%XX`，`+` 保持原样
+pub fn path_decode(s: &str) -> Result<String, DecodeError> {
+    decode(s, false)
+}
+
+/// 规范化已解码的路径：去掉空段和 `.`，`..` 回到上一级，结果以 `/` 开头且不以 `/` 结尾；
+/// `..` 超出根目录时返回 `None`
+pub fn normalize_path(path: &str) -> Option<String> {
+    let mut segments: Vec<&str> = Vec::new();
+    for segment in path.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop()?;
+            }
+            _ => segments.push(segment),
+        }
+    }
+    Some(format!("/{}", segments.join("/")))
+}
+
+/// 路径规范化后等于 `prefix` 或者位于 `prefix` 目录之下，`prefix` 末尾的 `/` 不影响结果
+///
+/// `//admin`、`/./admin` 和 `/x/../admin` 都按 `/admin` 判断；超出根目录的路径视为在任何前缀之下
+pub fn is_under_prefix(prefix: &str, path: &str) -> bool {
+    let Some(path) = normalize_path(path) else {
+        return true;
+    };
+    match path.strip_prefix(prefix.trim_end_matches('/')) {
+        Some(rest) => rest.is_empty() || rest.starts_with('/'),
+        None => false,
+    }
+}
+
+/// 编码单个路径段，保留 RFC 3986 中的非保留字符，其余字节写成 `%XX`
+pub fn encode_path_segment(s: &str) -> String {
+    let mut encoded = String::with_capacity(s.len());
+    for &byte in s.as_bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+fn decode(s: &str, plus_as_space: bool) -> Result<String, DecodeError> {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => {
+                let hex = bytes
+                    .get(i + 1..i + 3)
+                    .ok_or(DecodeError::IncompleteSequence(i))?;
+                let high = hex_value(hex[0]).ok_or(DecodeError::InvalidHex(i))?;
+                let low = hex_value(hex[1]).ok_or(DecodeError::InvalidHex(i))?;
+                decoded.push(high << 4 | low);
+                i += 3;
+            }
+            b'+' if plus_as_space => {
+                decoded.push(b' ');
+                i += 1;
+            }
+            byte => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+
+    String::from_utf8(decoded).map_err(|_| DecodeError::InvalidUtf8)
+}
+
+fn hex_value(byte: u8) -> Option<u8> {
+    (byte as char).to_digit(16).map(|value| value as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_non_ascii_utf8() {
+        assert_eq!(percent_decode("Jos%C3%A9").unwrap(), "José");
+        assert_eq!(percent_decode("%E4%BD%A0%E5%A5%BD").unwrap(), "你好");
+        assert_eq!(percent_decode("caf%c3%a9").unwrap(), "café");
+    }
+
+    #[test]
+    fn plus_is_a_space_only_in_queries() {
+        assert_eq!(percent_decode("a+b%20c").unwrap(), "a b c");
+        assert_eq!(path_decode("a+b%20c").unwrap(), "a+b c");
+        assert_eq!(percent_decode("1%2B1").unwrap(), "1+1");
+    }
+
+    #[test]
+    fn decodes_only_once() {
+        assert_eq!(percent_decode("%2520").unwrap(), "%20");
+        assert_eq!(path_decode("%252e%252e").unwrap(), "%2e%2e");
+    }
+
+    #[test]
+    fn rejects_incomplete_sequences() {
+        assert_eq!(
+            percent_decode("abc%"),
+            Err(DecodeError::IncompleteSequence(3))
+        );
+        assert_eq!(
+            percent_decode("%4"),
+            Err(DecodeError::IncompleteSequence(0))
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_hex() {
+        assert_eq!(percent_decode("%GZ"), Err(DecodeError::InvalidHex(0)));
+        assert_eq!(path_decode("/a%2G"), Err(DecodeError::InvalidHex(2)));
+    }
+
+    #[test]
+    fn rejects_invalid_utf8() {
+        assert_eq!(percent_decode("%C3"), Err(DecodeError::InvalidUtf8));
+        assert_eq!(percent_decode("%FF%FE"), Err(DecodeError::InvalidUtf8));
+    }
+
+    #[test]
+    fn encodes_path_segments() {
+        assert_eq!(encode_path_segment("my file.txt"), "my%20file.txt");
+        assert_eq!(encode_path_segment("a/b?c"), "a%2Fb%3Fc");
+        assert_eq!(encode_path_segment("é"), "%C3%A9");
+        let segment = "100% ok+fine";
+        assert_eq!(path_decode(&encode_path_segment(segment)).unwrap(), segment);
+    }
+
+    #[test]
+    fn normalizes_dot_and_empty_segments() {
+        assert_eq!(normalize_path("/").as_deref(), Some("/"));
+        assert_eq!(normalize_path("").as_deref(), Some("/"));
+        assert_eq!(
+            normalize_path("//admin//a.txt").as_deref(),
+            Some("/admin/a.txt")
+        );
+        assert_eq!(normalize_path("/./admin/./").as_deref(), Some("/admin"));
+        assert_eq!(normalize_path("/x/../admin").as_deref(), Some("/admin"));
+        assert_eq!(normalize_path("/a/b/../../c").as_deref(), Some("/c"));
+        assert_eq!(normalize_path("/..."), Some(String::from("/...")));
+    }
+
+    #[test]
+    fn prefix_matches_whole_segments_after_normalizing() {
+        assert!(is_under_prefix("/admin", "/admin"));
+        assert!(is_under_prefix("/admin/", "/admin/a.txt"));
+        assert!(is_under_prefix("/admin", "//admin/./a.txt"));
+        assert!(is_under_prefix("/admin", "/x/../admin"));
+        assert!(is_under_prefix("/admin", "/../admin"));
+        assert!(is_under_prefix("/", "/anything"));
+        assert!(!is_under_prefix("/admin", "/administrator"));
+        assert!(!is_under_prefix("/admin", "/admin/../public.txt"));
+    }
+
+    #[test]
+    fn escaping_the_root_is_not_normalized() {
+        assert_eq!(normalize_path("/.."), None);
+        assert_eq!(normalize_path("/a/../../b"), None);
+    }
+}
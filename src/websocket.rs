@@ -0,0 +1,586 @@
+use crate::{request::is_timeout, Request, Response};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use ring::digest::{digest, SHA1_FOR_LEGACY_USE_ONLY};
+use std::{
+    error::Error,
+    fmt,
+    io::{self, BufRead, BufReader, Read, Write},
+    sync::Arc,
+    time::Duration,
+};
+
+/// 计算 `Sec-WebSocket-Accept` 时拼接在客户端密钥之后的固定字符串（RFC 6455）
+const HANDSHAKE_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// 控制帧的负载最多 125 字节
+const MAX_CONTROL_PAYLOAD: usize = 125;
+
+/// 处理一条完整文本消息的函数，返回 `Some` 时把结果作为文本消息发回客户端
+pub type MessageHandler = dyn Fn(&str) -> Option<String> + Send + Sync;
+
+#[derive(Clone)]
+pub struct WebSocketHandler(Arc<MessageHandler>);
+
+impl WebSocketHandler {
+    pub fn new<F>(handler: F) -> WebSocketHandler
+    where
+        F: Fn(&str) -> Option<String> + Send + Sync + 'static,
+    {
+        WebSocketHandler(Arc::new(handler))
+    }
+}
+
+impl fmt::Debug for WebSocketHandler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("WebSocketHandler")
+    }
+}
+
+/// 接受 WebSocket 升级的路径和处理函数，其他路径上的升级请求按普通请求处理
+#[derive(Debug, Clone)]
+pub struct WebSocketRoute {
+    pub path: String,
+    pub handler: WebSocketHandler,
+    /// 这段时间内没有收到帧时发送 Ping，再过同样的时间仍然没有收到帧就关闭连接
+    pub idle_timeout: Duration,
+}
+
+/// 帧类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Opcode {
+    Continuation,
+    Text,
+    Binary,
+    Close,
+    Ping,
+    Pong,
+}
+
+impl Opcode {
+    fn from_u8(opcode: u8) -> Option<Opcode> {
+        match opcode {
+            0x0 => Some(Opcode::Continuation),
+            0x1 => Some(Opcode::Text),
+            0x2 => Some(Opcode::Binary),
+            0x8 => Some(Opcode::Close),
+            0x9 => Some(Opcode::Ping),
+            0xA => Some(Opcode::Pong),
+            _ => None,
+        }
+    }
+
+    fn as_u8(self) -> u8 {
+        match self {
+            Opcode::Continuation => 0x0,
+            Opcode::Text => 0x1,
+            Opcode::Binary => 0x2,
+            Opcode::Close => 0x8,
+            Opcode::Ping => 0x9,
+            Opcode::Pong => 0xA,
+        }
+    }
+
+    fn is_control(self) -> bool {
+        matches!(self, Opcode::Close | Opcode::Ping | Opcode::Pong)
+    }
+}
+
+/// 一个已去掉掩码的帧
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub fin: bool,
+    pub opcode: Opcode,
+    pub payload: Vec<u8>,
+}
+
+/// 关闭帧的负载：2 字节状态码和可选的 UTF-8 原因
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CloseFrame {
+    pub code: u16,
+    pub reason: String,
+}
+
+impl CloseFrame {
+    /// 正常关闭
+    pub const NORMAL: u16 = 1000;
+    pub const GOING_AWAY: u16 = 1001;
+    pub const PROTOCOL_ERROR: u16 = 1002;
+    pub const UNSUPPORTED_DATA: u16 = 1003;
+    pub const INVALID_PAYLOAD: u16 = 1007;
+    pub const MESSAGE_TOO_BIG: u16 = 1009;
+
+    pub fn new(code: u16, reason: &str) -> CloseFrame {
+        CloseFrame {
+            code,
+            reason: reason.to_string(),
+        }
+    }
+
+    /// 解析客户端发来的关闭帧负载，空负载表示没有状态码
+    fn parse(payload: &[u8]) -> Result<Option<CloseFrame>, WebSocketError> {
+        match payload {
+            [] => Ok(None),
+            [_] => Err(WebSocketError::Protocol("close frame payload is one byte")),
+            [high, low, reason @ ..] => {
+                let reason =
+                    String::from_utf8(reason.to_vec()).map_err(|_| WebSocketError::InvalidUtf8)?;
+                Ok(Some(CloseFrame {
+                    code: u16::from_be_bytes([*high, *low]),
+                    reason,
+                }))
+            }
+        }
+    }
+
+    fn payload(&self) -> Vec<u8> {
+        let mut payload = self.code.to_be_bytes().to_vec();
+        // 原因被截断到控制帧允许的长度内，不切开 UTF-8 字符
+        let mut end = self.reason.len().min(MAX_CONTROL_PAYLOAD - 2);
+        while !self.reason.is_char_boundary(end) {
+            end -= 1;
+        }
+        payload.extend_from_slice(&self.reason.as_bytes()[..end]);
+        payload
+    }
+}
+
+/// 读取帧失败的原因，除 `Io` 外都会以对应状态码的关闭帧结束连接
+#[derive(Debug)]
+pub enum WebSocketError {
+    Io(io::Error),
+    Protocol(&'static str),
+    /// 消息超过了请求体大小上限
+    TooLarge,
+    InvalidUtf8,
+    /// 不支持二进制消息
+    Unsupported,
+    /// 发送 Ping 后仍然没有收到客户端的帧
+    IdleTimeout,
+}
+
+impl WebSocketError {
+    fn close_code(&self) -> u16 {
+        match self {
+            WebSocketError::Io(_) | WebSocketError::Protocol(_) => CloseFrame::PROTOCOL_ERROR,
+            WebSocketError::TooLarge => CloseFrame::MESSAGE_TOO_BIG,
+            WebSocketError::InvalidUtf8 => CloseFrame::INVALID_PAYLOAD,
+            WebSocketError::Unsupported => CloseFrame::UNSUPPORTED_DATA,
+            WebSocketError::IdleTimeout => CloseFrame::GOING_AWAY,
+        }
+    }
+}
+
+impl fmt::Display for WebSocketError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WebSocketError::Io(err) => write!(f, "{}", err),
+            WebSocketError::Protocol(message) => write!(f, "Protocol error: {}", message),
+            WebSocketError::TooLarge => write!(f, "Message too big"),
+            WebSocketError::InvalidUtf8 => write!(f, "Text message is not valid UTF-8"),
+            WebSocketError::Unsupported => write!(f, "Binary messages are not supported"),
+            WebSocketError::IdleTimeout => write!(f, "Idle timeout"),
+        }
+    }
+}
+
+impl Error for WebSocketError {}
+
+impl From<io::Error> for WebSocketError {
+    fn from(err: io::Error) -> Self {
+        WebSocketError::Io(err)
+    }
+}
+
+/// 请求带有 `Connection: Upgrade` 和 `Upgrade: websocket`
+pub fn is_upgrade_request(request: &Request) -> bool {
+    let has_token = |name: &str, token: &str| {
+        request.header(name).is_some_and(|value| {
+            value
+                .split(',')
+                .any(|value| value.trim().eq_ignore_ascii_case(token))
+        })
+    };
+    has_token("connection", "upgrade") && has_token("upgrade", "websocket")
+}
+
+/// 由客户端的 `Sec-WebSocket-Key` 计算 `Sec-WebSocket-Accept`
+pub fn accept_key(key: &str) -> String {
+    let hash = digest(
+        &SHA1_FOR_LEGACY_USE_ONLY,
+        format!("{}{}", key.trim(), HANDSHAKE_GUID).as_bytes(),
+    );
+    STANDARD.encode(hash.as_ref())
+}
+
+/// 握手成功时的 `101 Switching Protocols` 响应；不是 HTTP/1.1 的 GET 请求、版本不是 13
+/// 或者密钥不是 16 字节的 base64 时返回 `None`
+pub fn handshake_response(request: &Request) -> Option<Response> {
+    if request.method != "GET" || request.version != "HTTP/1.1" {
+        return None;
+    }
+    if request.header("sec-websocket-version").map(str::trim) != Some("13") {
+        return None;
+    }
+    let key = request.header("sec-websocket-key")?.trim();
+    if STANDARD.decode(key).ok()?.len() != 16 {
+        return None;
+    }
+
+    Some(
+        Response::new(101)
+            .header("Upgrade", "websocket")
+            .header("Connection", "Upgrade")
+            .header("Sec-WebSocket-Accept", &accept_key(key)),
+    )
+}
+
+/// 读取一个客户端帧：解析 FIN、操作码、掩码位和 7 位、16 位或 64 位的负载长度，然后去掉掩码
+pub fn read_frame(reader: &mut impl Read, max_payload: usize) -> Result<Frame, WebSocketError> {
+    let mut header = [0; 2];
+    reader.read_exact(&mut header)?;
+
+    let fin = header[0] & 0x80 != 0;
+    if header[0] & 0x70 != 0 {
+        return Err(WebSocketError::Protocol("reserved bits are set"));
+    }
+    let opcode =
+        Opcode::from_u8(header[0] & 0x0F).ok_or(WebSocketError::Protocol("unknown opcode"))?;
+    // 客户端发送的帧必须带掩码
+    if header[1] & 0x80 == 0 {
+        return Err(WebSocketError::Protocol("client frame is not masked"));
+    }
+
+    let length = match header[1] & 0x7F {
+        126 => {
+            let mut length = [0; 2];
+            reader.read_exact(&mut length)?;
+            u64::from(u16::from_be_bytes(length))
+        }
+        127 => {
+            let mut length = [0; 8];
+            reader.read_exact(&mut length)?;
+            u64::from_be_bytes(length)
+        }
+        length => u64::from(length),
+    };
+    if opcode.is_control() && (!fin || length > MAX_CONTROL_PAYLOAD as u64) {
+        return Err(WebSocketError::Protocol("invalid control frame"));
+    }
+    if length > max_payload as u64 {
+        return Err(WebSocketError::TooLarge);
+    }
+
+    let mut mask = [0; 4];
+    reader.read_exact(&mut mask)?;
+    let mut payload = vec![0; length as usize];
+    reader.read_exact(&mut payload)?;
+    for (i, byte) in payload.iter_mut().enumerate() {
+        *byte ^= mask[i % 4];
+    }
+
+    Ok(Frame {
+        fin,
+        opcode,
+        payload,
+    })
+}
+
+/// 写出一个不分片、不带掩码的服务器帧
+pub fn write_frame(stream: &mut impl Write, opcode: Opcode, payload: &[u8]) -> io::Result<()> {
+    let mut frame = vec![0x80 | opcode.as_u8()];
+    match payload.len() {
+        len @ 0..=125 => frame.push(len as u8),
+        len @ 126..=0xFFFF => {
+            frame.push(126);
+            frame.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(127);
+            frame.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    frame.extend_from_slice(payload);
+    stream.write_all(&frame)?;
+    stream.flush()
+}
+
+/// 握手完成后处理连接上的帧，直到客户端关闭连接或者出错
+///
+/// 文本消息（包括分片的消息）交给 `handler`，Ping 回复 Pong；客户端发来关闭帧时回复关闭帧后返回，
+/// 协议错误时先发送对应状态码的关闭帧。读取超时由调用方在连接上设置，超时后发送 Ping，
+/// 连续两次超时时以 1001 关闭连接
+pub fn serve<S: Read + Write>(
+    reader: &mut BufReader<S>,
+    handler: &WebSocketHandler,
+    max_message_size: usize,
+) -> Result<(), WebSocketError> {
+    match read_messages(reader, handler, max_message_size) {
+        Err(WebSocketError::Io(err)) => Err(WebSocketError::Io(err)),
+        Err(err) => {
+            let close = CloseFrame::new(err.close_code(), &err.to_string());
+            write_frame(reader.get_mut(), Opcode::Close, &close.payload())?;
+            Err(err)
+        }
+        Ok(()) => Ok(()),
+    }
+}
+
+fn read_messages<S: Read + Write>(
+    reader: &mut BufReader<S>,
+    handler: &WebSocketHandler,
+    max_message_size: usize,
+) -> Result<(), WebSocketError> {
+    // 分片消息中已经收到的部分
+    let mut message: Option<Vec<u8>> = None;
+    // 已经发送了 Ping，还没有收到任何帧
+    let mut pinged = false;
+
+    loop {
+        // 只在等待下一帧时处理超时，帧读到一半超时视为连接出错
+        if let Err(err) = reader.fill_buf() {
+            if !is_timeout(&err) {
+                return Err(err.into());
+            }
+            if pinged {
+                return Err(WebSocketError::IdleTimeout);
+            }
+            write_frame(reader.get_mut(), Opcode::Ping, b"")?;
+            pinged = true;
+            continue;
+        }
+        let frame = read_frame(reader, max_message_size)?;
+        pinged = false;
+        let complete = match frame.opcode {
+            Opcode::Text if message.is_some() => {
+                return Err(WebSocketError::Protocol("expected a continuation frame"));
+            }
+            Opcode::Text if frame.fin => Some(frame.payload),
+            Opcode::Text => {
+                message = Some(frame.payload);
+                None
+            }
+            Opcode::Continuation => {
+                let Some(partial) = message.as_mut() else {
+                    return Err(WebSocketError::Protocol("unexpected continuation frame"));
+                };
+                if partial.len() + frame.payload.len() > max_message_size {
+                    return Err(WebSocketError::TooLarge);
+                }
+                partial.extend_from_slice(&frame.payload);
+                if frame.fin {
+                    message.take()
+                } else {
+                    None
+                }
+            }
+            Opcode::Binary => return Err(WebSocketError::Unsupported),
+            Opcode::Ping => {
+                write_frame(reader.get_mut(), Opcode::Pong, &frame.payload)?;
+                None
+            }
+            Opcode::Pong => None,
+            Opcode::Close => {
+                let code = CloseFrame::parse(&frame.payload)?
+                    .map_or(CloseFrame::NORMAL, |close| close.code);
+                let close = CloseFrame::new(code, "");
+                write_frame(reader.get_mut(), Opcode::Close, &close.payload())?;
+                return Ok(());
+            }
+        };
+
+        if let Some(payload) = complete {
+            let text = String::from_utf8(payload).map_err(|_| WebSocketError::InvalidUtf8)?;
+            if let Some(reply) = (handler.0)(&text) {
+                write_frame(reader.get_mut(), Opcode::Text, reply.as_bytes())?;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn upgrade(headers: &[&str]) -> Request {
+        let mut raw = String::from("GET /ws HTTP/1.1\r\nHost: localhost\r\n");
+        for header in headers {
+            raw.push_str(header);
+            raw.push_str("\r\n");
+        }
+        raw.push_str("\r\n");
+        Request::from_bytes(raw.as_bytes()).unwrap()
+    }
+
+    /// 客户端发送的带掩码的帧
+    fn masked(first: u8, payload: &[u8]) -> Vec<u8> {
+        let mask = [0x37, 0xFA, 0x21, 0x3D];
+        let mut frame = vec![first];
+        match payload.len() {
+            len @ 0..=125 => frame.push(0x80 | len as u8),
+            len @ 126..=0xFFFF => {
+                frame.push(0x80 | 126);
+                frame.extend_from_slice(&(len as u16).to_be_bytes());
+            }
+            len => {
+                frame.push(0x80 | 127);
+                frame.extend_from_slice(&(len as u64).to_be_bytes());
+            }
+        }
+        frame.extend_from_slice(&mask);
+        frame.extend(payload.iter().enumerate().map(|(i, b)| b ^ mask[i % 4]));
+        frame
+    }
+
+    /// 内存中的连接，读取预先写好的客户端帧，记录服务器写出的数据
+    struct Duplex {
+        input: Cursor<Vec<u8>>,
+        output: Vec<u8>,
+    }
+
+    impl Read for Duplex {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.input.read(buf)
+        }
+    }
+
+    impl Write for Duplex {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.output.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn echo(frames: &[Vec<u8>]) -> (Result<(), WebSocketError>, Vec<u8>) {
+        let mut reader = BufReader::new(Duplex {
+            input: Cursor::new(frames.concat()),
+            output: Vec::new(),
+        });
+        let handler = WebSocketHandler::new(|message| Some(message.to_uppercase()));
+        let result = serve(&mut reader, &handler, 1024);
+        (result, reader.into_inner().output)
+    }
+
+    #[test]
+    fn accept_key_matches_rfc_example() {
+        assert_eq!(
+            accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+    }
+
+    #[test]
+    fn upgrade_needs_both_tokens() {
+        assert!(is_upgrade_request(&upgrade(&[
+            "Connection: keep-alive, Upgrade",
+            "Upgrade: WebSocket",
+        ])));
+        assert!(!is_upgrade_request(&upgrade(&["Upgrade: websocket"])));
+        assert!(!is_upgrade_request(&upgrade(&["Connection: Upgrade"])));
+    }
+
+    #[test]
+    fn handshake_checks_version_and_key() {
+        let response = handshake_response(&upgrade(&[
+            "Sec-WebSocket-Version: 13",
+            "Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==",
+        ]))
+        .unwrap();
+        assert_eq!(response.status, 101);
+        assert_eq!(
+            response
+                .headers
+                .iter()
+                .find(|(name, _)| name == "Sec-WebSocket-Accept")
+                .map(|(_, value)| value.as_str()),
+            Some("s3pPLMBiTxaQ9kYGzzhZRbK+xOo=")
+        );
+
+        assert!(handshake_response(&upgrade(&[
+            "Sec-WebSocket-Version: 8",
+            "Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==",
+        ]))
+        .is_none());
+        assert!(handshake_response(&upgrade(&[
+            "Sec-WebSocket-Version: 13",
+            "Sec-WebSocket-Key: c2hvcnQ=",
+        ]))
+        .is_none());
+    }
+
+    #[test]
+    fn reads_every_length_form() {
+        for length in [0, 125, 126, 0xFFFF, 0x10000] {
+            let payload = vec![b'x'; length];
+            let frame = read_frame(&mut Cursor::new(masked(0x81, &payload)), 0x20000).unwrap();
+            assert!(frame.fin);
+            assert_eq!(frame.opcode, Opcode::Text);
+            assert_eq!(frame.payload, payload);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_frames() {
+        let unmasked = [0x81, 0x02, b'h', b'i'];
+        assert!(matches!(
+            read_frame(&mut Cursor::new(unmasked), 1024),
+            Err(WebSocketError::Protocol(_))
+        ));
+        assert!(matches!(
+            read_frame(&mut Cursor::new(masked(0xC1, b"hi")), 1024),
+            Err(WebSocketError::Protocol(_))
+        ));
+        assert!(matches!(
+            read_frame(&mut Cursor::new(masked(0x09, b"")), 1024),
+            Err(WebSocketError::Protocol(_))
+        ));
+        assert!(matches!(
+            read_frame(&mut Cursor::new(masked(0x81, &[0; 200])), 100),
+            Err(WebSocketError::TooLarge)
+        ));
+    }
+
+    #[test]
+    fn writes_unmasked_frames() {
+        let mut out = Vec::new();
+        write_frame(&mut out, Opcode::Text, b"hi").unwrap();
+        assert_eq!(out, [0x81, 0x02, b'h', b'i']);
+
+        let mut out = Vec::new();
+        write_frame(&mut out, Opcode::Binary, &[0; 300]).unwrap();
+        assert_eq!(out[..4], [0x82, 126, 0x01, 0x2C]);
+        assert_eq!(out.len(), 4 + 300);
+    }
+
+    #[test]
+    fn serves_fragmented_messages_pings_and_close() {
+        let (result, output) = echo(&[
+            masked(0x01, b"hel"),
+            masked(0x89, b"p"),
+            masked(0x80, b"lo"),
+            masked(0x88, &1000_u16.to_be_bytes()),
+        ]);
+        assert!(result.is_ok());
+
+        let mut expected = Vec::new();
+        write_frame(&mut expected, Opcode::Pong, b"p").unwrap();
+        write_frame(&mut expected, Opcode::Text, b"HELLO").unwrap();
+        write_frame(&mut expected, Opcode::Close, &1000_u16.to_be_bytes()).unwrap();
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn protocol_errors_send_a_close_frame() {
+        let (result, output) = echo(&[masked(0x82, b"bin")]);
+        assert!(matches!(result, Err(WebSocketError::Unsupported)));
+        assert_eq!(output[0], 0x88);
+        assert_eq!(output[2..4], CloseFrame::UNSUPPORTED_DATA.to_be_bytes());
+
+        let (result, output) = echo(&[masked(0x81, &[0xFF, 0xFE])]);
+        assert!(matches!(result, Err(WebSocketError::InvalidUtf8)));
+        assert_eq!(output[2..4], CloseFrame::INVALID_PAYLOAD.to_be_bytes());
+    }
+}
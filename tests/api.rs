@@ -0,0 +1,389 @@
+mod common;
+
+use common::{request, Server};
+
+#[test]
+fn encoded_path_parameters_are_decoded_before_routing() {
+    let server = Server::start(&[]);
+
+    let response = server.send(
+        request(
+            "PATCH",
+            "/api/items/%31",
+            &["Content-Type: application/merge-patch+json"],
+            br#"{"name":"Renamed"}"#,
+        )
+        .as_bytes(),
+    );
+
+    assert_eq!(response.status, 200);
+    assert_eq!(response.text(), r#"{"id":1,"name":"Renamed"}"#);
+}
+
+#[test]
+fn search_matches_percent_encoded_non_ascii_names() {
+    let server = Server::start(&[]);
+    let upload = server.send(
+        request(
+            "POST",
+            "/api/upload",
+            &["Content-Type: application/x-www-form-urlencoded"],
+            b"id=10&name=Jos%C3%A9+Garc%C3%ADa",
+        )
+        .as_bytes(),
+    );
+    assert_eq!(upload.status, 201);
+
+    let response = server.get("/api/search?name=Jos%C3%A9+Garc%C3%ADa", &[]);
+
+    assert_eq!(response.status, 200);
+    assert_eq!(response.text(), r#"[{"id":10,"name":"José García"}]"#);
+    assert_eq!(server.get("/api/search?name=%E9", &[]).status, 400);
+}
+
+#[test]
+fn encoded_slash_does_not_split_path_parameters() {
+    let server = Server::start(&[]);
+
+    let response = server.send(request("DELETE", "/api/items/1%2F2", &[], b"").as_bytes());
+
+    assert_eq!(response.status, 400);
+    assert_eq!(
+        server
+            .get("/api/list", &[])
+            .text()
+            .matches("\"id\"")
+            .count(),
+        4
+    );
+}
+
+#[test]
+fn upload_content_type_is_matched_case_insensitively_without_parameters() {
+    let server = Server::start(&[]);
+
+    let response = server.send(
+        b"POST /api/upload HTTP/1.1\r\nconnection: close\r\nx-first: 1\r\n\
+          content-type:  Application/JSON ; charset=utf-8\r\ncontent-length: 9\r\n\r\n{\"a\":\"b\"}",
+    );
+    assert_eq!(response.status, 200);
+    assert_eq!(response.text(), r#"{"a":"b"}"#);
+
+    let response = server.send(
+        request(
+            "POST",
+            "/api/upload",
+            &["content-type: application/x-www-form-urlencoded; charset=utf-8"],
+            b"id=20&name=Lower",
+        )
+        .as_bytes(),
+    );
+    assert_eq!(response.status, 201);
+    assert_eq!(response.header("location"), Some("/api/search?id=20"));
+}
+
+/// Content-Type 可以出现在任意位置，其他头的值中可以有冒号
+#[test]
+fn content_type_is_found_among_any_headers() {
+    let server = Server::start(&[]);
+
+    let response = server.send(
+        b"POST /api/upload HTTP/1.1\r\nHost: localhost:8080\r\n\
+          Referer: http://x:8080/form?a=b:c\r\nUser-Agent: Go-http-client/1.1\r\n\
+          Accept-Encoding: gzip\r\nX-Trace: a:b:c\r\nConnection: close\r\n\
+          Content-Length: 9\r\nCONTENT-TYPE:\tapplication/json; charset=utf-8 \r\n\r\n\
+          {\"a\":\"b\"}",
+    );
+    assert_eq!(response.status, 200);
+    assert_eq!(response.text(), r#"{"a":"b"}"#);
+
+    // 名称只是包含 Content-Type 的请求头不算，缺少 Content-Type 的上传是错误的请求
+    let response = server.send(
+        b"POST /api/upload HTTP/1.1\r\nX-Content-Type: application/json\r\n\
+          Connection: close\r\nContent-Length: 2\r\n\r\n{}",
+    );
+    assert_eq!(response.status, 400);
+}
+
+#[test]
+fn multipart_files_are_saved_under_generated_names() {
+    let server = Server::start(&["--upload-dir", "uploads"]);
+    let body = b"--b0undary\r\n\
+Content-Disposition: form-data; name=\"title\"\r\n\r\n\
+notes\r\n\
+--b0undary\r\n\
+Content-Disposition: form-data; name=\"file\"; filename=\"../../escape.txt\"\r\n\
+Content-Type: text/plain\r\n\r\n\
+line one\r\nline two\r\n\
+--b0undary--\r\n";
+
+    let response = server.send(
+        request(
+            "POST",
+            "/api/upload",
+            &["Content-Type: multipart/form-data; boundary=b0undary"],
+            body,
+        )
+        .as_bytes(),
+    );
+    assert_eq!(response.status, 200);
+    assert_eq!(
+        response.text(),
+        r#"[{"name":"title","size":5},{"filename":"../../escape.txt","name":"file","size":18}]"#
+    );
+
+    let saved: Vec<_> = std::fs::read_dir(server.dir.path().join("uploads"))
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    assert_eq!(saved.len(), 1);
+    assert!(saved[0]
+        .file_name()
+        .unwrap()
+        .to_string_lossy()
+        .starts_with("upload-"));
+    assert_eq!(std::fs::read(&saved[0]).unwrap(), b"line one\r\nline two");
+    assert!(!server.dir.path().join("escape.txt").exists());
+}
+
+#[test]
+fn multipart_without_a_boundary_gets_400() {
+    let server = Server::start(&[]);
+
+    let response = server.send(
+        request(
+            "POST",
+            "/api/upload",
+            &["Content-Type: multipart/form-data"],
+            b"--x\r\n\r\n--x--",
+        )
+        .as_bytes(),
+    );
+    assert_eq!(response.status, 400);
+
+    let response = server.send(
+        request(
+            "POST",
+            "/api/upload",
+            &["Content-Type: multipart/form-data; boundary=x"],
+            b"--x\r\nno terminator",
+        )
+        .as_bytes(),
+    );
+    assert_eq!(response.status, 400);
+}
+
+/// 返回对象的 `id` 列表和 `X-Total-Count`
+fn page(server: &Server, path: &str) -> (Vec<u64>, String) {
+    let response = server.get(path, &[]);
+    assert_eq!(response.status, 200, "{}", path);
+    let items: Vec<serde_json::Value> = serde_json::from_slice(&response.body).unwrap();
+    let ids = items
+        .iter()
+        .map(|item| item["id"].as_u64().unwrap())
+        .collect();
+    (ids, response.header("x-total-count").unwrap().to_string())
+}
+
+#[test]
+fn list_and_search_are_paginated_and_sorted() {
+    let server = Server::start_with(&[], |dir| {
+        let items: Vec<_> = [
+            (3, "carol"),
+            (1, "bob"),
+            (5, "alice"),
+            (2, "dave"),
+            (4, "bobby"),
+        ]
+        .iter()
+        .map(|(id, name)| serde_json::json!({ "id": id, "name": name }))
+        .collect();
+        std::fs::write(
+            dir.join("data/data.json"),
+            serde_json::to_vec(&items).unwrap(),
+        )
+        .unwrap();
+    });
+
+    assert_eq!(
+        page(&server, "/api/list"),
+        (vec![1, 2, 3, 4, 5], "5".into())
+    );
+    assert_eq!(
+        page(&server, "/api/list?limit=2&offset=1"),
+        (vec![2, 3], "5".into())
+    );
+    assert_eq!(page(&server, "/api/list?offset=10"), (vec![], "5".into()));
+    assert_eq!(
+        page(&server, "/api/list?sort=name"),
+        (vec![5, 1, 4, 3, 2], "5".into())
+    );
+    assert_eq!(
+        page(&server, "/api/list?sort=id&order=desc&limit=2"),
+        (vec![5, 4], "5".into())
+    );
+    assert_eq!(
+        page(&server, "/api/search?name_prefix=bob&sort=name&order=desc"),
+        (vec![4, 1], "2".into())
+    );
+
+    for query in ["limit=-1", "offset=x", "sort=size", "order=up"] {
+        let response = server.get(&format!("/api/list?{}", query), &[]);
+        assert_eq!(response.status, 400, "{}", query);
+    }
+}
+
+/// 数据文件中的对象故意不按 `id` 排列
+fn search_server() -> Server {
+    Server::start_with(&[], |dir| {
+        let items: Vec<_> = [
+            (5, "Alicia"),
+            (3, "Malice"),
+            (1, "Alice"),
+            (4, "Bob"),
+            (2, "alice"),
+        ]
+        .iter()
+        .map(|(id, name)| serde_json::json!({ "id": id, "name": name }))
+        .collect();
+        std::fs::write(
+            dir.join("data/data.json"),
+            serde_json::to_vec(&items).unwrap(),
+        )
+        .unwrap();
+    })
+}
+
+/// 搜索结果的 `id` 列表，没有结果时为空
+fn search(server: &Server, query: &str) -> Vec<u64> {
+    let response = server.get(&format!("/api/search?{}", query), &[]);
+    if response.status == 404 {
+        return Vec::new();
+    }
+    assert_eq!(response.status, 200, "{}", query);
+    let items: Vec<serde_json::Value> = serde_json::from_slice(&response.body).unwrap();
+    items
+        .iter()
+        .map(|item| item["id"].as_u64().unwrap())
+        .collect()
+}
+
+#[test]
+fn name_matches_exactly_ignoring_case_by_default() {
+    let server = search_server();
+
+    assert_eq!(search(&server, "name=alice"), [1, 2]);
+    assert_eq!(search(&server, "name=ALICE"), [1, 2]);
+    assert!(search(&server, "name=ali").is_empty());
+    assert_eq!(search(&server, "name=Alice&case_sensitive=true"), [1]);
+    assert!(search(&server, "name=ALICE&case_sensitive=true").is_empty());
+}
+
+#[test]
+fn name_contains_and_name_prefix_match_parts_of_the_name() {
+    let server = search_server();
+
+    assert_eq!(search(&server, "name_contains=lic"), [1, 2, 3, 5]);
+    assert!(search(&server, "name_contains=LIC&case_sensitive=true").is_empty());
+    assert_eq!(search(&server, "name_prefix=ali"), [1, 2, 5]);
+    assert_eq!(
+        search(&server, "name_prefix=Ali&case_sensitive=true"),
+        [1, 5]
+    );
+    assert!(search(&server, "name_prefix=lic").is_empty());
+}
+
+#[test]
+fn search_filters_are_anded_and_sorted_by_id() {
+    let server = search_server();
+
+    assert_eq!(search(&server, "name_contains=ice&name_prefix=a"), [1, 2]);
+    assert_eq!(search(&server, "name_prefix=a&name=alicia"), [5]);
+    assert_eq!(search(&server, "id=3&name_contains=lic"), [3]);
+    assert!(search(&server, "id=4&name_contains=lic").is_empty());
+    assert_eq!(search(&server, ""), [1, 2, 3, 4, 5]);
+
+    let response = server.get("/api/search?case_sensitive=maybe", &[]);
+    assert_eq!(response.status, 400);
+}
+
+#[test]
+fn id_and_name_are_matched_independently() {
+    let server = search_server();
+
+    // 只有 id
+    assert_eq!(search(&server, "id=3"), [3]);
+    assert!(search(&server, "id=42").is_empty());
+    // 只有 name
+    assert_eq!(search(&server, "name=bob"), [4]);
+    // 两者都要满足
+    assert_eq!(search(&server, "id=1&name=alice"), [1]);
+    assert!(search(&server, "id=4&name=alice").is_empty());
+    // 都没有时返回全部
+    assert_eq!(search(&server, "sort=id"), [1, 2, 3, 4, 5]);
+
+    assert_eq!(server.get("/api/search?id=abc", &[]).status, 400);
+}
+
+#[test]
+fn invalid_ids_get_a_json_error() {
+    let server = search_server();
+
+    for query in ["id=abc", "id=", "id=-1", "id=1.5", "id=abc&name=alice"] {
+        let response = server.get(&format!("/api/search?{}", query), &[]);
+        assert_eq!(response.status, 400, "{}", query);
+        assert_eq!(
+            response.header("content-type"),
+            Some("application/json; charset=utf-8")
+        );
+        let body: serde_json::Value = serde_json::from_slice(&response.body).unwrap();
+        assert_eq!(body, serde_json::json!({ "error": "invalid id" }));
+    }
+
+    // 没有 id 时不需要校验，只按 name 查找
+    assert_eq!(search(&server, "name=malice"), [3]);
+}
+
+#[test]
+fn item_and_record_routes_share_json_errors() {
+    let server = Server::start(&[]);
+    let json_error = |method: &str, path: &str, headers: &[&str], body: &[u8]| {
+        let response = server.send(request(method, path, headers, body).as_bytes());
+        assert_eq!(
+            response.header("content-type"),
+            Some("application/json; charset=utf-8"),
+            "{} {}",
+            method,
+            path
+        );
+        (response.status, response.text())
+    };
+    let not_found = (404, String::from(r#"{"error":"item not found"}"#));
+
+    assert_eq!(json_error("DELETE", "/api/items/99", &[], b""), not_found);
+    assert_eq!(json_error("DELETE", "/api/items/x", &[], b""), not_found);
+    assert_eq!(
+        json_error("DELETE", "/api/records?id=99", &[], b""),
+        not_found
+    );
+    assert_eq!(
+        json_error("PUT", "/api/items/99", &[], br#"{"name":"a"}"#),
+        not_found
+    );
+    assert_eq!(
+        json_error("PUT", "/api/records", &[], br#"{"id":99,"name":"a"}"#),
+        not_found
+    );
+    assert_eq!(json_error("PUT", "/api/items/1", &[], b"[1]").0, 400);
+    assert_eq!(json_error("PUT", "/api/records", &[], b"nope").0, 400);
+    assert_eq!(json_error("PATCH", "/api/items/1", &[], b"{}").0, 415);
+
+    // 两组路由修改的是同一份数据
+    let response =
+        server.send(request("PUT", "/api/items/1", &[], br#"{"name":"One"}"#).as_bytes());
+    assert_eq!(response.status, 200);
+    assert_eq!(response.text(), r#"{"id":1,"name":"One"}"#);
+    let response = server.send(request("DELETE", "/api/records?id=1", &[], b"").as_bytes());
+    assert_eq!(response.status, 204);
+    assert_eq!(json_error("DELETE", "/api/items/1", &[], b""), not_found);
+}
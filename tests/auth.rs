@@ -0,0 +1,82 @@
+mod common;
+
+use common::Server;
+
+/// alice:secret
+const ALICE: &str = "Authorization: Basic YWxpY2U6c2VjcmV0";
+
+fn start() -> Server {
+    Server::start_with(
+        &["--auth-file", "users", "--auth-prefix", "/admin"],
+        |dir| {
+            let hash = bcrypt::hash("secret", 4).unwrap();
+            std::fs::write(dir.join("users"), format!("alice:{}\n", hash)).unwrap();
+            std::fs::create_dir(dir.join("static/admin")).unwrap();
+            std::fs::write(dir.join("static/admin/secret.txt"), "top secret").unwrap();
+        },
+    )
+}
+
+#[test]
+fn correct_credentials_are_accepted() {
+    let server = start();
+
+    let response = server.get("/admin/secret.txt", &[ALICE]);
+
+    assert_eq!(response.status, 200);
+    assert_eq!(response.text(), "top secret");
+}
+
+#[test]
+fn bad_credentials_get_401_with_a_challenge() {
+    let server = start();
+
+    for headers in [
+        &[][..],
+        &["Authorization: Basic YWxpY2U6d3Jvbmc="],
+        &["Authorization: Basic %%%"],
+        &["Authorization: Bearer YWxpY2U6c2VjcmV0"],
+    ] {
+        let response = server.get("/admin/secret.txt", headers);
+        assert_eq!(response.status, 401, "{:?}", headers);
+        assert_eq!(
+            response.header("www-authenticate"),
+            Some("Basic realm=\"http-server\"")
+        );
+    }
+}
+
+#[test]
+fn non_normalized_paths_do_not_bypass_the_prefix() {
+    let server = start();
+
+    for path in [
+        "//admin/secret.txt",
+        "/./admin/secret.txt",
+        "/x/../admin/secret.txt",
+        "/%2E/admin/secret.txt",
+        "/%61dmin/secret.txt",
+    ] {
+        assert_eq!(server.get(path, &[]).status, 401, "{}", path);
+        assert_eq!(server.get(path, &[ALICE]).status, 200, "{}", path);
+    }
+}
+
+#[test]
+fn paths_outside_the_prefix_are_public() {
+    let server = start();
+
+    assert_eq!(server.get("/hello-world.txt", &[]).status, 200);
+    assert_eq!(server.get("/admin/../hello-world.txt", &[]).status, 200);
+}
+
+#[test]
+fn jwt_prefix_is_checked_on_the_normalized_path() {
+    let server = Server::start(&["--jwt-secret", "secret"]);
+
+    for path in ["/api/list", "//api/list", "/./api/list", "/x/../api/list"] {
+        let response = server.get(path, &[]);
+        assert_eq!(response.status, 401, "{}", path);
+        assert_eq!(response.header("www-authenticate"), Some("Bearer"));
+    }
+}
@@ -0,0 +1,162 @@
+mod common;
+
+use common::Server;
+use std::io::{BufReader, Read, Write};
+
+/// 同一请求分别以 GET 和 HEAD 发送；HEAD 的响应之后不能有任何字节
+fn get_and_head(
+    server: &Server,
+    path: &str,
+    headers: &[&str],
+) -> (common::Response, common::Response) {
+    let get = server.get(path, headers);
+
+    let mut stream = server.connect();
+    stream
+        .write_all(common::request("HEAD", path, headers, b"").as_bytes())
+        .unwrap();
+    let mut reader = BufReader::new(stream);
+    let head = common::read_head(&mut reader);
+    let mut rest = Vec::new();
+    reader.read_to_end(&mut rest).unwrap();
+    assert!(rest.is_empty(), "HEAD {} has a body", path);
+
+    (get, head)
+}
+
+#[test]
+fn last_modified_is_honoured_by_if_modified_since() {
+    let server = Server::start(&[]);
+
+    let response = server.get("/hello-world.txt", &[]);
+    let last_modified = response.header("last-modified").unwrap().to_string();
+    assert!(last_modified.ends_with(" GMT"));
+
+    let since = format!("If-Modified-Since: {}", last_modified);
+    let response = server.get("/hello-world.txt", &[&since]);
+    assert_eq!(response.status, 304);
+    assert!(response.body.is_empty());
+
+    let earlier = server.get(
+        "/hello-world.txt",
+        &["If-Modified-Since: Thu, 01 Jan 1970 00:00:00 GMT"],
+    );
+    assert_eq!(earlier.status, 200);
+    let invalid = server.get("/hello-world.txt", &["If-Modified-Since: yesterday"]);
+    assert_eq!(invalid.status, 200);
+}
+
+#[test]
+fn matching_etag_gets_304_until_the_file_changes() {
+    let server = Server::start(&[]);
+    let path = server.dir.path().join("static/hello-world.txt");
+
+    let first = server.get("/hello-world.txt", &[]);
+    assert_eq!(first.status, 200);
+    let etag = first.header("etag").unwrap().to_string();
+
+    let if_none_match = format!("If-None-Match: {}", etag);
+    let cached = server.get("/hello-world.txt", &[&if_none_match]);
+    assert_eq!(cached.status, 304);
+    assert_eq!(cached.header("etag"), Some(etag.as_str()));
+    assert!(cached.body.is_empty());
+    assert!(cached
+        .header("content-length")
+        .is_none_or(|length| length == "0"));
+
+    std::fs::write(&path, "changed contents").unwrap();
+    let changed = server.get("/hello-world.txt", &[&if_none_match]);
+    assert_eq!(changed.status, 200);
+    assert_eq!(changed.text(), "changed contents");
+    assert_ne!(changed.header("etag"), Some(etag.as_str()));
+}
+
+#[test]
+fn head_carries_the_same_etag() {
+    let server = Server::start(&[]);
+    let etag = server
+        .get("/index.html", &[])
+        .header("etag")
+        .unwrap()
+        .to_string();
+
+    let mut stream = server.connect();
+    stream
+        .write_all(common::request("HEAD", "/index.html", &[], b"").as_bytes())
+        .unwrap();
+    let head = common::read_head(&mut BufReader::new(stream));
+    assert_eq!(head.status, 200);
+    assert_eq!(head.header("etag"), Some(etag.as_str()));
+}
+
+/// 304 没有响应体，同一连接上的下一个响应不会错位
+#[test]
+fn not_modified_responses_keep_the_connection_in_sync() {
+    use std::io::{BufReader, Write};
+
+    let server = Server::start(&[]);
+    let full = server.get("/index.html", &[]);
+    let etag = full.header("etag").unwrap().to_string();
+
+    let mut stream = server.connect();
+    let conditional = format!(
+        "GET /index.html HTTP/1.1\r\nIf-None-Match: \"other\", {}\r\n\r\n",
+        etag
+    );
+    stream.write_all(conditional.as_bytes()).unwrap();
+    stream
+        .write_all(b"GET /index.html HTTP/1.1\r\nConnection: close\r\n\r\n")
+        .unwrap();
+    let mut reader = BufReader::new(stream);
+
+    let cached = common::read_response(&mut reader);
+    assert_eq!(cached.status, 304);
+    assert_eq!(cached.header("etag"), Some(etag.as_str()));
+    assert!(cached
+        .header("content-length")
+        .is_none_or(|length| length == "0"));
+
+    let response = common::read_response(&mut reader);
+    assert_eq!(response.status, 200);
+    assert_eq!(response.body, full.body);
+}
+
+#[test]
+fn head_content_length_matches_the_get_body() {
+    let server = Server::start(&[]);
+
+    for (path, status) in [
+        ("/index.html", 200),
+        ("/hello-world.txt", 200),
+        ("/missing", 404),
+    ] {
+        let (get, head) = get_and_head(&server, path, &[]);
+        assert_eq!(head.status, status, "{}", path);
+        assert_eq!(head.status, get.status);
+        assert_eq!(head.header("content-type"), get.header("content-type"));
+        assert_eq!(
+            head.header("content-length"),
+            Some(get.body.len().to_string().as_str()),
+            "{}",
+            path
+        );
+    }
+}
+
+#[test]
+fn head_content_length_matches_the_compressed_body() {
+    let server = Server::start_with(&["--compress"], |dir| {
+        let text = "All work and no play makes Jack a dull boy.\n".repeat(200);
+        std::fs::write(dir.join("static/jack.txt"), text).unwrap();
+    });
+
+    let (get, head) = get_and_head(&server, "/jack.txt", &["Accept-Encoding: gzip"]);
+    assert_eq!(get.header("content-encoding"), Some("gzip"));
+    assert_eq!(head.status, 200);
+    assert_eq!(head.header("content-encoding"), Some("gzip"));
+    assert_eq!(head.header("etag"), get.header("etag"));
+    assert_eq!(
+        head.header("content-length"),
+        Some(get.body.len().to_string().as_str())
+    );
+}
@@ -0,0 +1,214 @@
+//! 集成测试共用的工具：在临时目录中启动服务器进程，发送原始请求并解析响应
+#![allow(dead_code)]
+
+use std::{
+    fs,
+    io::{BufRead, BufReader, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    path::Path,
+    process::{Child, Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+use tempfile::TempDir;
+
+/// 测试结束时被结束的服务器进程，工作目录是复制了 `static` 和 `data` 的临时目录
+pub struct Server {
+    pub child: Child,
+    pub addr: SocketAddr,
+    pub dir: TempDir,
+}
+
+impl Server {
+    /// 用额外的命令行参数启动服务器，等到端口可以连接后返回
+    pub fn start(args: &[&str]) -> Server {
+        Server::start_with(args, |_| {})
+    }
+
+    /// 启动前可以修改临时目录中的文件
+    pub fn start_with(args: &[&str], prepare: impl FnOnce(&Path)) -> Server {
+        let dir = TempDir::new().unwrap();
+        let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+        copy_dir(&manifest_dir.join("static"), &dir.path().join("static"));
+        copy_dir(&manifest_dir.join("data"), &dir.path().join("data"));
+        prepare(dir.path());
+
+        let addr = free_addr();
+        let child = Command::new(env!("CARGO_BIN_EXE_http-server"))
+            .args(["-p", &addr.port().to_string()])
+            .args(args)
+            .current_dir(dir.path())
+            .env("RUST_LOG", "off")
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+
+        let server = Server { child, addr, dir };
+        server.wait_until_listening();
+        server
+    }
+
+    fn wait_until_listening(&self) {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while TcpStream::connect(self.addr).is_err() {
+            assert!(Instant::now() < deadline, "server did not start");
+            thread::sleep(Duration::from_millis(20));
+        }
+    }
+
+    pub fn connect(&self) -> TcpStream {
+        let stream = TcpStream::connect(self.addr).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(10)))
+            .unwrap();
+        stream
+    }
+
+    /// 在新连接上发送原始请求并读取一个响应
+    pub fn send(&self, request: &[u8]) -> Response {
+        let mut stream = self.connect();
+        stream.write_all(request).unwrap();
+        read_response(&mut BufReader::new(stream))
+    }
+
+    /// 发送带 `Connection: close` 的 GET 请求，`headers` 是完整的请求头行
+    pub fn get(&self, path: &str, headers: &[&str]) -> Response {
+        self.send(request("GET", path, headers, b"").as_bytes())
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// 拼出一个关闭连接的请求，请求体非空时加上 `Content-Length`
+pub fn request(method: &str, path: &str, headers: &[&str], body: &[u8]) -> String {
+    let mut request = format!("{} {} HTTP/1.1\r\nHost: localhost\r\n", method, path);
+    for header in headers {
+        request.push_str(header);
+        request.push_str("\r\n");
+    }
+    if !body.is_empty() || method == "POST" || method == "PUT" {
+        request.push_str(&format!("Content-Length: {}\r\n", body.len()));
+    }
+    request.push_str("Connection: close\r\n\r\n");
+    request.push_str(&String::from_utf8_lossy(body));
+    request
+}
+
+/// 解析后的响应，响应头名称统一为小写
+#[derive(Debug)]
+pub struct Response {
+    pub version: String,
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+}
+
+/// 从连接中读取一个响应，按 `Content-Length`、chunked 或关闭连接确定响应体的结尾；
+/// HEAD 请求和 1xx、204、304 响应没有响应体时由调用者用 [`read_head`] 读取
+pub fn read_response(reader: &mut impl BufRead) -> Response {
+    let mut response = read_head(reader);
+    if matches!(response.status, 204 | 304) {
+        return response;
+    }
+
+    if let Some(length) = response.header("content-length") {
+        let mut body = vec![0; length.parse().unwrap()];
+        reader.read_exact(&mut body).unwrap();
+        response.body = body;
+    } else if response
+        .header("transfer-encoding")
+        .is_some_and(|value| value.eq_ignore_ascii_case("chunked"))
+    {
+        response.body = read_chunked(reader);
+    } else {
+        reader.read_to_end(&mut response.body).unwrap();
+    }
+    response
+}
+
+/// 只读取状态行和响应头
+pub fn read_head(reader: &mut impl BufRead) -> Response {
+    let mut line = String::new();
+    reader.read_line(&mut line).unwrap();
+    let mut parts = line.trim_end().splitn(3, ' ');
+    let version = parts.next().unwrap_or_default().to_string();
+    let status = parts
+        .next()
+        .and_then(|status| status.parse().ok())
+        .unwrap_or_else(|| panic!("invalid status line: {:?}", line));
+
+    let mut headers = Vec::new();
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        let (name, value) = line.split_once(':').unwrap();
+        headers.push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+    }
+
+    Response {
+        version,
+        status,
+        headers,
+        body: Vec::new(),
+    }
+}
+
+fn read_chunked(reader: &mut impl BufRead) -> Vec<u8> {
+    let mut body = Vec::new();
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        let size = usize::from_str_radix(line.trim_end(), 16).unwrap();
+        let mut chunk = vec![0; size + 2];
+        reader.read_exact(&mut chunk).unwrap();
+        if size == 0 {
+            return body;
+        }
+        body.extend_from_slice(&chunk[..size]);
+    }
+}
+
+/// 操作系统分配的空闲端口；先绑定再释放，启动服务器前被占用的可能很小
+fn free_addr() -> SocketAddr {
+    TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+}
+
+fn copy_dir(from: &Path, to: &Path) {
+    fs::create_dir_all(to).unwrap();
+    for entry in fs::read_dir(from).unwrap() {
+        let entry = entry.unwrap();
+        let target = to.join(entry.file_name());
+        if entry.file_type().unwrap().is_dir() {
+            copy_dir(&entry.path(), &target);
+        } else {
+            fs::copy(entry.path(), target).unwrap();
+        }
+    }
+}
@@ -0,0 +1,77 @@
+mod common;
+
+use common::Server;
+use flate2::read::GzDecoder;
+use std::io::Read;
+
+fn start() -> (Server, Vec<u8>) {
+    let text = "All work and no play makes Jack a dull boy.\n".repeat(200);
+    let server = Server::start_with(&["--compress"], |dir| {
+        std::fs::write(dir.join("static/jack.txt"), &text).unwrap();
+    });
+    (server, text.into_bytes())
+}
+
+fn gunzip(body: &[u8]) -> Vec<u8> {
+    let mut decoded = Vec::new();
+    GzDecoder::new(body).read_to_end(&mut decoded).unwrap();
+    decoded
+}
+
+#[test]
+fn gzip_is_used_when_accepted() {
+    let (server, text) = start();
+
+    let response = server.get("/jack.txt", &["Accept-Encoding: gzip"]);
+
+    assert_eq!(response.status, 200);
+    assert_eq!(response.header("content-encoding"), Some("gzip"));
+    assert_eq!(response.header("vary"), Some("Accept-Encoding"));
+    assert_eq!(
+        response.header("content-length"),
+        Some(response.body.len().to_string().as_str())
+    );
+    assert!(response.body.len() < text.len());
+    assert_eq!(gunzip(&response.body), text);
+}
+
+#[test]
+fn identity_is_used_otherwise() {
+    let (server, text) = start();
+
+    let cases: [&[&str]; 3] = [
+        &[],
+        &["Accept-Encoding: br"],
+        &["Accept-Encoding: gzip;q=0, *"],
+    ];
+    for headers in cases {
+        let response = server.get("/jack.txt", headers);
+        assert_eq!(response.header("content-encoding"), None, "{:?}", headers);
+        assert_eq!(response.body, text);
+    }
+}
+
+#[test]
+fn gzip_and_identity_have_different_etags() {
+    let (server, _) = start();
+
+    let identity = server.get("/jack.txt", &[]);
+    let gzip = server.get("/jack.txt", &["Accept-Encoding: gzip"]);
+    let identity_etag = identity.header("etag").unwrap();
+    let gzip_etag = gzip.header("etag").unwrap();
+
+    assert_ne!(identity_etag, gzip_etag);
+    assert_eq!(
+        gzip_etag,
+        format!("{}-gzip\"", identity_etag.trim_end_matches('"'))
+    );
+
+    for etag in [identity_etag, gzip_etag] {
+        let response = server.get(
+            "/jack.txt",
+            &["Accept-Encoding: gzip", &format!("If-None-Match: {}", etag)],
+        );
+        assert_eq!(response.status, 304);
+        assert_eq!(response.header("etag"), Some(etag));
+    }
+}
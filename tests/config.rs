@@ -0,0 +1,160 @@
+//! 环境变量属于整个进程，读取环境变量的用例都放在这一个测试函数里，不与其他用例并行
+
+use clap::Parser;
+use http_server::{config, Args, FileConfig, ResolvedConfig};
+use std::{env, fs};
+
+fn resolve(argv: &[&str]) -> Result<ResolvedConfig, http_server::ConfigError> {
+    let args =
+        Args::try_parse_from(std::iter::once("http-server").chain(argv.iter().copied())).unwrap();
+    let file = match &args.config {
+        Some(path) => FileConfig::from_toml_file(path)?,
+        None => FileConfig::default(),
+    };
+    config::resolve(args, file)
+}
+
+/// 一项参数在每一层的取值，`get` 取出最终生效的值
+struct Field {
+    flag: &'static str,
+    var: &'static str,
+    default: Option<&'static str>,
+    file: &'static str,
+    env: &'static str,
+    cli: &'static str,
+    get: fn(&ResolvedConfig) -> Option<String>,
+}
+
+const FIELDS: [Field; 7] = [
+    Field {
+        flag: "--ip",
+        var: "HTTPSERVER_IP",
+        default: Some("127.0.0.1"),
+        file: "0.0.0.0",
+        env: "127.0.0.2",
+        cli: "127.0.0.3",
+        get: |config| Some(config.ip.clone()),
+    },
+    Field {
+        flag: "--port",
+        var: "HTTPSERVER_PORT",
+        default: Some("8080"),
+        file: "9000",
+        env: "7000",
+        cli: "6000",
+        get: |config| Some(config.port.to_string()),
+    },
+    Field {
+        flag: "--threads",
+        var: "HTTPSERVER_THREADS",
+        default: Some("8"),
+        file: "2",
+        env: "3",
+        cli: "4",
+        get: |config| Some(config.threads.to_string()),
+    },
+    Field {
+        flag: "--proxy",
+        var: "HTTPSERVER_PROXY",
+        default: None,
+        file: "http://127.0.0.1:9",
+        env: "http://127.0.0.1:10",
+        cli: "http://127.0.0.1:11",
+        get: |config| Some(config.proxy.clone()).filter(|proxy| !proxy.is_empty()),
+    },
+    Field {
+        flag: "--jwt-secret",
+        var: "HTTPSERVER_JWT_SECRET",
+        default: None,
+        file: "from-file",
+        env: "from-env",
+        cli: "from-cli",
+        get: |config| config.jwt_secret.clone(),
+    },
+    Field {
+        flag: "--tls-cert",
+        var: "HTTPSERVER_TLS_CERT",
+        default: None,
+        file: "file-cert.pem",
+        env: "env-cert.pem",
+        cli: "cli-cert.pem",
+        get: |config| Some(config.tls_cert.as_ref()?.display().to_string()),
+    },
+    Field {
+        flag: "--tls-key",
+        var: "HTTPSERVER_TLS_KEY",
+        default: None,
+        file: "file-key.pem",
+        env: "env-key.pem",
+        cli: "cli-key.pem",
+        get: |config| Some(config.tls_key.as_ref()?.display().to_string()),
+    },
+];
+
+#[test]
+fn cli_beats_env_beats_config_file_beats_default() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("server.toml");
+    fs::write(
+        &file,
+        "ip = \"0.0.0.0\"\nport = 9000\nthreads = 2\nproxy = \"http://127.0.0.1:9\"\n\
+         jwt_secret = \"from-file\"\ntls_cert = \"file-cert.pem\"\ntls_key = \"file-key.pem\"\n",
+    )
+    .unwrap();
+    let file = file.to_str().unwrap();
+
+    for field in &FIELDS {
+        let value = |argv: &[&str]| (field.get)(&resolve(argv).unwrap());
+
+        // 只有默认值
+        assert_eq!(value(&[]).as_deref(), field.default, "{}", field.var);
+
+        // 配置文件替换默认值
+        assert_eq!(value(&["--config", file]).unwrap(), field.file);
+
+        // 环境变量替换默认值，也优先于配置文件
+        env::set_var(field.var, field.env);
+        if field.var != "HTTPSERVER_TLS_CERT" && field.var != "HTTPSERVER_TLS_KEY" {
+            assert_eq!(value(&[]).unwrap(), field.env);
+        }
+        assert_eq!(value(&["--config", file]).unwrap(), field.env);
+
+        // 命令行参数优先于环境变量和配置文件
+        assert_eq!(
+            value(&["--config", file, field.flag, field.cli]).unwrap(),
+            field.cli
+        );
+
+        // 为空的环境变量视为未设置
+        env::set_var(field.var, "");
+        assert_eq!(value(&["--config", file]).unwrap(), field.file);
+        env::remove_var(field.var);
+    }
+
+    // 证书和私钥可以分别来自不同的来源，但必须同时设置
+    env::set_var("HTTPSERVER_TLS_KEY", "env-key.pem");
+    let config = resolve(&["--tls-cert", "cli-cert.pem"]).unwrap();
+    assert_eq!(config.tls_cert.unwrap().to_str(), Some("cli-cert.pem"));
+    assert_eq!(config.tls_key.unwrap().to_str(), Some("env-key.pem"));
+    assert!(resolve(&[]).is_err());
+    env::remove_var("HTTPSERVER_TLS_KEY");
+
+    // 配置文件也可以由环境变量指定
+    env::set_var("HTTPSERVER_CONFIG", file);
+    let config = resolve(&[]).unwrap();
+    assert_eq!(config.port, 9000);
+    assert_eq!(config.ip, "0.0.0.0");
+    env::remove_var("HTTPSERVER_CONFIG");
+
+    // 取值不合法的环境变量和命令行参数一样被拒绝
+    for (var, value) in [
+        ("HTTPSERVER_PORT", "0"),
+        ("HTTPSERVER_PORT", "70000"),
+        ("HTTPSERVER_THREADS", "0"),
+        ("HTTPSERVER_THREADS", "many"),
+    ] {
+        env::set_var(var, value);
+        assert!(resolve(&[]).is_err(), "{}={}", var, value);
+        env::remove_var(var);
+    }
+}
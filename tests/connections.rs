@@ -0,0 +1,96 @@
+mod common;
+
+use common::{read_response, Server};
+use std::{
+    io::{BufReader, ErrorKind},
+    thread,
+    time::{Duration, Instant},
+};
+
+#[test]
+fn connections_over_the_limit_get_503() {
+    const LIMIT: usize = 256;
+    const OPENED: usize = 300;
+
+    // 队列容量（线程数的 10 倍）大于连接数上限，超出的连接只会因为许可用完而被拒绝
+    let server = Server::start(&["--max-connections", "256", "--threads", "32"]);
+    let streams: Vec<_> = (0..OPENED).map(|_| server.connect()).collect();
+    for stream in &streams {
+        stream.set_nonblocking(true).unwrap();
+    }
+
+    // 没有发送请求的连接中，只有被拒绝的会立即收到响应
+    let rejected = || {
+        streams
+            .iter()
+            .filter(|stream| match stream.peek(&mut [0]) {
+                Ok(_) => true,
+                Err(err) if err.kind() == ErrorKind::WouldBlock => false,
+                Err(err) => panic!("{}", err),
+            })
+            .count()
+    };
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while rejected() < OPENED - LIMIT && Instant::now() < deadline {
+        thread::sleep(Duration::from_millis(50));
+    }
+    thread::sleep(Duration::from_millis(200));
+    // 启动时探测端口的连接可能还占着一个许可
+    let served = OPENED - rejected();
+    assert!((LIMIT - 1..=LIMIT).contains(&served), "{} served", served);
+
+    let rejection = streams
+        .into_iter()
+        .find(|stream| stream.peek(&mut [0]).is_ok())
+        .unwrap();
+    rejection.set_nonblocking(false).unwrap();
+    let response = read_response(&mut BufReader::new(rejection));
+    assert_eq!(response.status, 503);
+    assert_eq!(response.header("retry-after"), Some("1"));
+}
+
+#[test]
+fn permits_are_returned_when_connections_close() {
+    let server = Server::start(&["--max-connections", "2", "--threads", "2"]);
+    // 启动时探测端口的连接可能还占着许可，直到两个空闲连接都没有被拒绝
+    let idle = loop {
+        let idle: Vec<_> = (0..2).map(|_| server.connect()).collect();
+        thread::sleep(Duration::from_millis(200));
+        let accepted = idle.iter().all(|stream| {
+            stream.set_nonblocking(true).unwrap();
+            stream
+                .peek(&mut [0])
+                .is_err_and(|err| err.kind() == ErrorKind::WouldBlock)
+        });
+        if accepted {
+            break idle;
+        }
+    };
+    assert_eq!(server.get("/", &[]).status, 503);
+
+    drop(idle);
+    let deadline = Instant::now() + Duration::from_secs(5);
+    loop {
+        let status = server.get("/", &[]).status;
+        if status == 200 {
+            break;
+        }
+        assert_eq!(status, 503);
+        assert!(Instant::now() < deadline, "permits were not returned");
+        thread::sleep(Duration::from_millis(50));
+    }
+}
+
+#[test]
+fn rate_limited_connections_get_429_before_sending_a_request() {
+    // 每秒 1 个请求，突发 2 个；启动时探测端口的连接和第一个空闲连接用完了令牌
+    let server = Server::start(&["--rate-limit", "1", "--threads", "1"]);
+    let idle = server.connect();
+
+    // 唯一的工作线程被空闲连接占着，429 只能在接受连接时直接写出
+    let response = read_response(&mut BufReader::new(server.connect()));
+    assert_eq!(response.status, 429);
+    assert_eq!(response.header("retry-after"), Some("1"));
+    assert_eq!(response.header("connection"), Some("close"));
+    drop(idle);
+}
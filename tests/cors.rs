@@ -0,0 +1,118 @@
+mod common;
+
+use common::{request, Server};
+
+const PREFLIGHT: &[&str] = &[
+    "Origin: https://app.example",
+    "Access-Control-Request-Method: POST",
+    "Access-Control-Request-Headers: content-type, x-trace",
+];
+
+fn options(server: &Server, path: &str, headers: &[&str]) -> common::Response {
+    server.send(request("OPTIONS", path, headers, b"").as_bytes())
+}
+
+#[test]
+fn options_lists_the_methods_of_the_path() {
+    let server = Server::start(&[]);
+
+    let response = options(&server, "/api/upload", &[]);
+    assert_eq!(response.status, 204);
+    assert_eq!(response.header("allow"), Some("POST, OPTIONS"));
+    assert_eq!(response.header("access-control-allow-origin"), None);
+
+    assert_eq!(options(&server, "/missing.html", &[]).status, 404);
+}
+
+#[test]
+fn preflight_from_an_allowed_origin_is_answered() {
+    let server = Server::start(&["--cors-origin", "https://app.example,https://admin.example"]);
+
+    let response = options(&server, "/api/upload", PREFLIGHT);
+    assert_eq!(response.status, 204);
+    assert_eq!(
+        response.header("access-control-allow-origin"),
+        Some("https://app.example")
+    );
+    assert_eq!(
+        response.header("access-control-allow-methods"),
+        Some("POST, OPTIONS")
+    );
+    assert_eq!(
+        response.header("access-control-allow-headers"),
+        Some("content-type, x-trace")
+    );
+    assert_eq!(response.header("access-control-max-age"), Some("600"));
+    assert_eq!(response.header("vary"), Some("Origin"));
+}
+
+#[test]
+fn preflight_from_another_origin_gets_no_cors_headers() {
+    let server = Server::start(&["--cors-origin", "https://app.example"]);
+
+    let response = options(
+        &server,
+        "/api/upload",
+        &[
+            "Origin: https://evil.example",
+            "Access-Control-Request-Method: POST",
+        ],
+    );
+    assert_eq!(response.status, 204);
+    assert_eq!(response.header("access-control-allow-origin"), None);
+    assert_eq!(response.header("access-control-allow-methods"), None);
+}
+
+#[test]
+fn configured_methods_and_max_age_replace_the_defaults() {
+    let server = Server::start(&[
+        "--cors",
+        "--cors-methods",
+        "get, post",
+        "--cors-max-age",
+        "60",
+    ]);
+
+    let response = options(&server, "/api/upload", PREFLIGHT);
+    assert_eq!(response.header("access-control-allow-origin"), Some("*"));
+    assert_eq!(
+        response.header("access-control-allow-methods"),
+        Some("GET, POST")
+    );
+    assert_eq!(response.header("access-control-max-age"), Some("60"));
+    assert_eq!(response.header("vary"), None);
+}
+
+#[test]
+fn preflight_skips_authentication_but_requests_do_not() {
+    let server = Server::start_with(&["--cors", "--auth-file", "users.txt"], |dir| {
+        let hash = bcrypt::hash("secret", 4).unwrap();
+        std::fs::write(dir.join("users.txt"), format!("alice:{}\n", hash)).unwrap();
+    });
+
+    let response = options(&server, "/api/upload", PREFLIGHT);
+    assert_eq!(response.status, 204);
+    assert_eq!(response.header("access-control-allow-origin"), Some("*"));
+
+    let response = server.get("/api/list", &["Origin: https://app.example"]);
+    assert_eq!(response.status, 401);
+}
+
+#[test]
+fn simple_requests_get_the_allowed_origin() {
+    let server = Server::start(&["--cors-origin", "https://app.example"]);
+
+    let response = server.get("/api/list", &["Origin: https://app.example"]);
+    assert_eq!(response.status, 200);
+    assert_eq!(
+        response.header("access-control-allow-origin"),
+        Some("https://app.example")
+    );
+
+    let response = server.get("/api/list", &["Origin: https://evil.example"]);
+    assert_eq!(response.status, 200);
+    assert_eq!(response.header("access-control-allow-origin"), None);
+
+    let response = server.get("/api/list", &[]);
+    assert_eq!(response.header("access-control-allow-origin"), None);
+}
@@ -0,0 +1,71 @@
+mod common;
+
+use common::{read_head, Server};
+use std::io::{BufRead, BufReader, Write};
+
+/// 读到第一个空行为止，即第一条 SSE 消息
+fn read_message(reader: &mut impl BufRead) -> String {
+    let mut message = String::new();
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        if line == "\n" || line.is_empty() {
+            return message;
+        }
+        message.push_str(&line);
+    }
+}
+
+#[test]
+fn event_stream_is_chunked_on_http_1_1() {
+    let server = Server::start(&[]);
+    let mut stream = server.connect();
+    stream
+        .write_all(b"GET /api/events HTTP/1.1\r\nHost: localhost\r\n\r\n")
+        .unwrap();
+    let mut reader = BufReader::new(stream);
+
+    let head = read_head(&mut reader);
+    assert_eq!(head.status, 200);
+    assert_eq!(
+        head.header("content-type"),
+        Some("text/event-stream; charset=utf-8")
+    );
+    assert_eq!(head.header("transfer-encoding"), Some("chunked"));
+
+    let mut size = String::new();
+    reader.read_line(&mut size).unwrap();
+    assert_eq!(read_message(&mut reader), ": connected\n");
+}
+
+#[test]
+fn event_stream_is_sent_unbuffered_on_http_1_0() {
+    let server = Server::start(&[]);
+
+    // 事件流没有结尾，不能先读入内存；响应头和第一条消息应当立即到达
+    let mut first = server.connect();
+    first
+        .write_all(b"GET /api/events HTTP/1.0\r\n\r\n")
+        .unwrap();
+    let mut reader = BufReader::new(first);
+
+    let head = read_head(&mut reader);
+    assert_eq!(head.version, "HTTP/1.0");
+    assert_eq!(head.status, 200);
+    assert_eq!(head.header("transfer-encoding"), None);
+    assert_eq!(head.header("content-length"), None);
+    assert_eq!(head.header("connection"), Some("close"));
+    assert_eq!(read_message(&mut reader), ": connected\n");
+}
+
+#[test]
+fn keep_alive_is_refused_for_http_1_0_event_streams() {
+    let server = Server::start(&[]);
+    let mut stream = server.connect();
+    stream
+        .write_all(b"GET /api/events HTTP/1.0\r\nConnection: keep-alive\r\n\r\n")
+        .unwrap();
+
+    let head = read_head(&mut BufReader::new(stream));
+    assert_eq!(head.header("connection"), Some("close"));
+}
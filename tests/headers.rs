@@ -0,0 +1,48 @@
+mod common;
+
+use common::Server;
+use http_server::datetime;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[test]
+fn server_header_defaults_to_the_crate_version() {
+    let server = Server::start(&[]);
+
+    let response = server.get("/", &[]);
+    assert_eq!(
+        response.header("server"),
+        Some(concat!("http-server/", env!("CARGO_PKG_VERSION")))
+    );
+}
+
+#[test]
+fn server_banner_can_be_replaced_or_suppressed() {
+    let server = Server::start(&["--server-banner", "edge/2"]);
+    assert_eq!(server.get("/", &[]).header("server"), Some("edge/2"));
+    // 错误响应同样带有 Server 头
+    assert_eq!(server.get("/missing", &[]).header("server"), Some("edge/2"));
+
+    let server = Server::start(&["--server-banner", ""]);
+    let response = server.get("/", &[]);
+    assert_eq!(response.status, 200);
+    assert_eq!(response.header("server"), None);
+    assert_eq!(server.get("/missing", &[]).header("server"), None);
+}
+
+#[test]
+fn date_header_is_the_current_http_date() {
+    let server = Server::start(&[]);
+
+    for path in ["/", "/missing", "/api/list"] {
+        // Date 只精确到秒，发送请求前的时间向下取整后不应晚于它
+        let before = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+        let before = UNIX_EPOCH + Duration::from_secs(before.as_secs());
+        let response = server.get(path, &[]);
+        let now = SystemTime::now();
+
+        let date = response.header("date").unwrap();
+        assert!(date.ends_with(" GMT"), "{}", date);
+        let sent = datetime::parse_http_date(date).unwrap();
+        assert!(before <= sent && sent <= now, "{}", date);
+    }
+}
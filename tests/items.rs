@@ -0,0 +1,95 @@
+mod common;
+
+use common::{request, Server};
+use serde_json::Value;
+use std::{
+    collections::HashSet,
+    fs,
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+
+const UPLOADS: u64 = 50;
+
+fn upload(server: &Server, id: u64, name: &str) -> common::Response {
+    let body = format!("id={}&name={}", id, name);
+    server.send(
+        request(
+            "POST",
+            "/api/upload",
+            &["Content-Type: application/x-www-form-urlencoded"],
+            body.as_bytes(),
+        )
+        .as_bytes(),
+    )
+}
+
+#[test]
+fn concurrent_uploads_are_all_saved_to_the_data_file() {
+    let server = Arc::new(Server::start_with(&[], |dir| {
+        fs::write(dir.join("data/data.json"), "[]").unwrap();
+    }));
+
+    let uploads: Vec<_> = (1..=UPLOADS)
+        .map(|id| {
+            let server = Arc::clone(&server);
+            thread::spawn(move || (id, upload(&server, id, &format!("item{}", id))))
+        })
+        .collect();
+    for handle in uploads {
+        let (id, response) = handle.join().unwrap();
+        assert_eq!(response.status, 201, "{}", response.text());
+        assert_eq!(
+            response.header("location"),
+            Some(format!("/api/search?id={}", id).as_str())
+        );
+        let record: Value = serde_json::from_slice(&response.body).unwrap();
+        assert_eq!(record["id"], id);
+        assert_eq!(record["name"], format!("item{}", id));
+    }
+
+    let duplicate = upload(&server, 7, "again");
+    assert_eq!(duplicate.status, 409);
+    assert_eq!(duplicate.header("location"), None);
+
+    let content = fs::read(server.dir.path().join("data/data.json")).unwrap();
+    let items: Vec<Value> = serde_json::from_slice(&content).unwrap();
+    assert_eq!(items.len() as u64, UPLOADS);
+    let ids: HashSet<u64> = items
+        .iter()
+        .map(|item| {
+            let id = item["id"].as_u64().unwrap();
+            assert_eq!(item["name"], format!("item{}", id));
+            id
+        })
+        .collect();
+    assert_eq!(ids, (1..=UPLOADS).collect());
+}
+
+/// 数据文件只在启动和文件变化时读取，删掉文件后查询仍然由内存中的数据回答
+#[test]
+fn reads_are_served_from_memory() {
+    let server = Server::start(&[]);
+    let path = server.dir.path().join("data/data.json");
+    let expected = server.get("/api/search?name=foo", &[]).text();
+    assert!(expected.contains("\"id\":1"));
+
+    fs::remove_file(&path).unwrap();
+    for _ in 0..200 {
+        assert_eq!(server.get("/api/search?name=foo", &[]).text(), expected);
+    }
+    assert_eq!(server.get("/api/list", &[]).status, 200);
+
+    // 文件重新出现后，内容在下一次检查时被重新读入
+    fs::write(&path, r#"[{"id":9,"name":"Foo"}]"#).unwrap();
+    let deadline = Instant::now() + Duration::from_secs(10);
+    loop {
+        let response = server.get("/api/search?name=foo", &[]);
+        if response.text() == r#"[{"id":9,"name":"Foo"}]"# {
+            break;
+        }
+        assert!(Instant::now() < deadline, "data file was not reloaded");
+        thread::sleep(Duration::from_millis(50));
+    }
+}
@@ -0,0 +1,206 @@
+mod common;
+
+use common::{read_response, Server};
+use std::{
+    io::{BufReader, Read, Write},
+    time::{Duration, Instant},
+};
+
+fn get(path: &str, extra: &str) -> String {
+    format!("GET {} HTTP/1.1\r\nHost: localhost\r\n{}\r\n", path, extra)
+}
+
+#[test]
+fn two_requests_share_one_connection() {
+    let server = Server::start(&[]);
+    let stream = server.connect();
+    let mut writer = stream.try_clone().unwrap();
+    let mut reader = BufReader::new(stream);
+
+    writer
+        .write_all(get("/hello-world.txt", "").as_bytes())
+        .unwrap();
+    let first = read_response(&mut reader);
+    assert_eq!(first.status, 200);
+    assert_eq!(first.header("connection"), Some("keep-alive"));
+
+    writer
+        .write_all(get("/index.html", "Connection: close\r\n").as_bytes())
+        .unwrap();
+    let second = read_response(&mut reader);
+    assert_eq!(second.status, 200);
+    assert_eq!(second.header("connection"), Some("close"));
+
+    let mut rest = Vec::new();
+    assert_eq!(reader.read_to_end(&mut rest).unwrap(), 0);
+}
+
+#[test]
+fn pipelined_requests_are_answered_in_order() {
+    let server = Server::start(&[]);
+    let stream = server.connect();
+    let mut writer = stream.try_clone().unwrap();
+    let mut reader = BufReader::new(stream);
+
+    let pipeline = [
+        get("/hello-world.txt", ""),
+        get("/missing", ""),
+        get("/index.html", "Connection: close\r\n"),
+    ]
+    .concat();
+    writer.write_all(pipeline.as_bytes()).unwrap();
+
+    let statuses: Vec<u16> = (0..3).map(|_| read_response(&mut reader).status).collect();
+    assert_eq!(statuses, [200, 404, 200]);
+}
+
+#[test]
+fn http_1_0_closes_unless_keep_alive_is_requested() {
+    let server = Server::start(&[]);
+
+    let response = server.send(b"GET / HTTP/1.0\r\n\r\n");
+    assert_eq!(response.header("connection"), Some("close"));
+
+    let stream = server.connect();
+    let mut writer = stream.try_clone().unwrap();
+    let mut reader = BufReader::new(stream);
+    writer
+        .write_all(b"GET / HTTP/1.0\r\nConnection: keep-alive\r\n\r\n")
+        .unwrap();
+    assert_eq!(
+        read_response(&mut reader).header("connection"),
+        Some("keep-alive")
+    );
+    writer.write_all(b"GET / HTTP/1.0\r\n\r\n").unwrap();
+    assert_eq!(read_response(&mut reader).status, 200);
+}
+
+#[test]
+fn silent_new_connections_get_408_after_the_read_timeout() {
+    let server = Server::start(&["--read-timeout", "500"]);
+    let stream = server.connect();
+    let opened = Instant::now();
+
+    let mut reader = BufReader::new(stream);
+    let response = read_response(&mut reader);
+    assert_eq!(response.status, 408);
+    assert_eq!(response.header("connection"), Some("close"));
+    assert!(opened.elapsed() >= Duration::from_millis(400));
+    assert!(opened.elapsed() < Duration::from_secs(5));
+
+    let mut rest = Vec::new();
+    assert_eq!(reader.read_to_end(&mut rest).unwrap(), 0);
+}
+
+#[test]
+fn stalled_request_on_a_kept_alive_connection_gets_408() {
+    let server = Server::start(&["--read-timeout", "500"]);
+    let stream = server.connect();
+    let mut writer = stream.try_clone().unwrap();
+    let mut reader = BufReader::new(stream);
+
+    writer.write_all(get("/", "").as_bytes()).unwrap();
+    assert_eq!(read_response(&mut reader).status, 200);
+
+    // 开始发送第二个请求后停下，读取超时按请求计算
+    writer.write_all(b"GET / HTTP/1.1\r\nHost: loc").unwrap();
+    let response = read_response(&mut reader);
+    assert_eq!(response.status, 408);
+    let mut rest = Vec::new();
+    assert_eq!(reader.read_to_end(&mut rest).unwrap(), 0);
+}
+
+#[test]
+fn idle_connections_are_closed_after_the_timeout() {
+    let server = Server::start(&["--keepalive-timeout", "1"]);
+    let stream = server.connect();
+    let mut writer = stream.try_clone().unwrap();
+    let mut reader = BufReader::new(stream);
+
+    writer.write_all(get("/", "").as_bytes()).unwrap();
+    read_response(&mut reader);
+    let idle = Instant::now();
+
+    // 空闲超时直接关闭，不回复 408
+    let mut rest = Vec::new();
+    assert_eq!(reader.read_to_end(&mut rest).unwrap(), 0);
+    assert!(idle.elapsed() >= Duration::from_millis(900));
+    assert!(idle.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn zero_timeout_disables_keep_alive() {
+    let server = Server::start(&["--keepalive-timeout", "0"]);
+    let mut stream = server.connect();
+    stream.write_all(get("/", "").as_bytes()).unwrap();
+
+    let mut reader = BufReader::new(stream);
+    assert_eq!(
+        read_response(&mut reader).header("connection"),
+        Some("close")
+    );
+    let mut rest = Vec::new();
+    assert_eq!(reader.read_to_end(&mut rest).unwrap(), 0);
+}
+
+#[test]
+fn rejected_requests_close_cleanly_despite_unread_input() {
+    let server = Server::start(&["--max-body-size", "1024"]);
+    let mut stream = server.connect();
+    stream
+        .write_all(b"POST /api/echo HTTP/1.1\r\nHost: localhost\r\nContent-Length: 100000\r\n\r\n")
+        .unwrap();
+    // 服务器没有读取的请求体，关闭前会被丢弃，不会导致连接被重置
+    stream.write_all(&[b'x'; 16 * 1024]).unwrap();
+
+    let mut reader = BufReader::new(stream);
+    let response = read_response(&mut reader);
+    assert_eq!(response.status, 413);
+    assert_eq!(response.header("connection"), Some("close"));
+    let mut rest = Vec::new();
+    assert_eq!(reader.read_to_end(&mut rest).unwrap(), 0);
+}
+
+#[test]
+fn connection_closes_after_the_request_limit() {
+    let server = Server::start(&["--max-keepalive-requests", "2"]);
+    let stream = server.connect();
+    let mut writer = stream.try_clone().unwrap();
+    let mut reader = BufReader::new(stream);
+
+    writer.write_all(get("/", "").as_bytes()).unwrap();
+    assert_eq!(
+        read_response(&mut reader).header("connection"),
+        Some("keep-alive")
+    );
+    writer.write_all(get("/", "").as_bytes()).unwrap();
+    assert_eq!(
+        read_response(&mut reader).header("connection"),
+        Some("close")
+    );
+
+    let mut rest = Vec::new();
+    assert_eq!(reader.read_to_end(&mut rest).unwrap(), 0);
+}
+
+#[test]
+fn rate_limit_counts_requests_on_a_kept_alive_connection() {
+    let server = Server::start(&["--rate-limit", "2"]);
+    let stream = server.connect();
+    let mut writer = stream.try_clone().unwrap();
+    let mut reader = BufReader::new(stream);
+
+    // 每秒 2 个请求，突发 4 个；启动时探测端口的连接和这个连接各消耗一个，
+    // 连接上的第一个请求不再消耗，之后的请求各消耗一个
+    for _ in 0..3 {
+        writer.write_all(get("/", "").as_bytes()).unwrap();
+        assert_eq!(read_response(&mut reader).status, 200);
+    }
+    writer.write_all(get("/", "").as_bytes()).unwrap();
+    let limited = read_response(&mut reader);
+    assert_eq!(limited.status, 429);
+    assert_eq!(limited.header("retry-after"), Some("1"));
+    assert_eq!(limited.header("connection"), Some("close"));
+
+    assert_eq!(server.get("/", &[]).status, 429);
+}
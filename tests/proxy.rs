@@ -0,0 +1,95 @@
+mod common;
+
+use common::{request, Server};
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::TcpListener,
+    sync::mpsc,
+    thread,
+    time::Duration,
+};
+
+/// 只回答一次请求的上游服务器，把收到的请求行发到返回的通道
+fn upstream() -> (String, mpsc::Receiver<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            let _ = sender.send(line.trim_end().to_string());
+            loop {
+                let mut header = String::new();
+                if reader.read_line(&mut header).unwrap() == 0 || header.trim_end().is_empty() {
+                    break;
+                }
+            }
+            stream
+                .write_all(
+                    b"HTTP/1.1 200 OK\r\nContent-Length: 8\r\nConnection: close\r\n\r\nupstream",
+                )
+                .unwrap();
+        }
+    });
+    (url, receiver)
+}
+
+fn start_with_auth(proxy: &str) -> Server {
+    Server::start_with(
+        &[
+            "--proxy",
+            proxy,
+            "--auth-file",
+            "users",
+            "--auth-prefix",
+            "/admin",
+        ],
+        |dir| {
+            let hash = bcrypt::hash("secret", 4).unwrap();
+            std::fs::write(dir.join("users"), format!("alice:{}\n", hash)).unwrap();
+        },
+    )
+}
+
+#[test]
+fn protected_paths_are_authenticated_before_forwarding() {
+    let (url, received) = upstream();
+    let server = start_with_auth(&url);
+
+    for path in [
+        "/admin",
+        "/admin/secret.txt",
+        "//admin/secret.txt",
+        "/x/../admin/secret.txt",
+        "/%61dmin/",
+    ] {
+        let response = server.get(path, &[]);
+        assert_eq!(response.status, 401, "{}", path);
+        assert!(response.header("www-authenticate").is_some());
+    }
+    let wrong = server.get("/admin", &["Authorization: Basic YWxpY2U6d3Jvbmc="]);
+    assert_eq!(wrong.status, 401);
+
+    assert!(received.recv_timeout(Duration::from_millis(200)).is_err());
+}
+
+#[test]
+fn authenticated_and_public_requests_are_forwarded() {
+    let (url, received) = upstream();
+    let server = start_with_auth(&url);
+
+    let response = server.get(
+        "/admin/secret.txt",
+        &["Authorization: Basic YWxpY2U6c2VjcmV0"],
+    );
+    assert_eq!(response.status, 200);
+    assert_eq!(response.text(), "upstream");
+    assert_eq!(received.recv().unwrap(), "GET /admin/secret.txt HTTP/1.1");
+
+    let response = server.send(request("GET", "/public", &[], b"").as_bytes());
+    assert_eq!(response.text(), "upstream");
+    assert_eq!(received.recv().unwrap(), "GET /public HTTP/1.1");
+}
@@ -0,0 +1,96 @@
+mod common;
+
+use common::Server;
+
+fn start() -> (Server, Vec<u8>) {
+    let video: Vec<u8> = (0..=255u8).cycle().take(10_000).collect();
+    let server = Server::start_with(&[], |dir| {
+        std::fs::write(dir.join("static/clip.mp4"), &video).unwrap();
+    });
+    (server, video)
+}
+
+#[test]
+fn full_responses_advertise_byte_ranges() {
+    let (server, video) = start();
+
+    let response = server.get("/clip.mp4", &[]);
+
+    assert_eq!(response.status, 200);
+    assert_eq!(response.header("accept-ranges"), Some("bytes"));
+    assert_eq!(response.body, video);
+}
+
+#[test]
+fn single_ranges_get_206_with_the_slice() {
+    let (server, video) = start();
+
+    let cases = [
+        ("bytes=0-99", 0, 99),
+        ("bytes=9900-", 9900, 9999),
+        ("bytes=-10", 9990, 9999),
+        ("bytes=5000-20000", 5000, 9999),
+    ];
+    for (range, start, end) in cases {
+        let response = server.get("/clip.mp4", &[&format!("Range: {}", range)]);
+        assert_eq!(response.status, 206, "{}", range);
+        assert_eq!(
+            response.header("content-range"),
+            Some(format!("bytes {}-{}/10000", start, end).as_str())
+        );
+        assert_eq!(
+            response.header("content-length"),
+            Some((end - start + 1).to_string().as_str())
+        );
+        assert_eq!(response.body, &video[start..=end]);
+    }
+}
+
+#[test]
+fn ranges_past_the_end_get_416() {
+    let (server, _) = start();
+
+    for range in ["bytes=10000-", "bytes=20000-20010", "bytes=-0"] {
+        let response = server.get("/clip.mp4", &[&format!("Range: {}", range)]);
+        assert_eq!(response.status, 416, "{}", range);
+        assert_eq!(response.header("content-range"), Some("bytes */10000"));
+    }
+}
+
+#[test]
+fn multiple_or_malformed_ranges_get_the_full_body() {
+    let (server, video) = start();
+
+    for range in ["bytes=0-1,5-6", "bytes=abc", "lines=1-2"] {
+        let response = server.get("/clip.mp4", &[&format!("Range: {}", range)]);
+        assert_eq!(response.status, 200, "{}", range);
+        assert_eq!(response.body, video);
+    }
+}
+
+/// 播放器拖动进度时在同一个连接上连续请求不同区间
+#[test]
+fn consecutive_ranges_on_one_connection() {
+    use std::io::{BufReader, Write};
+
+    let (server, video) = start();
+    let mut stream = server.connect();
+    let ranges = ["bytes=0-", "bytes=4096-8191", "bytes=-100"];
+    for range in ranges {
+        let request = format!("GET /clip.mp4 HTTP/1.1\r\nRange: {}\r\n\r\n", range);
+        stream.write_all(request.as_bytes()).unwrap();
+    }
+    let mut reader = BufReader::new(stream);
+
+    for (range, (start, end)) in ranges.iter().zip([(0, 9999), (4096, 8191), (9900, 9999)]) {
+        let response = common::read_response(&mut reader);
+        assert_eq!(response.status, 206, "{}", range);
+        assert_eq!(response.header("accept-ranges"), Some("bytes"));
+        assert_eq!(response.header("content-type"), Some("video/mp4"));
+        assert_eq!(
+            response.header("content-range"),
+            Some(format!("bytes {}-{}/10000", start, end).as_str())
+        );
+        assert_eq!(response.body, &video[start..=end]);
+    }
+}
@@ -0,0 +1,344 @@
+mod common;
+
+use common::{request, Server};
+
+#[test]
+fn long_headers_are_read_completely() {
+    let server = Server::start(&[]);
+    let cookie = format!("Cookie: session={}", "a".repeat(3 * 1024));
+
+    let response = server.get("/hello-world.txt", &[&cookie]);
+
+    assert_eq!(response.status, 200);
+    assert_eq!(
+        response.body,
+        std::fs::read(server.dir.path().join("static/hello-world.txt")).unwrap()
+    );
+}
+
+#[test]
+fn headers_over_the_limit_get_431() {
+    let server = Server::start(&[]);
+    let cookie = format!("Cookie: session={}", "a".repeat(20 * 1024));
+
+    let response = server.get("/hello-world.txt", &[&cookie]);
+
+    assert_eq!(response.status, 431);
+}
+
+#[test]
+fn header_limit_is_configurable() {
+    let server = Server::start(&["--max-header-size", "1024"]);
+    let cookie = format!("Cookie: session={}", "a".repeat(2 * 1024));
+
+    assert_eq!(server.get("/", &[&cookie]).status, 431);
+    assert_eq!(server.get("/", &[]).status, 200);
+}
+
+#[test]
+fn request_split_across_writes_is_reassembled() {
+    use std::io::{BufReader, Write};
+
+    let server = Server::start(&[]);
+    let mut stream = server.connect();
+    let raw = request("GET", "/hello-world.txt", &["X-Padding: 1"], b"");
+    for chunk in raw.as_bytes().chunks(7) {
+        stream.write_all(chunk).unwrap();
+        stream.flush().unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(2));
+    }
+
+    let response = common::read_response(&mut BufReader::new(stream));
+    assert_eq!(response.status, 200);
+}
+
+#[test]
+fn json_upload_is_echoed_intact() {
+    let server = Server::start(&[]);
+    let items: Vec<String> = (0..400)
+        .map(|i| format!(r#"{{"id":{},"name":"item number {}"}}"#, i, i))
+        .collect();
+    let body = format!("[{}]", items.join(","));
+    assert!(body.len() > 10 * 1024);
+
+    let response = server.send(
+        request(
+            "POST",
+            "/api/upload",
+            &["Content-Type: application/json"],
+            body.as_bytes(),
+        )
+        .as_bytes(),
+    );
+
+    assert_eq!(response.status, 200);
+    assert_eq!(response.text(), body);
+}
+
+#[test]
+fn body_arriving_after_the_headers_is_waited_for() {
+    use std::io::{BufReader, Write};
+
+    let server = Server::start(&[]);
+    let body = vec![b'x'; 64 * 1024];
+    let mut stream = server.connect();
+    let head = format!(
+        "POST /api/echo HTTP/1.1\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        body.len()
+    );
+    stream.write_all(head.as_bytes()).unwrap();
+    for chunk in body.chunks(8 * 1024) {
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        stream.write_all(chunk).unwrap();
+    }
+
+    let response = common::read_response(&mut BufReader::new(stream));
+    assert_eq!(response.status, 200);
+    assert_eq!(response.body, body);
+}
+
+fn echo(server: &Server, body: &[u8]) -> common::Response {
+    server.send(request("POST", "/api/echo", &[], body).as_bytes())
+}
+
+#[test]
+fn bodies_up_to_the_limit_are_read_exactly() {
+    let server = Server::start(&["--max-body-size", "4096"]);
+
+    for size in [0, 1, 1023, 1024, 1025, 4096] {
+        let body: Vec<u8> = (b'a'..=b'z').cycle().take(size).collect();
+        let response = echo(&server, &body);
+        assert_eq!(response.status, 200, "{} bytes", size);
+        assert_eq!(response.body, body, "{} bytes", size);
+    }
+}
+
+#[test]
+fn bodies_over_the_limit_get_413() {
+    let server = Server::start(&["--max-body-size", "4096"]);
+
+    assert_eq!(echo(&server, &[b'x'; 4097]).status, 413);
+
+    // 只声明长度、不发送请求体也会被拒绝，请求体不会被读取
+    let response = server.send(
+        b"POST /api/echo HTTP/1.1\r\nContent-Length: 1000000000\r\nConnection: close\r\n\r\n",
+    );
+    assert_eq!(response.status, 413);
+}
+
+#[test]
+fn post_without_content_length_gets_400() {
+    let server = Server::start(&[]);
+
+    let response = server.send(b"POST /api/echo HTTP/1.1\r\nConnection: close\r\n\r\nhello");
+    assert_eq!(response.status, 400);
+
+    let response = server
+        .send(b"POST /api/echo HTTP/1.1\r\nContent-Length: ten\r\nConnection: close\r\n\r\nhello");
+    assert_eq!(response.status, 400);
+}
+
+#[test]
+fn chunked_with_content_length_gets_400_and_closes() {
+    let server = Server::start(&[]);
+
+    // 不带 Connection: close，服务器也必须在 400 之后关闭连接，不把剩余字节当作下一个请求
+    let response = server.send(
+        b"POST /api/echo HTTP/1.1\r\nTransfer-Encoding: chunked\r\nContent-Length: 4\r\n\r\n\
+          0\r\n\r\nGET / HTTP/1.1\r\n\r\n",
+    );
+    assert_eq!(response.status, 400);
+    assert_eq!(response.header("connection"), Some("close"));
+}
+
+#[test]
+fn garbage_gets_400_and_the_server_keeps_serving() {
+    let server = Server::start(&[]);
+
+    let response = server.send(b"GARBAGE\r\n\r\n");
+    assert_eq!(response.status, 400);
+    assert!(response.text().contains("400"));
+
+    assert_eq!(server.get("/", &[]).status, 200);
+}
+
+/// 无法解析的请求得到 HTML 格式的 400，连接随后关闭；服务器不受影响
+#[test]
+fn junk_requests_get_an_html_400_and_a_closed_connection() {
+    use std::io::{BufReader, Read, Write};
+
+    let server = Server::start(&[]);
+    let junk: [&[u8]; 4] = [
+        b"GARBAGE\r\n\r\n",
+        b"GET\r\n\r\n",
+        b"GET / HTTP/1.1\r\nno colon here\r\n\r\n",
+        b"\xff\xfe\x00\x01\r\n\r\n",
+    ];
+    for _ in 0..5 {
+        for request in junk {
+            let mut stream = server.connect();
+            stream.write_all(request).unwrap();
+            let mut reader = BufReader::new(stream);
+
+            let response = common::read_response(&mut reader);
+            assert_eq!(response.status, 400, "{:?}", request);
+            assert_eq!(
+                response.header("content-type"),
+                Some("text/html; charset=utf-8")
+            );
+            assert_eq!(response.header("connection"), Some("close"));
+            let mut rest = Vec::new();
+            reader.read_to_end(&mut rest).unwrap();
+            assert!(rest.is_empty());
+        }
+    }
+
+    assert_eq!(server.get("/", &[]).status, 200);
+}
+
+#[test]
+fn connect_and_disconnect_is_ignored() {
+    let server = Server::start(&[]);
+
+    for _ in 0..5 {
+        drop(server.connect());
+    }
+
+    assert_eq!(server.get("/", &[]).status, 200);
+}
+
+#[test]
+fn unsupported_versions_get_505() {
+    let server = Server::start(&[]);
+
+    assert_eq!(server.send(b"GET / HTTP/2.0\r\n\r\n").status, 505);
+    assert_eq!(server.send(b"GET / HTTP/x\r\n\r\n").status, 400);
+    assert_eq!(server.send(b"GET /\r\n\r\n").status, 400);
+}
+
+/// 发送请求头，先读到 `100 Continue` 再发送请求体
+#[test]
+fn expect_continue_gets_100_before_the_body_is_sent() {
+    use std::io::{BufReader, Write};
+
+    let server = Server::start(&[]);
+    let mut stream = server.connect();
+    stream
+        .write_all(
+            b"POST /api/echo HTTP/1.1\r\nContent-Length: 5\r\nExpect: 100-continue\r\n\
+              Connection: close\r\n\r\n",
+        )
+        .unwrap();
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+    let interim = common::read_head(&mut reader);
+    assert_eq!(interim.version, "HTTP/1.1");
+    assert_eq!(interim.status, 100);
+    assert!(interim.headers.is_empty());
+
+    stream.write_all(b"hello").unwrap();
+    let response = common::read_response(&mut reader);
+    assert_eq!(response.status, 200);
+    assert_eq!(response.text(), "hello");
+}
+
+/// 路由前先解码路径，编码过的路径同样得到 `100 Continue`
+#[test]
+fn expect_continue_matches_routes_on_the_decoded_path() {
+    use std::io::{BufReader, Write};
+
+    let server = Server::start(&[]);
+    let mut stream = server.connect();
+    stream
+        .write_all(
+            b"POST /api/%75pload HTTP/1.1\r\nContent-Type: application/json\r\n\
+              Content-Length: 9\r\nExpect: 100-continue\r\nConnection: close\r\n\r\n",
+        )
+        .unwrap();
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+    assert_eq!(common::read_head(&mut reader).status, 100);
+
+    stream.write_all(br#"{"id":11}"#).unwrap();
+    let response = common::read_response(&mut reader);
+    assert_eq!(response.status, 200);
+    assert_eq!(response.text(), r#"{"id":11}"#);
+}
+
+/// 不会被接受的请求直接得到最终响应，客户端不必发送请求体
+#[test]
+fn rejected_expect_continue_requests_get_no_100() {
+    use std::io::{BufReader, Write};
+
+    let server = Server::start(&["--max-body-size", "16"]);
+    for (head, status) in [
+        (
+            "POST /api/echo HTTP/1.1\r\nContent-Length: 1000\r\nExpect: 100-continue\r\n\r\n",
+            413,
+        ),
+        (
+            "POST /missing HTTP/1.1\r\nContent-Length: 5\r\nExpect: 100-continue\r\n\r\n",
+            404,
+        ),
+        (
+            "POST /index.html HTTP/1.1\r\nContent-Length: 5\r\nExpect: 100-continue\r\n\r\n",
+            405,
+        ),
+        (
+            "POST /%69ndex.html HTTP/1.1\r\nContent-Length: 5\r\nExpect: 100-continue\r\n\r\n",
+            405,
+        ),
+        (
+            "POST /api/echo HTTP/1.1\r\nContent-Length: 5\r\nExpect: 200-ok\r\n\r\n",
+            417,
+        ),
+    ] {
+        let mut stream = server.connect();
+        stream.write_all(head.as_bytes()).unwrap();
+
+        let response = common::read_response(&mut BufReader::new(stream));
+        assert_eq!(response.status, status, "{}", head);
+        assert_eq!(response.header("connection"), Some("close"));
+    }
+}
+
+#[test]
+fn get_and_post_are_routed_and_other_methods_get_501() {
+    use std::io::{Read, Write};
+
+    let server = Server::start(&[]);
+
+    let response = server.get("/", &[]);
+    assert_eq!(response.status, 200);
+    assert_eq!(
+        response.body,
+        std::fs::read(server.dir.path().join("static/index.html")).unwrap()
+    );
+
+    let response = echo(&server, b"ping");
+    assert_eq!(response.status, 200);
+    assert_eq!(response.text(), "ping");
+
+    for (method, path) in [
+        ("DELETE", "/index.html"),
+        ("DELETE", "/missing"),
+        ("PUT", "/api/upload"),
+        ("PATCH", "/"),
+        ("TRACE", "/"),
+    ] {
+        let response = server.send(request(method, path, &[], b"").as_bytes());
+        assert_eq!(response.status, 501, "{} {}", method, path);
+    }
+
+    let mut stream = server.connect();
+    stream
+        .write_all(b"DELETE / HTTP/1.1\r\nConnection: close\r\n\r\n")
+        .unwrap();
+    let mut raw = Vec::new();
+    stream.read_to_end(&mut raw).unwrap();
+    assert!(raw.starts_with(b"HTTP/1.1 501 Not Implemented\r\n"));
+
+    // 注册了 DELETE 的路由照常处理
+    let response = server.send(request("DELETE", "/api/items/2", &[], b"").as_bytes());
+    assert_eq!(response.status, 204);
+}
@@ -0,0 +1,65 @@
+#![cfg(unix)]
+
+mod common;
+
+use common::{read_response, Server};
+use std::{
+    io::{BufReader, Write},
+    net::TcpStream,
+    process::{Command, ExitStatus},
+    thread,
+    time::{Duration, Instant},
+};
+
+fn sigterm(server: &Server) {
+    let status = Command::new("kill")
+        .args(["-TERM", &server.child.id().to_string()])
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+/// 等待服务器进程退出，超时时测试失败
+fn wait_for_exit(server: &mut Server) -> ExitStatus {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    loop {
+        if let Some(status) = server.child.try_wait().unwrap() {
+            return status;
+        }
+        assert!(Instant::now() < deadline, "server did not exit");
+        thread::sleep(Duration::from_millis(20));
+    }
+}
+
+#[test]
+fn idle_server_exits_cleanly_on_sigterm() {
+    let mut server = Server::start(&[]);
+    assert_eq!(server.get("/", &[]).status, 200);
+
+    sigterm(&server);
+
+    assert!(wait_for_exit(&mut server).success());
+    assert!(TcpStream::connect(server.addr).is_err());
+}
+
+#[test]
+fn in_flight_request_finishes_before_exit() {
+    let mut server = Server::start(&[]);
+    let mut stream = server.connect();
+    stream
+        .write_all(b"POST /api/echo HTTP/1.1\r\nHost: localhost\r\nContent-Length: 5\r\n\r\n")
+        .unwrap();
+    thread::sleep(Duration::from_millis(200));
+
+    sigterm(&server);
+    thread::sleep(Duration::from_millis(200));
+    assert!(server.child.try_wait().unwrap().is_none());
+
+    stream.write_all(b"hello").unwrap();
+    let response = read_response(&mut BufReader::new(stream));
+    assert_eq!(response.status, 200);
+    assert_eq!(response.text(), "hello");
+    assert_eq!(response.header("connection"), Some("close"));
+
+    assert!(wait_for_exit(&mut server).success());
+}
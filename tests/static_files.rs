@@ -0,0 +1,278 @@
+mod common;
+
+use common::{request, Server};
+use std::io::{BufReader, Read, Write};
+
+#[test]
+fn percent_encoded_paths_are_decoded_once() {
+    let server = Server::start_with(&[], |dir| {
+        std::fs::write(dir.join("static/my file.txt"), "space").unwrap();
+        std::fs::write(dir.join("static/café.txt"), "utf-8").unwrap();
+        std::fs::write(dir.join("static/a+b.txt"), "plus").unwrap();
+    });
+
+    assert_eq!(server.get("/my%20file.txt", &[]).text(), "space");
+    assert_eq!(server.get("/caf%C3%A9.txt", &[]).text(), "utf-8");
+    assert_eq!(server.get("/a+b.txt", &[]).text(), "plus");
+    assert_eq!(server.get("/hello-world%2Etxt", &[]).status, 200);
+    assert_eq!(server.get("/my%2520file.txt", &[]).status, 404);
+}
+
+#[test]
+fn malformed_encodings_get_400() {
+    let server = Server::start(&[]);
+
+    for path in ["/hello%GZ.txt", "/hello%2", "/%FF.txt", "/test%2Ftest.html"] {
+        assert_eq!(server.get(path, &[]).status, 400, "{}", path);
+    }
+}
+
+#[test]
+fn encoded_question_mark_stays_in_the_path() {
+    let server = Server::start(&[]);
+
+    assert_eq!(server.get("/index.html%3Fjunk", &[]).status, 404);
+    assert_eq!(server.get("/hello-world.txt%3F", &[]).status, 404);
+    assert_eq!(server.get("/hello-world.txt?%3F", &[]).status, 200);
+}
+
+#[test]
+fn traversal_outside_the_root_gets_403() {
+    let server = Server::start(&[]);
+
+    for path in [
+        "/..",
+        "/../Cargo.toml",
+        "/%2e%2e/",
+        "/%2E%2E/data/data.json",
+        "/foo/../../etc/passwd",
+        "/test/../../static/index.html",
+        "/index.html%00.txt",
+        "/..%5C..%5Cetc%5Cpasswd",
+    ] {
+        assert_eq!(server.get(path, &[]).status, 403, "{}", path);
+    }
+}
+
+#[test]
+fn dot_segments_inside_the_root_are_resolved() {
+    let server = Server::start(&[]);
+    let index = std::fs::read(server.dir.path().join("static/index.html")).unwrap();
+
+    for path in [
+        "/test/../index.html",
+        "/./index.html",
+        "/test/%2E%2E/index.html",
+    ] {
+        let response = server.get(path, &[]);
+        assert_eq!(response.status, 200, "{}", path);
+        assert_eq!(response.body, index);
+    }
+}
+
+#[test]
+fn binary_files_are_served_byte_for_byte() {
+    // PNG 签名之后是不合法的 UTF-8 和 NUL 字节
+    let png: Vec<u8> = b"\x89PNG\r\n\x1a\n"
+        .iter()
+        .copied()
+        .chain((0..=255u8).cycle().take(4096))
+        .collect();
+    let server = Server::start_with(&[], |dir| {
+        std::fs::write(dir.join("static/logo.png"), &png).unwrap();
+        std::fs::write(dir.join("static/app.wasm"), b"\0asm\x01\0\0\0").unwrap();
+    });
+
+    let response = server.get("/logo.png", &[]);
+    assert_eq!(response.status, 200);
+    assert_eq!(response.header("content-type"), Some("image/png"));
+    assert_eq!(response.body, png);
+
+    let response = server.get("/app.wasm", &[]);
+    assert_eq!(response.header("content-type"), Some("application/wasm"));
+    assert_eq!(response.body, b"\0asm\x01\0\0\0");
+}
+
+#[test]
+fn every_binary_type_is_served_with_its_content_type() {
+    const TYPES: [(&str, &str); 13] = [
+        ("png", "image/png"),
+        ("jpg", "image/jpeg"),
+        ("gif", "image/gif"),
+        ("webp", "image/webp"),
+        ("svg", "image/svg+xml; charset=utf-8"),
+        ("ico", "image/x-icon"),
+        ("wasm", "application/wasm"),
+        ("pdf", "application/pdf"),
+        ("woff", "font/woff"),
+        ("woff2", "font/woff2"),
+        ("ttf", "font/ttf"),
+        ("mp4", "video/mp4"),
+        ("webm", "video/webm"),
+    ];
+    // 所有字节取值都出现，内容不是合法的 UTF-8
+    let contents: Vec<u8> = (0..=255u8).rev().cycle().take(1000).collect();
+    let server = Server::start_with(&[], |dir| {
+        for (extension, _) in TYPES {
+            std::fs::write(dir.join(format!("static/file.{}", extension)), &contents).unwrap();
+        }
+    });
+
+    for (extension, content_type) in TYPES {
+        let response = server.get(&format!("/file.{}", extension), &[]);
+        assert_eq!(response.status, 200, "{}", extension);
+        assert_eq!(response.header("content-type"), Some(content_type));
+        assert_eq!(response.header("content-length"), Some("1000"));
+        assert_eq!(response.body, contents, "{}", extension);
+    }
+}
+
+#[test]
+fn configured_mime_types_are_used() {
+    let server = Server::start_with(&["--mime-type", "md=text/markdown"], |dir| {
+        std::fs::write(dir.join("static/notes.md"), "# Notes").unwrap();
+        std::fs::write(dir.join("static/blob"), [0u8, 1, 2]).unwrap();
+    });
+
+    assert_eq!(
+        server.get("/notes.md", &[]).header("content-type"),
+        Some("text/markdown; charset=utf-8")
+    );
+    assert_eq!(
+        server.get("/blob", &[]).header("content-type"),
+        Some("application/octet-stream")
+    );
+}
+
+#[test]
+fn utf8_html_pages_declare_their_charset() {
+    let page = "<!DOCTYPE html>\n<html lang=\"zh\"><head><title>你好，世界</title></head>\
+                <body><p>日本語のテキスト · 한국어 — ✓</p></body></html>\n";
+    let server = Server::start_with(&[], |dir| {
+        std::fs::write(dir.join("static/中文.html"), page).unwrap();
+    });
+
+    let response = server.get("/%E4%B8%AD%E6%96%87.html", &[]);
+    assert_eq!(response.status, 200);
+    assert_eq!(
+        response.header("content-type"),
+        Some("text/html; charset=utf-8")
+    );
+    assert_eq!(
+        response.header("content-length"),
+        Some(page.len().to_string().as_str())
+    );
+    assert_eq!(response.body, page.as_bytes());
+}
+
+#[test]
+fn files_over_the_threshold_are_sent_in_64k_chunks() {
+    const THRESHOLD: usize = 1024 * 1024;
+    let large: Vec<u8> = (0..=255u8).cycle().take(3 * THRESHOLD + 100).collect();
+    let server = Server::start_with(&[], |dir| {
+        std::fs::write(dir.join("static/large.bin"), &large).unwrap();
+        std::fs::write(dir.join("static/limit.bin"), &large[..THRESHOLD]).unwrap();
+    });
+
+    let response = server.get("/large.bin", &[]);
+    assert_eq!(response.status, 200);
+    assert_eq!(response.header("transfer-encoding"), Some("chunked"));
+    assert_eq!(response.header("content-length"), None);
+    assert!(response.header("etag").is_some());
+    assert_eq!(response.body, large);
+
+    // 每块 64 KB，最后是不满一块的余下部分和结束块
+    let mut stream = server.connect();
+    stream
+        .write_all(request("GET", "/large.bin", &[], b"").as_bytes())
+        .unwrap();
+    let mut raw = Vec::new();
+    stream.read_to_end(&mut raw).unwrap();
+    let body_start = raw.windows(4).position(|w| w == b"\r\n\r\n").unwrap() + 4;
+    let mut chunks = &raw[body_start..];
+    let mut sizes = Vec::new();
+    loop {
+        let line_end = chunks.windows(2).position(|w| w == b"\r\n").unwrap();
+        let size =
+            usize::from_str_radix(std::str::from_utf8(&chunks[..line_end]).unwrap(), 16).unwrap();
+        sizes.push(size);
+        chunks = &chunks[line_end + 2 + size..];
+        assert!(chunks.starts_with(b"\r\n"));
+        chunks = &chunks[2..];
+        if size == 0 {
+            break;
+        }
+    }
+    assert!(chunks.is_empty());
+    assert_eq!(sizes.len(), 3 * 16 + 2);
+    assert!(sizes[..48].iter().all(|&size| size == 64 * 1024));
+    assert_eq!(sizes[48..], [100, 0]);
+
+    // HEAD 不发送响应体，仍然给出文件的 Content-Length
+    let mut stream = server.connect();
+    stream
+        .write_all(request("HEAD", "/large.bin", &[], b"").as_bytes())
+        .unwrap();
+    let mut reader = BufReader::new(stream);
+    let head = common::read_head(&mut reader);
+    assert_eq!(head.status, 200);
+    assert_eq!(
+        head.header("content-length"),
+        Some(large.len().to_string().as_str())
+    );
+    assert_eq!(head.header("transfer-encoding"), None);
+    let mut rest = Vec::new();
+    reader.read_to_end(&mut rest).unwrap();
+    assert!(rest.is_empty());
+
+    // 不超过阈值的文件仍然带 Content-Length，Range 请求也不受影响
+    let response = server.get("/limit.bin", &[]);
+    assert_eq!(response.header("content-length"), Some("1048576"));
+    assert_eq!(response.body, &large[..THRESHOLD]);
+    let response = server.get("/large.bin", &["Range: bytes=-100"]);
+    assert_eq!(response.status, 206);
+    assert_eq!(response.header("content-length"), Some("100"));
+    assert_eq!(response.body, &large[large.len() - 100..]);
+}
+
+#[test]
+fn large_files_are_served_whole_with_their_content_length() {
+    use std::hash::{DefaultHasher, Hash, Hasher};
+
+    fn hash(bytes: &[u8]) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        bytes.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    // 50 MB 的伪随机内容，错位或者漏掉的块都会改变哈希
+    let mut state = 1u32;
+    let large: Vec<u8> = (0..50 * 1024 * 1024)
+        .map(|_| {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            (state >> 16) as u8
+        })
+        .collect();
+    let server = Server::start_with(&[], |dir| {
+        std::fs::write(dir.join("static/video.bin"), &large).unwrap();
+    });
+    let length = large.len().to_string();
+
+    // HTTP/1.0 不能分块，整个文件按 Content-Length 发送
+    let response = server.send(b"GET /video.bin HTTP/1.0\r\n\r\n");
+    assert_eq!(response.status, 200);
+    assert_eq!(response.header("content-length"), Some(length.as_str()));
+    assert_eq!(hash(&response.body), hash(&large));
+
+    let response = server.get("/video.bin", &[]);
+    assert_eq!(response.status, 200);
+    assert_eq!(hash(&response.body), hash(&large));
+
+    let response = server.get("/video.bin", &["Range: bytes=1000-"]);
+    assert_eq!(response.status, 206);
+    assert_eq!(
+        response.header("content-length"),
+        Some((large.len() - 1000).to_string().as_str())
+    );
+    assert_eq!(hash(&response.body), hash(&large[1000..]));
+}
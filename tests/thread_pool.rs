@@ -0,0 +1,242 @@
+use http_server::{
+    handle_connection, Logger, MiddlewareStack, Response, Router, ServerConfig, ThreadPool,
+    ThreadPoolError,
+};
+use std::{
+    collections::HashSet,
+    io::{self, Read, Write},
+    net::{TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc, Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+#[test]
+fn worker_survives_a_panicking_job() {
+    let pool = ThreadPool::new(1);
+    let (sender, receiver) = mpsc::channel();
+
+    pool.execute(|| panic!("deliberate panic")).unwrap();
+    pool.execute(move || sender.send("still running").unwrap())
+        .unwrap();
+
+    assert_eq!(
+        receiver.recv_timeout(Duration::from_secs(5)),
+        Ok("still running")
+    );
+    assert_eq!(pool.workers(), 1);
+}
+
+#[test]
+fn dropping_the_pool_after_panics_does_not_panic() {
+    let pool = ThreadPool::new(2);
+    for i in 0..4 {
+        pool.execute(move || panic!("job {} failed", i)).unwrap();
+    }
+    drop(pool);
+}
+
+/// 提交 `count` 个任务，每个任务等到所有任务都开始运行或者超时，返回同时运行的任务是否达到 `count` 个
+fn run_concurrently(pool: &ThreadPool, count: usize) -> bool {
+    let started = Arc::new(AtomicUsize::new(0));
+    let (sender, receiver) = mpsc::channel();
+    for _ in 0..count {
+        let started = Arc::clone(&started);
+        let sender = sender.clone();
+        pool.execute(move || {
+            started.fetch_add(1, Ordering::SeqCst);
+            let deadline = Instant::now() + Duration::from_secs(2);
+            while started.load(Ordering::SeqCst) < count && Instant::now() < deadline {
+                thread::sleep(Duration::from_millis(1));
+            }
+            sender
+                .send(started.load(Ordering::SeqCst) >= count)
+                .unwrap();
+        })
+        .unwrap();
+    }
+    (0..count).all(|_| receiver.recv_timeout(Duration::from_secs(10)).unwrap())
+}
+
+#[test]
+fn growing_adds_workers_that_take_jobs() {
+    let pool = ThreadPool::new(1);
+
+    pool.set_workers(4).unwrap();
+
+    assert_eq!(pool.workers(), 4);
+    assert!(run_concurrently(&pool, 4));
+}
+
+#[test]
+fn shrinking_lets_in_flight_jobs_finish() {
+    let pool = ThreadPool::new(4);
+    let finished = Arc::new(AtomicUsize::new(0));
+    for _ in 0..8 {
+        let finished = Arc::clone(&finished);
+        pool.execute(move || {
+            thread::sleep(Duration::from_millis(50));
+            finished.fetch_add(1, Ordering::SeqCst);
+        })
+        .unwrap();
+    }
+
+    pool.set_workers(1).unwrap();
+    assert_eq!(pool.workers(), 1);
+
+    // 退出消息排在之前的任务后面，之后的任务都由剩下的一个线程处理
+    let (sender, receiver) = mpsc::channel();
+    for _ in 0..10 {
+        let sender = sender.clone();
+        pool.execute(move || {
+            sender
+                .send(thread::current().name().map(String::from))
+                .unwrap()
+        })
+        .unwrap();
+    }
+    let names: HashSet<_> = (0..10)
+        .map(|_| receiver.recv_timeout(Duration::from_secs(10)).unwrap())
+        .collect();
+
+    assert_eq!(finished.load(Ordering::SeqCst), 8);
+    assert_eq!(names.len(), 1);
+}
+
+#[test]
+fn resized_pool_can_grow_again() {
+    let pool = ThreadPool::new(3);
+    pool.set_workers(1).unwrap();
+    pool.set_workers(3).unwrap();
+
+    assert_eq!(pool.workers(), 3);
+    assert!(run_concurrently(&pool, 3));
+}
+
+#[test]
+fn full_queue_is_reported_without_blocking() {
+    let pool = ThreadPool::with_capacity(1, 1);
+    let (release, blocked) = mpsc::channel::<()>();
+    let (started, running) = mpsc::channel();
+    pool.execute(move || {
+        started.send(()).unwrap();
+        let _ = blocked.recv();
+    })
+    .unwrap();
+    running.recv_timeout(Duration::from_secs(5)).unwrap();
+
+    pool.execute(|| {}).unwrap();
+    assert!(matches!(
+        pool.execute(|| {}),
+        Err(ThreadPoolError::QueueFull)
+    ));
+
+    drop(release);
+}
+
+/// 发一个 `Connection: close` 的 GET 请求，返回状态行
+fn status_line(addr: &str, path: &str) -> String {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    write!(
+        stream,
+        "GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+        path
+    )
+    .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    response.lines().next().unwrap_or_default().to_string()
+}
+
+#[test]
+fn panicking_routes_leave_every_worker_serving() {
+    const WORKERS: usize = 2;
+
+    let mut router = Router::new();
+    router
+        .get("/boom", |_, _| panic!("deliberate panic"))
+        .get("/ok", |_, _| Ok(Response::new(200).body_text("ok")));
+    let router = Arc::new(router);
+    let config = Arc::new(ServerConfig::default());
+    let middleware = Arc::new(MiddlewareStack::new());
+    let logger = Arc::new(Mutex::new(Logger::new(io::sink())));
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    let pool = Arc::new(ThreadPool::new(WORKERS));
+    let server_pool = Arc::clone(&pool);
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let stream = stream.unwrap();
+            let (config, router, middleware, logger) = (
+                Arc::clone(&config),
+                Arc::clone(&router),
+                Arc::clone(&middleware),
+                Arc::clone(&logger),
+            );
+            server_pool
+                .execute(move || handle_connection(stream, &config, &router, &middleware, &logger))
+                .unwrap();
+        }
+    });
+
+    for _ in 0..10 {
+        assert_eq!(
+            status_line(&addr, "/boom"),
+            "HTTP/1.1 500 Internal Server Error"
+        );
+    }
+    for _ in 0..4 {
+        assert_eq!(status_line(&addr, "/ok"), "HTTP/1.1 200 OK");
+    }
+    assert_eq!(pool.workers(), WORKERS);
+    assert!(run_concurrently(&pool, WORKERS));
+}
+
+/// 不同队列深度下处理大量短任务的吞吐量，用 `cargo test --release -- --ignored --nocapture` 运行
+#[test]
+#[ignore]
+fn queue_depth_throughput() {
+    const JOBS: usize = 200_000;
+
+    for capacity in [1, 10, 40, 160, 1000] {
+        let pool = ThreadPool::with_capacity(4, capacity);
+        let done = Arc::new(AtomicUsize::new(0));
+        let mut rejected = 0;
+        let start = Instant::now();
+
+        for _ in 0..JOBS {
+            let done = Arc::clone(&done);
+            let job = move || {
+                done.fetch_add(1, Ordering::Relaxed);
+            };
+            // 与接受连接的循环不同，这里队列满时重试，统计被拒绝的次数
+            loop {
+                match pool.execute(job.clone()) {
+                    Ok(()) => break,
+                    Err(ThreadPoolError::QueueFull) => {
+                        rejected += 1;
+                        thread::yield_now();
+                    }
+                    Err(err) => panic!("{}", err),
+                }
+            }
+        }
+        drop(pool);
+
+        let elapsed = start.elapsed();
+        assert_eq!(done.load(Ordering::Relaxed), JOBS);
+        println!(
+            "capacity {:>4}: {:>10.0} jobs/s, {} rejected submissions",
+            capacity,
+            JOBS as f64 / elapsed.as_secs_f64(),
+            rejected
+        );
+    }
+}
@@ -0,0 +1,98 @@
+mod common;
+
+use common::{read_head, request, Response, Server};
+use std::{
+    io::{BufReader, Read, Write},
+    net::TcpStream,
+    time::{Duration, Instant},
+};
+
+const UPGRADE: &[&str] = &[
+    "Connection: Upgrade",
+    "Upgrade: websocket",
+    "Sec-WebSocket-Version: 13",
+    "Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==",
+];
+
+/// 客户端发送的带掩码的短帧
+fn masked(first: u8, payload: &[u8]) -> Vec<u8> {
+    let mask = [1, 2, 3, 4];
+    let mut frame = vec![first, 0x80 | payload.len() as u8];
+    frame.extend_from_slice(&mask);
+    frame.extend(payload.iter().enumerate().map(|(i, b)| b ^ mask[i % 4]));
+    frame
+}
+
+/// 读取一个服务器发来的短帧，返回第一个字节和负载
+fn read_frame(reader: &mut impl Read) -> (u8, Vec<u8>) {
+    let mut header = [0; 2];
+    reader.read_exact(&mut header).unwrap();
+    assert_eq!(header[1] & 0x80, 0, "server frames are not masked");
+    let mut payload = vec![0; usize::from(header[1])];
+    reader.read_exact(&mut payload).unwrap();
+    (header[0], payload)
+}
+
+/// 在 `path` 上发起升级请求，返回响应头和连接
+fn open(server: &Server, path: &str) -> (Response, BufReader<TcpStream>) {
+    let mut stream = server.connect();
+    let upgrade = request("GET", path, UPGRADE, b"").replace("Connection: close\r\n", "");
+    stream.write_all(upgrade.as_bytes()).unwrap();
+    let mut reader = BufReader::new(stream);
+    let response = read_head(&mut reader);
+    (response, reader)
+}
+
+#[test]
+fn echoes_text_on_the_configured_path() {
+    let server = Server::start(&["--websocket", "/ws"]);
+    let (response, mut reader) = open(&server, "/ws");
+    assert_eq!(response.status, 101);
+    assert_eq!(
+        response.header("sec-websocket-accept"),
+        Some("s3pPLMBiTxaQ9kYGzzhZRbK+xOo=")
+    );
+
+    reader.get_mut().write_all(&masked(0x81, b"hello")).unwrap();
+    assert_eq!(read_frame(&mut reader), (0x81, b"hello".to_vec()));
+
+    reader
+        .get_mut()
+        .write_all(&masked(0x88, &1000_u16.to_be_bytes()))
+        .unwrap();
+    assert_eq!(
+        read_frame(&mut reader),
+        (0x88, 1000_u16.to_be_bytes().to_vec())
+    );
+}
+
+#[test]
+fn upgrades_are_refused_unless_enabled_for_the_path() {
+    let server = Server::start(&[]);
+    assert_ne!(open(&server, "/ws").0.status, 101);
+
+    let server = Server::start(&["--websocket", "/ws"]);
+    assert_ne!(open(&server, "/other").0.status, 101);
+    assert_eq!(open(&server, "/ws").0.status, 101);
+}
+
+#[test]
+fn idle_connections_are_pinged_then_closed() {
+    let server = Server::start(&["--websocket", "/ws", "--websocket-idle-timeout", "1"]);
+    let (response, mut reader) = open(&server, "/ws");
+    assert_eq!(response.status, 101);
+
+    // 回复 Pong 后连接保持打开，下一次空闲时再次收到 Ping
+    let started = Instant::now();
+    assert_eq!(read_frame(&mut reader), (0x89, Vec::new()));
+    assert!(started.elapsed() >= Duration::from_millis(900));
+    reader.get_mut().write_all(&masked(0x8A, b"")).unwrap();
+    assert_eq!(read_frame(&mut reader), (0x89, Vec::new()));
+
+    // 不回复时以 1001 关闭
+    let (first, payload) = read_frame(&mut reader);
+    assert_eq!(first, 0x88);
+    assert_eq!(payload[..2], 1001_u16.to_be_bytes());
+    let mut rest = Vec::new();
+    assert_eq!(reader.read_to_end(&mut rest).unwrap(), 0);
+}